@@ -36,15 +36,20 @@ fn main() {
     {
         let data_sets = get_frame_datasets();
         frame_decompress(&data_sets);
+        frame_decompress_checksums();
         frame_compress(InputGroup::new_with_inputs(data_sets));
     }
 
     let named_data = ALL
         .iter()
         .map(|data| (data.len().to_string(), data.to_vec()))
+        .chain(repetitive_datasets())
         .collect();
     block_compress(InputGroup::new_with_inputs(named_data));
+    block_compress_dict();
     block_decompress();
+    block_decompress_max_distance();
+    block_compress_within();
 }
 
 #[cfg(feature = "frame")]
@@ -65,6 +70,14 @@ fn frame_decompress(data_sets: &[(String, Vec<u8>)]) {
             black_box(lz4_flex_frame_decompress(i).unwrap());
             Some(())
         });
+        group.register_with_input(
+            "lz4 flex independent slice",
+            &compressed_independent,
+            move |i| {
+                black_box(lz4_flex::frame::decompress_frame_slice(i).unwrap());
+                Some(())
+            },
+        );
         group.register_with_input("lz4 c90 independent", &compressed_independent, move |i| {
             black_box(lz4_cpp_frame_decompress(i).unwrap());
             Some(())
@@ -99,6 +112,23 @@ fn frame_compress(mut runner: InputGroup<Vec<u8>, usize>) {
         let out = black_box(lz4_flex_frame_compress_with(frame_info, i).unwrap());
         Some(out.len())
     });
+    runner.register("lz4 flex independent adaptive", move |i| {
+        let mut frame_info = lz4_flex::frame::FrameInfo::new();
+        frame_info.block_size = lz4_flex::frame::BlockSize::Max256KB;
+        frame_info.block_mode = lz4_flex::frame::BlockMode::Independent;
+        let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+        enc.set_adaptive(true);
+        enc.write_all(i).unwrap();
+        let out = black_box(enc.finish().unwrap());
+        Some(out.len())
+    });
+    runner.register("lz4 flex independent borrowed", move |i| {
+        let mut frame_info = lz4_flex::frame::FrameInfo::new();
+        frame_info.block_size = lz4_flex::frame::BlockSize::Max256KB;
+        frame_info.block_mode = lz4_flex::frame::BlockMode::Independent;
+        let out = black_box(lz4_flex_frame_compress_borrowed(frame_info, 256 * 1024, i).unwrap());
+        Some(out.len())
+    });
     runner.register("lz4 c90 indep", move |i| {
         let out = black_box(lz4_cpp_frame_compress(i, true).unwrap());
         Some(out.len())
@@ -110,6 +140,13 @@ fn frame_compress(mut runner: InputGroup<Vec<u8>, usize>) {
         let out = black_box(lz4_flex_frame_compress_with(frame_info, i).unwrap());
         Some(out.len())
     });
+    runner.register("lz4 flex linked borrowed", move |i| {
+        let mut frame_info = lz4_flex::frame::FrameInfo::new();
+        frame_info.block_size = lz4_flex::frame::BlockSize::Max256KB;
+        frame_info.block_mode = lz4_flex::frame::BlockMode::Linked;
+        let out = black_box(lz4_flex_frame_compress_borrowed(frame_info, 256 * 1024, i).unwrap());
+        Some(out.len())
+    });
     runner.register("lz4 c90 linked", move |i| {
         let out = black_box(lz4_cpp_frame_compress(i, false).unwrap());
         Some(out.len())
@@ -144,6 +181,38 @@ fn block_compress(mut runner: InputGroup<Vec<u8>, usize>) {
     runner.run();
 }
 
+/// Compresses 10k small records against a 64KB dictionary.
+fn block_compress_dict() {
+    let mut runner = BenchRunner::with_name("block_compress_dict");
+    runner.add_plugin(PeakMemAllocPlugin::new(&GLOBAL));
+    let dict = &COMPRESSION66K[..64 * 1024];
+    let records: Vec<&[u8]> = COMPRESSION10MB.chunks(200).take(10_000).collect();
+    let input_size = records.iter().map(|record| record.len()).sum();
+
+    let mut group = runner.new_group();
+    group.set_name("10k records 64KB dict");
+    group.set_input_size(input_size);
+    group.register_with_input("compress_with_dict", &records, move |records| {
+        let mut size = 0;
+        for record in records {
+            size += black_box(lz4_flex::block::compress_with_dict(record, dict)).len();
+        }
+        Some(size)
+    });
+    let prepared = lz4_flex::block::PreparedDict::new(dict);
+    group.register_with_input("compress_with_prepared_dict", &records, move |records| {
+        let mut size = 0;
+        for record in records {
+            size += black_box(lz4_flex::block::compress_with_prepared_dict(
+                record, &prepared,
+            ))
+            .len();
+        }
+        Some(size)
+    });
+    group.run();
+}
+
 fn block_decompress() {
     let mut runner = BenchRunner::with_name("block_decompress");
     // Set the peak mem allocator. This will enable peak memory reporting.
@@ -170,6 +239,84 @@ fn block_decompress() {
     }
 }
 
+/// Decompression of blocks compressed with a limited match distance.
+fn block_decompress_max_distance() {
+    let mut runner = BenchRunner::with_name("block_decompress_max_distance");
+    runner.add_plugin(PeakMemAllocPlugin::new(&GLOBAL));
+    let mut group = runner.new_group();
+    group.set_name(COMPRESSION66K.len().to_string());
+    group.set_input_size(COMPRESSION66K.len());
+    for (name, max_distance) in [("4KB", 4 * 1024), ("64KB", u16::MAX)] {
+        let compressed = lz4_flex::block::compress_with_max_distance(COMPRESSION66K, max_distance);
+        group.register_with_input(
+            format!("lz4 flex max distance {name}"),
+            &compressed,
+            move |i| {
+                let size = black_box(lz4_flex::decompress(i, COMPRESSION66K.len()).unwrap());
+                Some(size.len())
+            },
+        );
+    }
+    group.run();
+}
+
+/// Compression of incompressible input, which exceeds a tight output budget early.
+fn block_compress_within() {
+    let mut runner = BenchRunner::with_name("block_compress_within");
+    runner.add_plugin(PeakMemAllocPlugin::new(&GLOBAL));
+    let mut group = runner.new_group();
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let random: Vec<u8> = (0..1 << 20)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    group.set_name("1MB random");
+    group.set_input_size(random.len());
+    group.register_with_input("compress", &random, move |i| {
+        Some(black_box(lz4_flex::block::compress(i)).len())
+    });
+    group.register_with_input("compress_within 64KB budget", &random, move |i| {
+        let result = black_box(lz4_flex::block::compress_within(i, 64 * 1024));
+        Some(result.map_or(0, |compressed| compressed.len()))
+    });
+    group.run();
+}
+
+/// Decompression of a frame with block and content checksums, with and without verification.
+#[cfg(feature = "frame")]
+fn frame_decompress_checksums() {
+    let mut runner = BenchRunner::with_name("frame_decompress_checksums");
+    runner.add_plugin(PeakMemAllocPlugin::new(&GLOBAL));
+    let mut group = runner.new_group();
+    group.set_name(COMPRESSION10MB.len().to_string());
+    group.set_input_size(COMPRESSION10MB.len());
+    let frame_info = lz4_flex::frame::FrameInfo::new()
+        .block_checksums(true)
+        .content_checksum(true);
+    let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+    enc.write_all(COMPRESSION10MB).unwrap();
+    let compressed = enc.finish().unwrap();
+    for (name, verify) in [("verified", true), ("not verified", false)] {
+        group.register_with_input(
+            format!("lz4 flex checksums {name}"),
+            &compressed,
+            move |i| {
+                let mut dec = lz4_flex::frame::FrameDecoder::new(&i[..]);
+                dec.set_verify_block_checksums(verify);
+                dec.set_verify_content_checksum(verify);
+                let mut out = Vec::with_capacity(COMPRESSION10MB.len());
+                dec.read_to_end(&mut out).unwrap();
+                Some(black_box(out).len())
+            },
+        );
+    }
+    group.run();
+}
+
 fn get_frame_datasets() -> Vec<(String, Vec<u8>)> {
     let paths = [
         "compression_1k.txt",
@@ -187,9 +334,37 @@ fn get_frame_datasets() -> Vec<(String, Vec<u8>)> {
             file.read_to_end(&mut buf).unwrap();
             (path.to_string(), buf)
         })
+        .chain(std::iter::once((
+            "mixed_text_random".to_string(),
+            mixed_text_random(),
+        )))
         .collect()
 }
 
+/// Long runs, which are compressed as a few long matches.
+fn repetitive_datasets() -> Vec<(String, Vec<u8>)> {
+    vec![
+        ("1MB zeros".to_string(), vec![0u8; 1 << 20]),
+        (
+            "1MB 16 byte pattern".to_string(),
+            (0..1u32 << 20).map(|i| (i % 16) as u8 * 17).collect(),
+        ),
+    ]
+}
+
+/// Half text, half pseudo random bytes.
+fn mixed_text_random() -> Vec<u8> {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let mut data = COMPRESSION10MB.to_vec();
+    data.extend((0..COMPRESSION10MB.len()).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as u8
+    }));
+    data
+}
+
 fn compress_lz4_fear(input: &[u8]) -> Vec<u8> {
     let mut buf = Vec::new();
     if input.len() <= 0xFFFF {
@@ -278,6 +453,19 @@ pub fn lz4_flex_frame_compress_with(
     enc.finish()
 }
 
+#[cfg(feature = "frame")]
+pub fn lz4_flex_frame_compress_borrowed(
+    frame_info: lz4_flex::frame::FrameInfo,
+    block_size: usize,
+    input: &[u8],
+) -> Result<Vec<u8>, lz4_flex::frame::Error> {
+    let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+    for block in input.chunks(block_size) {
+        enc.write_block_borrowed(block)?;
+    }
+    enc.finish()
+}
+
 #[cfg(feature = "frame")]
 pub fn lz4_flex_frame_decompress(input: &[u8]) -> Result<Vec<u8>, lz4_flex::frame::Error> {
     let mut de = lz4_flex::frame::FrameDecoder::new(input);
@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use lz4_flex::block::{decompress, decompress_small, DecompressError};
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let max_size = u16::from_le_bytes([data[0], data[1]]) as usize;
+    let block = &data[2..];
+
+    match (decompress(block, max_size), decompress_small(block, max_size)) {
+        (Ok(expected), Ok(output)) => assert_eq!(output, expected),
+        (Err(_), Err(_)) => {}
+        // matches with offset 0 are only rejected by decompress_small
+        (Ok(_), Err(err)) => assert!(matches!(err, DecompressError::OffsetOutOfBounds)),
+        (Err(err), Ok(_)) => panic!("only decompress failed with {:?}", err),
+    }
+});
@@ -0,0 +1,28 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use lz4_flex::block::{decompress, decompress_streaming, DecompressError};
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let max_size = u16::from_le_bytes([data[0], data[1]]) as usize * 4;
+    let block = &data[2..];
+
+    let expected = decompress(block, max_size);
+    let mut output = Vec::new();
+    let result = decompress_streaming(block, max_size, |chunk| {
+        output.extend_from_slice(chunk);
+        Ok(())
+    });
+    match (expected, result) {
+        (Ok(expected), Ok(())) => assert_eq!(output, expected),
+        (Err(_), Err(_)) => {}
+        // matches with offset 0 are only rejected by the streaming decoder
+        (Ok(_), Err(err)) => assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(DecompressError::OffsetOutOfBounds)
+        )),
+        (Err(err), Ok(())) => panic!("only decompress failed with {:?}", err),
+    }
+});
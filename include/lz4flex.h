@@ -0,0 +1,66 @@
+#ifndef LZ4FLEX_H
+#define LZ4FLEX_H
+
+/* Generated with cbindgen from src/capi.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * A pointer is null, while its length is not 0.
+ */
+#define LZ4FLEX_ERROR_NULL_POINTER -1
+
+/**
+ * `dst_cap` is too small. For compression, `dst_cap` needs to be at least
+ * `lz4flex_compress_bound(src_len)`.
+ */
+#define LZ4FLEX_ERROR_OUTPUT_TOO_SMALL -2
+
+/**
+ * The compressed data is invalid.
+ */
+#define LZ4FLEX_ERROR_INVALID_INPUT -3
+
+/**
+ * `src` and `dst` overlap.
+ */
+#define LZ4FLEX_ERROR_OVERLAP -4
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the size `dst` needs to have to compress `size` bytes with `lz4flex_compress`, or 0 if
+ * `size` is too large.
+ */
+size_t lz4flex_compress_bound(size_t size);
+
+/**
+ * Compresses the `src_len` bytes at `src` into a block at `dst`, which has room for `dst_cap`
+ * bytes. Returns the number of bytes written, or a negative `LZ4FLEX_ERROR_*` code.
+ *
+ * # Safety
+ * `src` has to be valid for reads of `src_len` bytes and `dst` for writes of `dst_cap` bytes.
+ * They may be null if their length is 0. The two ranges must not overlap, which is checked and
+ * reported as `LZ4FLEX_ERROR_OVERLAP`.
+ */
+ptrdiff_t lz4flex_compress(const uint8_t *src, size_t src_len, uint8_t *dst, size_t dst_cap);
+
+/**
+ * Decompresses the block of `src_len` bytes at `src` into `dst`, which has room for `dst_cap`
+ * bytes. Returns the number of bytes written, or a negative `LZ4FLEX_ERROR_*` code.
+ *
+ * # Safety
+ * `src` has to be valid for reads of `src_len` bytes and `dst` for writes of `dst_cap` bytes.
+ * They may be null if their length is 0. The two ranges must not overlap, which is checked and
+ * reported as `LZ4FLEX_ERROR_OVERLAP`.
+ */
+ptrdiff_t lz4flex_decompress(const uint8_t *src, size_t src_len, uint8_t *dst, size_t dst_cap);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LZ4FLEX_H */
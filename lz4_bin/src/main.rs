@@ -144,17 +144,17 @@ fn handle_file(
         let mut in_file = File::open(file)?;
 
         let out_file = File::create(&output)?;
-        let mut compressor = lz4_flex::frame::FrameEncoder::new(TrackWriteSize::new(out_file));
-        let input_size = io::copy(&mut in_file, &mut compressor)?;
+        let mut compressor = lz4_flex::frame::FrameEncoder::new(out_file);
+        io::copy(&mut in_file, &mut compressor)?;
 
-        let output_size = compressor.finish()?.written;
+        let (_, stats) = compressor.finish_with_stats()?;
 
         if print_info {
             println!(
                 "Compressed {} bytes into {} ==> {:.2}%",
-                input_size,
-                output_size,
-                output_size as f32 * 100.0 / input_size as f32
+                stats.input_bytes,
+                stats.output_bytes,
+                stats.output_bytes as f32 * 100.0 / stats.input_bytes as f32
             );
         }
     }
@@ -165,33 +165,6 @@ fn handle_file(
     Ok(())
 }
 
-struct TrackWriteSize<W: io::Write> {
-    inner: W,
-    written: u64,
-}
-impl<W: io::Write> TrackWriteSize<W> {
-    fn new(inner: W) -> Self {
-        TrackWriteSize { inner, written: 0 }
-    }
-}
-impl<W: io::Write> io::Write for TrackWriteSize<W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let written = self.inner.write(buf)?;
-        self.written += written as u64;
-        Ok(written)
-    }
-
-    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-        let written = self.inner.write_vectored(bufs)?;
-        self.written += written as u64;
-        Ok(written)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        self.inner.flush()
-    }
-}
-
 pub fn lz4_flex_frame_compress_with(
     frame_info: lz4_flex::frame::FrameInfo,
     input: &[u8],
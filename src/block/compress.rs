@@ -10,55 +10,257 @@ use crate::block::LZ4_MIN_LENGTH;
 use crate::block::MAX_DISTANCE;
 use crate::block::MFLIMIT;
 use crate::block::MINMATCH;
-#[cfg(not(feature = "safe-encode"))]
+#[cfg(all(feature = "alloc", feature = "safe-encode"))]
+use crate::sink::CountingSink;
+#[cfg(all(feature = "alloc", not(feature = "safe-encode")))]
 use crate::sink::PtrSink;
 use crate::sink::Sink;
 use crate::sink::SliceSink;
+#[cfg(feature = "alloc")]
 #[allow(unused_imports)]
 use alloc::vec;
+use core::ops::Range;
 
+#[cfg(feature = "alloc")]
 #[allow(unused_imports)]
 use alloc::vec::Vec;
 
+#[cfg(feature = "alloc")]
 use super::hashtable::HashTable4K;
+use super::hashtable::HashTable4KInline;
+#[cfg(feature = "alloc")]
 use super::hashtable::HashTable4KU16;
+#[cfg(feature = "alloc")]
+use super::hashtable::HashTable4KU16Hash5;
+use super::hashtable::HashTable4KU16Inline;
 use super::{CompressError, WINDOW_SIZE};
+#[cfg(feature = "alloc")]
+use super::{SizeTag, MAX_TAGGED_HEADER_SIZE};
+
+/// The hash tables of the functions compressing into a slice, for inputs (including the
+/// dictionary) smaller than 64KB and for larger inputs. Without the `alloc` feature, they are
+/// stored on the stack instead of the heap.
+#[cfg(feature = "alloc")]
+type SliceTableU16 = HashTable4KU16;
+#[cfg(feature = "alloc")]
+type SliceTable = HashTable4K;
+#[cfg(not(feature = "alloc"))]
+type SliceTableU16 = HashTable4KU16Inline;
+#[cfg(not(feature = "alloc"))]
+type SliceTable = HashTable4KInline;
 
 /// Increase step size after 1<<INCREASE_STEPSIZE_BITSHIFT non matches
-const INCREASE_STEPSIZE_BITSHIFT: usize = 5;
+const INCREASE_STEPSIZE_BITSHIFT: u8 = 5;
+
+/// Upper bound for `CompressOptions::step_size_bitshift`, so that the non match counter can't
+/// overflow.
+const MAX_STEPSIZE_BITSHIFT: u8 = 24;
+
+/// Options to tune the block compression.
+///
+/// The default options produce the same output as [`compress`]. More options may be added in
+/// future versions, so create them with [`CompressOptions::new`] and the builder methods:
+///
+/// ```
+/// use lz4_flex::block::CompressOptions;
+/// let options = CompressOptions::new().max_distance(4096).long_hash(true);
+/// # assert_eq!(options.max_distance, 4096);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct CompressOptions {
+    /// The step size of the match search increases by one after `1 << step_size_bitshift`
+    /// positions without a match. Lower values skip incompressible data faster at the cost of
+    /// compression ratio, higher values search more thoroughly. Values above 24 are treated as 24.
+    ///
+    /// Defaults to 5.
+    pub step_size_bitshift: u8,
+    /// The maximum offset of matches. Lower values keep back references within the CPU cache
+    /// during decompression, at the cost of compression ratio.
+    ///
+    /// Defaults to `u16::MAX`, the maximum offset of the LZ4 block format.
+    pub max_distance: u16,
+    /// Hash 5 instead of 4 bytes to find match candidates for inputs smaller than 64KB. This
+    /// tends to improve the compression ratio of text, but not of very small inputs.
+    ///
+    /// Inputs of 64KB and more are always hashed with 5 bytes. Defaults to `false`.
+    pub long_hash: bool,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl CompressOptions {
+    const DEFAULT: CompressOptions = CompressOptions {
+        step_size_bitshift: INCREASE_STEPSIZE_BITSHIFT,
+        max_distance: MAX_DISTANCE as u16,
+        long_hash: false,
+    };
+
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `step_size_bitshift`, see the field documentation.
+    pub fn step_size_bitshift(mut self, step_size_bitshift: u8) -> Self {
+        self.step_size_bitshift = step_size_bitshift;
+        self
+    }
+
+    /// Sets `max_distance`, see the field documentation.
+    pub fn max_distance(mut self, max_distance: u16) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Sets `long_hash`, see the field documentation.
+    pub fn long_hash(mut self, long_hash: bool) -> Self {
+        self.long_hash = long_hash;
+        self
+    }
+}
+
+/// Named presets for [`compress_level`], trading compression speed for ratio.
+///
+/// Approximate compression ratios on the benchmark corpora (34KB text, 66KB JSON, 10MB text):
+///
+/// | Level     | 34KB text | 66KB JSON | 10MB text |
+/// |-----------|-----------|-----------|-----------|
+/// | `Fastest` | 1.68      | 4.28      | 1.49      |
+/// | `Default` | 1.73      | 4.37      | 1.57      |
+/// | `Better`  | 1.75      | 4.38      | 1.57      |
+/// | `Best`    | 1.75      | 4.38      | 1.57      |
+///
+/// `Fastest` skips incompressible data more aggressively and is around 10% faster on text.
+/// There is no high compression mode yet, so `Best` only searches more thoroughly than `Better`
+/// on poorly compressible data. The output of a level may change between versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum CompressionLevel {
+    /// Favor speed over compression ratio.
+    Fastest,
+    /// The same as [`compress`].
+    #[default]
+    Default,
+    /// Favor compression ratio over speed.
+    Better,
+    /// The best compression ratio available.
+    Best,
+}
+
+impl CompressionLevel {
+    /// Returns the [`CompressOptions`] used for this level.
+    pub fn options(self) -> CompressOptions {
+        match self {
+            CompressionLevel::Fastest => CompressOptions::new().step_size_bitshift(2),
+            CompressionLevel::Default => CompressOptions::new(),
+            CompressionLevel::Better => {
+                CompressOptions::new().step_size_bitshift(8).long_hash(true)
+            }
+            CompressionLevel::Best => CompressOptions::new()
+                .step_size_bitshift(MAX_STEPSIZE_BITSHIFT)
+                .long_hash(true),
+        }
+    }
+}
+
+/// What [`auto_tune`] optimizes for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TuneTarget {
+    /// The smallest output.
+    MaxRatio,
+    /// The fastest options which compress almost as well as the best ones.
+    Balanced,
+    /// The fastest options which still find most matches.
+    MaxSpeed,
+}
+
+/// Tolerated loss of compression ratio for [`TuneTarget::Balanced`], in thousandths of the
+/// smallest output.
+#[cfg(feature = "alloc")]
+const BALANCED_TOLERANCE: usize = 20;
+
+/// Tolerated loss of compression ratio for [`TuneTarget::MaxSpeed`], in thousandths of the
+/// smallest output.
+#[cfg(feature = "alloc")]
+const MAX_SPEED_TOLERANCE: usize = 100;
+
+/// Picks the [`CompressOptions`] for data like `sample`, to reuse for compressing the same kind
+/// of data repeatedly.
+///
+/// `sample` is compressed with each [`CompressionLevel`], from the fastest to the slowest. The
+/// result is the fastest level whose output is at most 2% (`Balanced`) or 10% (`MaxSpeed`)
+/// larger than the smallest one, or the smallest output for `MaxRatio`. The speed isn't timed,
+/// which would make the result depend on the machine and its load, so the levels are ranked by
+/// their speed on the benchmark corpora. There is no high compression mode yet, so `MaxRatio`
+/// can only pick the most thorough search.
+///
+/// A sample of a few hundred KB is representative for most data, as inputs of 64KB and more are
+/// all hashed alike.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{auto_tune, compress_with_options, TuneTarget};
+/// let sample = b"some text, which is similar to the data compressed later. ".repeat(100);
+/// let options = auto_tune(&sample, TuneTarget::Balanced);
+/// let compressed = compress_with_options(b"some text, which is similar", &options);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn auto_tune(sample: &[u8], target: TuneTarget) -> CompressOptions {
+    const LEVELS: [CompressionLevel; 4] = [
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Better,
+        CompressionLevel::Best,
+    ];
+    let lens = LEVELS.map(|level| compress_with_options(sample, &level.options()).len());
+    let smallest = *lens.iter().min().unwrap();
+    let tolerance = match target {
+        TuneTarget::MaxRatio => 0,
+        TuneTarget::Balanced => BALANCED_TOLERANCE,
+        TuneTarget::MaxSpeed => MAX_SPEED_TOLERANCE,
+    };
+    let acceptable = smallest + smallest * tolerance / 1000;
+    let fastest = lens.iter().position(|&len| len <= acceptable).unwrap();
+    LEVELS[fastest].options()
+}
 
 /// Read a 4-byte "batch" from some position.
 ///
-/// This will read a native-endian 4-byte integer from some position.
+/// This will read a little-endian 4-byte integer from some position.
+///
+/// The batches are hashed to find match candidates. Reading them as little-endian regardless of
+/// the host makes the hash values, and therefore the compressed output, identical on little- and
+/// big-endian targets. On little-endian targets this is a plain load.
 #[inline]
 #[cfg(not(feature = "safe-encode"))]
 pub(super) fn get_batch(input: &[u8], n: usize) -> u32 {
-    unsafe { read_u32_ptr(input.as_ptr().add(n)) }
+    u32::from_le(unsafe { read_u32_ptr(input.as_ptr().add(n)) })
 }
 
 #[inline]
 #[cfg(feature = "safe-encode")]
 pub(super) fn get_batch(input: &[u8], n: usize) -> u32 {
-    u32::from_ne_bytes(input[n..n + 4].try_into().unwrap())
+    u32::from_le_bytes(input[n..n + 4].try_into().unwrap())
 }
 
-/// Read an usize sized "batch" from some position.
+/// Read an 8-byte "batch" from some position.
 ///
-/// This will read a native-endian usize from some position.
+/// This will read a little-endian u64 from some position, see `get_batch`.
 #[inline]
-#[allow(dead_code)]
 #[cfg(not(feature = "safe-encode"))]
-pub(super) fn get_batch_arch(input: &[u8], n: usize) -> usize {
-    unsafe { read_usize_ptr(input.as_ptr().add(n)) }
+pub(super) fn get_batch_u64(input: &[u8], n: usize) -> u64 {
+    u64::from_le(unsafe { read_u64_ptr(input.as_ptr().add(n)) })
 }
 
 #[inline]
-#[allow(dead_code)]
 #[cfg(feature = "safe-encode")]
-pub(super) fn get_batch_arch(input: &[u8], n: usize) -> usize {
-    const USIZE_SIZE: usize = core::mem::size_of::<usize>();
-    let arr: &[u8; USIZE_SIZE] = input[n..n + USIZE_SIZE].try_into().unwrap();
-    usize::from_ne_bytes(*arr)
+pub(super) fn get_batch_u64(input: &[u8], n: usize) -> u64 {
+    u64::from_le_bytes(input[n..n + 8].try_into().unwrap())
 }
 
 #[inline]
@@ -95,25 +297,25 @@ fn token_from_literal_and_match_length(lit_len: usize, duplicate_length: usize)
     token
 }
 
-/// Counts the number of same bytes in two byte streams.
-/// `input` is the complete input
-/// `cur` is the current position in the input. it will be incremented by the number of matched
-/// bytes `source` either the same as input or an external slice
-/// `candidate` is the candidate position in `source`
+/// Returns the length of the common prefix of `a` and `b`, i.e. the number of leading bytes which
+/// are equal in both.
 ///
-/// The function ignores the last END_OFFSET bytes in input as those should be literals.
+/// This compares a word at a time, like the match finder of the compressor does to determine the
+/// length of matches, e.g. for building diff or deduplication tools.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::common_prefix_len;
+/// assert_eq!(common_prefix_len(b"compressed", b"compression"), 8);
+/// assert_eq!(common_prefix_len(b"lz4", b"lz4_flex"), 3);
+/// ```
 #[inline]
 #[cfg(feature = "safe-encode")]
-fn count_same_bytes(input: &[u8], cur: &mut usize, source: &[u8], candidate: usize) -> usize {
+pub fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
     const USIZE_SIZE: usize = core::mem::size_of::<usize>();
-    let cur_slice = &input[*cur..input.len() - END_OFFSET];
-    let cand_slice = &source[candidate..];
 
     let mut num = 0;
-    for (block1, block2) in cur_slice
-        .chunks_exact(USIZE_SIZE)
-        .zip(cand_slice.chunks_exact(USIZE_SIZE))
-    {
+    for (block1, block2) in a.chunks_exact(USIZE_SIZE).zip(b.chunks_exact(USIZE_SIZE)) {
         let input_block = usize::from_ne_bytes(block1.try_into().unwrap());
         let match_block = usize::from_ne_bytes(block2.try_into().unwrap());
 
@@ -122,7 +324,6 @@ fn count_same_bytes(input: &[u8], cur: &mut usize, source: &[u8], candidate: usi
         } else {
             let diff = input_block ^ match_block;
             num += (diff.to_le().trailing_zeros() / 8) as usize;
-            *cur += num;
             return num;
         }
     }
@@ -131,88 +332,120 @@ fn count_same_bytes(input: &[u8], cur: &mut usize, source: &[u8], candidate: usi
     // or source slices. Since this is rare occurrence we mark it cold to get better
     // ~5% better performance.
     #[cold]
-    fn count_same_bytes_tail(a: &[u8], b: &[u8], offset: usize) -> usize {
+    fn common_prefix_len_tail(a: &[u8], b: &[u8], offset: usize) -> usize {
         a.iter()
             .zip(b)
             .skip(offset)
             .take_while(|(a, b)| a == b)
             .count()
     }
-    num += count_same_bytes_tail(cur_slice, cand_slice, num);
-
-    *cur += num;
-    num
+    num + common_prefix_len_tail(a, b, num)
 }
 
-/// Counts the number of same bytes in two byte streams.
-/// `input` is the complete input
-/// `cur` is the current position in the input. it will be incremented by the number of matched
-/// bytes `source` either the same as input OR an external slice
-/// `candidate` is the candidate position in `source`
+/// Returns the length of the common prefix of `a` and `b`, i.e. the number of leading bytes which
+/// are equal in both.
 ///
-/// The function ignores the last END_OFFSET bytes in input as those should be literals.
+/// This compares a word at a time, like the match finder of the compressor does to determine the
+/// length of matches, e.g. for building diff or deduplication tools.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::common_prefix_len;
+/// assert_eq!(common_prefix_len(b"compressed", b"compression"), 8);
+/// assert_eq!(common_prefix_len(b"lz4", b"lz4_flex"), 3);
+/// ```
 #[inline]
 #[cfg(not(feature = "safe-encode"))]
-fn count_same_bytes(input: &[u8], cur: &mut usize, source: &[u8], candidate: usize) -> usize {
-    let max_input_match = input.len().saturating_sub(*cur + END_OFFSET);
-    let max_candidate_match = source.len() - candidate;
-    // Considering both limits calc how far we may match in input.
-    let input_end = *cur + max_input_match.min(max_candidate_match);
-
-    let start = *cur;
-    let mut source_ptr = unsafe { source.as_ptr().add(candidate) };
+pub fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    let len = a.len().min(b.len());
+    let mut a_ptr = a.as_ptr();
+    let mut b_ptr = b.as_ptr();
+    let mut num = 0;
 
     // compare 4/8 bytes blocks depending on the arch
     const STEP_SIZE: usize = core::mem::size_of::<usize>();
-    while *cur + STEP_SIZE <= input_end {
-        let diff = read_usize_ptr(unsafe { input.as_ptr().add(*cur) }) ^ read_usize_ptr(source_ptr);
+    while num + STEP_SIZE <= len {
+        let diff = read_usize_ptr(a_ptr) ^ read_usize_ptr(b_ptr);
 
         if diff == 0 {
-            *cur += STEP_SIZE;
+            num += STEP_SIZE;
             unsafe {
-                source_ptr = source_ptr.add(STEP_SIZE);
+                a_ptr = a_ptr.add(STEP_SIZE);
+                b_ptr = b_ptr.add(STEP_SIZE);
             }
         } else {
-            *cur += (diff.to_le().trailing_zeros() / 8) as usize;
-            return *cur - start;
+            return num + (diff.to_le().trailing_zeros() / 8) as usize;
         }
     }
 
     // compare 4 bytes block
     #[cfg(target_pointer_width = "64")]
     {
-        if input_end - *cur >= 4 {
-            let diff = read_u32_ptr(unsafe { input.as_ptr().add(*cur) }) ^ read_u32_ptr(source_ptr);
+        if len - num >= 4 {
+            let diff = read_u32_ptr(a_ptr) ^ read_u32_ptr(b_ptr);
 
             if diff == 0 {
-                *cur += 4;
+                num += 4;
                 unsafe {
-                    source_ptr = source_ptr.add(4);
+                    a_ptr = a_ptr.add(4);
+                    b_ptr = b_ptr.add(4);
                 }
             } else {
-                *cur += (diff.to_le().trailing_zeros() / 8) as usize;
-                return *cur - start;
+                return num + (diff.to_le().trailing_zeros() / 8) as usize;
             }
         }
     }
 
     // compare 2 bytes block
-    if input_end - *cur >= 2
-        && unsafe { read_u16_ptr(input.as_ptr().add(*cur)) == read_u16_ptr(source_ptr) }
-    {
-        *cur += 2;
+    if len - num >= 2 && read_u16_ptr(a_ptr) == read_u16_ptr(b_ptr) {
+        num += 2;
         unsafe {
-            source_ptr = source_ptr.add(2);
+            a_ptr = a_ptr.add(2);
+            b_ptr = b_ptr.add(2);
         }
     }
 
-    if *cur < input_end
-        && unsafe { input.as_ptr().add(*cur).read() } == unsafe { source_ptr.read() }
-    {
-        *cur += 1;
+    if num < len && unsafe { a_ptr.read() == b_ptr.read() } {
+        num += 1;
     }
 
-    *cur - start
+    num
+}
+
+/// Counts the number of same bytes in two byte streams.
+/// `input` is the complete input
+/// `cur` is the current position in the input. it will be incremented by the number of matched
+/// bytes `source` either the same as input or an external slice
+/// `candidate` is the candidate position in `source`
+///
+/// The function ignores the last END_OFFSET bytes in input as those should be literals.
+#[inline]
+#[cfg(feature = "safe-encode")]
+fn count_same_bytes(input: &[u8], cur: &mut usize, source: &[u8], candidate: usize) -> usize {
+    let num = common_prefix_len(&input[*cur..input.len() - END_OFFSET], &source[candidate..]);
+    *cur += num;
+    num
+}
+
+/// Counts the number of same bytes in two byte streams.
+/// `input` is the complete input
+/// `cur` is the current position in the input. it will be incremented by the number of matched
+/// bytes `source` either the same as input OR an external slice
+/// `candidate` is the candidate position in `source`
+///
+/// The function ignores the last END_OFFSET bytes in input as those should be literals.
+#[inline]
+#[cfg(not(feature = "safe-encode"))]
+fn count_same_bytes(input: &[u8], cur: &mut usize, source: &[u8], candidate: usize) -> usize {
+    let input_end = input.len().saturating_sub(END_OFFSET).max(*cur);
+    let num = unsafe {
+        common_prefix_len(
+            input.get_unchecked(*cur..input_end),
+            source.get_unchecked(candidate..),
+        )
+    };
+    *cur += num;
+    num
 }
 
 /// Write an integer to the output.
@@ -343,18 +576,181 @@ fn backtrack_match(
 /// A similar const argument could be used to disable the Prefix mode (eg. USE_PREFIX),
 /// which would impose `input_pos == 0 && input_stream_offset == 0`. Experiments didn't
 /// show significant improvement though.
+#[inline]
+pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
+    input: &[u8],
+    input_pos: usize,
+    output: &mut S,
+    dict: &mut T,
+    ext_dict: &[u8],
+    input_stream_offset: usize,
+) -> Result<usize, CompressError> {
+    compress_internal_with_options::<T, USE_DICT, LIMIT_NONE, S, _>(
+        input,
+        input_pos,
+        output,
+        dict,
+        ext_dict,
+        input_stream_offset,
+        &CompressOptions::DEFAULT,
+        &mut OutputLimit::new(usize::MAX),
+        &mut (),
+    )
+}
+
+/// Same as `compress_internal` without a dictionary, but the progress is reported to `consume`,
+/// which can also pause and resume the match search. When resuming, `input_pos` is the
+/// `literal_start` of the paused search. Returns the number of bytes written by this call.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn compress_internal_resumable<T: HashTable, S: Sink, C: ConsumeInput>(
+    input: &[u8],
+    input_pos: usize,
+    output: &mut S,
+    dict: &mut T,
+    consume: &mut C,
+) -> Result<usize, CompressError> {
+    compress_internal_with_options::<T, false, LIMIT_NONE, S, _>(
+        input,
+        input_pos,
+        output,
+        dict,
+        b"",
+        0,
+        &CompressOptions::DEFAULT,
+        &mut OutputLimit::new(usize::MAX),
+        consume,
+    )
+}
+
+/// Notified by `compress_internal_with_options` after each sequence, with the end of the input
+/// consumed so far.
+///
+/// The match search can also be paused and resumed with `pause` and `resume`, see `CompressJob`.
+pub(crate) trait ConsumeInput {
+    fn consumed(&mut self, input: &[u8], end: usize);
+
+    /// Returns the state to resume a paused match search from, instead of starting at
+    /// `input_pos`.
+    #[inline(always)]
+    fn resume(&mut self) -> Option<SearchState> {
+        None
+    }
+
+    /// Called before each position of the match search. Returns whether to pause the search at
+    /// `state`, which is then passed back by `resume` to continue with the same output.
+    #[inline(always)]
+    fn pause(&mut self, _state: SearchState) -> bool {
+        false
+    }
+
+    /// Called when the compressor enters `phase`, see `compress_with_analysis`.
+    #[inline(always)]
+    fn phase(&mut self, _phase: Phase) {}
+
+    /// Called with the literals of each sequence before a match, see `compress_with_coverage`.
+    /// The last literals of the block start at the `end` of the last call to `consumed`.
+    #[inline(always)]
+    fn literals(&mut self, _literals: Range<usize>) {}
+
+    /// Inputs shorter than this are written as literals only, without a match search.
+    ///
+    /// Blocks decoded after a prefix can start with a match, so they only need `MFLIMIT` bytes,
+    /// see `compress_dependent_block`.
+    #[inline(always)]
+    fn min_length(&self) -> usize {
+        LZ4_MIN_LENGTH
+    }
+}
+
+/// Phases of `compress_internal_with_options`, reported to `ConsumeInput::phase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    /// Searching a match in the hash table and determining its length.
+    MatchFinding,
+    /// Copying literals to the output.
+    LiteralCopy,
+    /// Writing tokens, offsets and lengths.
+    Output,
+}
+
+impl ConsumeInput for () {
+    #[inline(always)]
+    fn consumed(&mut self, _input: &[u8], _end: usize) {}
+}
+
+/// Position of a paused match search of `compress_internal_with_options`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SearchState {
+    /// The next position to look up in the hash table.
+    pub(crate) cur: usize,
+    /// Start of the literals not written yet.
+    pub(crate) literal_start: usize,
+    /// Counter of positions without a match, which determines the step size.
+    non_match_count: usize,
+}
+
+/// `LIMIT` of `compress_internal_with_options`: The output is not limited, `limit` is ignored,
+/// which keeps the bookkeeping out of the regular compression.
+const LIMIT_NONE: u8 = 0;
+/// `LIMIT` of `compress_internal_with_options`: Returns `CompressError::OutputBudgetExceeded` as
+/// soon as the bytes written to `output` plus the pending literals exceed `limit.max_output`, see
+/// `compress_within`.
+const LIMIT_FAIL: u8 = 1;
+/// `LIMIT` of `compress_internal_with_options`: Stops at a sequence boundary, so that the output
+/// is a valid block of at most `limit.max_output` bytes, and sets `limit.input_end` to the end of
+/// the compressed part of the input, see `compress_dest_size`. `output` doesn't need to hold
+/// `get_maximum_output_size` bytes, only `limit.max_output`.
+const LIMIT_FILL: u8 = 2;
+
+/// Number of bytes reserved after a sequence with `LIMIT_FILL`, for the last sequence of the
+/// block. Its literals have to cover the last `MFLIMIT` bytes of the block, of which the match
+/// before covers at least `MINMATCH` bytes. Also covers the bytes written past the literals by
+/// `copy_literals_wild`.
+const FILL_LAST_SEQUENCE_RESERVE: usize = 1 + MFLIMIT - MINMATCH;
+
+/// Limit of the output size of `compress_internal_with_options`, see `LIMIT_FAIL` and
+/// `LIMIT_FILL`.
+struct OutputLimit {
+    max_output: usize,
+    input_end: usize,
+}
+
+impl OutputLimit {
+    fn new(max_output: usize) -> Self {
+        OutputLimit {
+            max_output,
+            input_end: 0,
+        }
+    }
+}
+
+/// Same as `compress_internal`, but tuned by `options`, with the output size limited according to
+/// `LIMIT`, and the consumed input reported to `consume`.
 // Intentionally avoid inlining.
 // Empirical tests revealed it to be rarely better but often significantly detrimental.
 #[inline(never)]
-pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
+#[allow(clippy::too_many_arguments)]
+fn compress_internal_with_options<
+    T: HashTable,
+    const USE_DICT: bool,
+    const LIMIT: u8,
+    S: Sink,
+    C: ConsumeInput,
+>(
     input: &[u8],
     input_pos: usize,
     output: &mut S,
     dict: &mut T,
     ext_dict: &[u8],
     input_stream_offset: usize,
+    options: &CompressOptions,
+    limit: &mut OutputLimit,
+    consume: &mut C,
 ) -> Result<usize, CompressError> {
     assert!(input_pos <= input.len());
+    let max_distance = options.max_distance as usize;
+    let step_size_bitshift = options.step_size_bitshift.min(MAX_STEPSIZE_BITSHIFT);
     if USE_DICT {
         assert!(ext_dict.len() <= super::WINDOW_SIZE);
         assert!(ext_dict.len() <= input_stream_offset);
@@ -362,24 +758,50 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
         assert!(input_stream_offset
             .checked_add(input.len())
             .and_then(|i| i.checked_add(ext_dict.len()))
-            .map_or(false, |i| i <= isize::MAX as usize));
+            .is_some_and(|i| i <= isize::MAX as usize));
     } else {
         assert!(ext_dict.is_empty());
     }
-    if output.capacity() - output.pos() < get_maximum_output_size(input.len() - input_pos) {
-        return Err(CompressError::OutputTooSmall);
+    let max_output = limit.max_output;
+    let needed = if LIMIT == LIMIT_FILL {
+        max_output
+    } else {
+        get_maximum_output_size(input.len() - input_pos)
+    };
+    let provided = output.capacity() - output.pos();
+    if provided < needed {
+        return Err(CompressError::OutputTooSmall { needed, provided });
     }
 
     let output_start_pos = output.pos();
-    if input.len() - input_pos < LZ4_MIN_LENGTH {
-        handle_last_literals(output, input, input_pos);
-        return Ok(output.pos() - output_start_pos);
+    let resume = consume.resume();
+    if resume.is_none() && input.len() - input_pos < consume.min_length() {
+        consume.phase(Phase::LiteralCopy);
+        return finish_last_literals::<LIMIT>(output, input, input_pos, output_start_pos, limit);
     }
 
     let ext_dict_stream_offset = input_stream_offset - ext_dict.len();
     let end_pos_check = input.len() - MFLIMIT;
     let mut literal_start = input_pos;
     let mut cur = input_pos;
+    let mut resume_non_match_count = None;
+    if let Some(state) = resume {
+        debug_assert!(state.literal_start >= input_pos && state.cur <= end_pos_check);
+        literal_start = state.literal_start;
+        cur = state.cur;
+        resume_non_match_count = Some(state.non_match_count);
+    }
+    // With a limit, the match search stops at `scan_end`, where the pending literals alone would
+    // exceed the limit.
+    let budget_scan_end = |written: usize, literal_start: usize| {
+        if LIMIT == LIMIT_NONE {
+            return end_pos_check;
+        }
+        literal_start
+            .saturating_add(max_output.saturating_sub(written))
+            .min(end_pos_check)
+    };
+    let mut scan_end = budget_scan_end(0, literal_start);
 
     if cur == 0 && input_stream_offset == 0 {
         // According to the spec we can't start with a match,
@@ -395,14 +817,17 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
         let mut candidate;
         let mut candidate_source;
         let mut offset;
-        let mut non_match_count = 1 << INCREASE_STEPSIZE_BITSHIFT;
+        let mut non_match_count = resume_non_match_count
+            .take()
+            .unwrap_or(1 << step_size_bitshift);
         // The number of bytes before our cursor, where the duplicate starts.
         let mut next_cur = cur;
+        consume.phase(Phase::MatchFinding);
 
         // In this loop we search for duplicates via the hashtable. 4bytes or 8bytes are hashed and
         // compared.
         loop {
-            step_size = non_match_count >> INCREASE_STEPSIZE_BITSHIFT;
+            step_size = non_match_count >> step_size_bitshift;
             non_match_count += 1;
 
             cur = next_cur;
@@ -410,7 +835,34 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
 
             // Same as cur + MFLIMIT > input.len()
             if cur > end_pos_check {
-                handle_last_literals(output, input, literal_start);
+                consume.phase(Phase::LiteralCopy);
+                return finish_last_literals::<LIMIT>(
+                    output,
+                    input,
+                    literal_start,
+                    output_start_pos,
+                    limit,
+                );
+            }
+            if LIMIT == LIMIT_FAIL && cur > scan_end {
+                return Err(CompressError::OutputBudgetExceeded { max_output });
+            }
+            if LIMIT == LIMIT_FILL && cur > scan_end {
+                return finish_last_literals::<LIMIT>(
+                    output,
+                    input,
+                    literal_start,
+                    output_start_pos,
+                    limit,
+                );
+            }
+            let state = SearchState {
+                cur,
+                literal_start,
+                // Before the increment above, so that the resumed search takes the same step.
+                non_match_count: non_match_count - 1,
+            };
+            if consume.pause(state) {
                 return Ok(output.pos() - output_start_pos);
             }
             // Find a candidate in the dictionary with the hash of the current four bytes.
@@ -430,8 +882,13 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
             // - We should not return a position which is merely a hash collision, so that the
             //   candidate actually matches what we search for.
             // - We can address up to 16-bit offset, hence we are only able to address the candidate
-            //   if its offset is less than or equals to 0xFFFF.
-            if input_stream_offset + cur - candidate > MAX_DISTANCE {
+            //   if its offset is between 1 and 0xFFFF (`MAX_DISTANCE`), or `max_distance` if it's
+            //   lower. An offset of 0 is invalid, and 0x10000 would be truncated to 0.
+            // Wrapping, so that the current position or a position ahead of `cur` from a broken
+            // user `HashTable` is skipped in release builds, instead of writing an invalid offset
+            // or reading out of bounds.
+            let distance = (input_stream_offset + cur).wrapping_sub(candidate);
+            if distance.wrapping_sub(1) >= max_distance {
                 continue;
             }
 
@@ -491,6 +948,22 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
         let hash = T::get_hash_at(input, cur - 2);
         dict.put_at(hash, cur - 2 + input_stream_offset);
 
+        if LIMIT == LIMIT_FILL {
+            let sequence_size = literal_run_size(lit_len) + 2 + extra_length_size(duplicate_length);
+            let written = output.pos() - output_start_pos;
+            if written + sequence_size + FILL_LAST_SEQUENCE_RESERVE > max_output {
+                // The sequence doesn't fit, end the block with the literals before it instead.
+                return finish_last_literals::<LIMIT>(
+                    output,
+                    input,
+                    literal_start,
+                    output_start_pos,
+                    limit,
+                );
+            }
+        }
+
+        consume.phase(Phase::Output);
         let token = token_from_literal_and_match_length(lit_len, duplicate_length);
 
         // Push the token to the output stream.
@@ -506,7 +979,10 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
         // The unsafe version copies blocks of 8bytes, and therefore may copy up to 7bytes more than
         // needed. This is safe, because the last 12 bytes (MF_LIMIT) are handled in
         // handle_last_literals.
+        consume.phase(Phase::LiteralCopy);
+        consume.literals(literal_start..literal_start + lit_len);
         copy_literals_wild(output, input, literal_start, lit_len);
+        consume.phase(Phase::Output);
         // write the offset in little endian.
         push_u16(output, offset);
 
@@ -516,9 +992,76 @@ pub(crate) fn compress_internal<T: HashTable, const USE_DICT: bool, S: Sink>(
             write_integer(output, duplicate_length - 0xF);
         }
         literal_start = cur;
+        consume.consumed(input, cur);
+        if LIMIT != LIMIT_NONE {
+            let written = output.pos() - output_start_pos;
+            if LIMIT == LIMIT_FAIL && written > max_output {
+                return Err(CompressError::OutputBudgetExceeded { max_output });
+            }
+            scan_end = budget_scan_end(written, literal_start);
+        }
+    }
+}
+
+/// Writes the literals from `start` as the last sequence of the block and returns the number of
+/// bytes written since `output_start_pos`.
+///
+/// With `LIMIT_FAIL`, returns `CompressError::OutputBudgetExceeded` if the output exceeds the
+/// limit. With `LIMIT_FILL`, only as many literals as fit are written.
+#[inline]
+fn finish_last_literals<const LIMIT: u8>(
+    output: &mut impl Sink,
+    input: &[u8],
+    start: usize,
+    output_start_pos: usize,
+    limit: &mut OutputLimit,
+) -> Result<usize, CompressError> {
+    if LIMIT == LIMIT_FILL {
+        let space = limit.max_output - (output.pos() - output_start_pos);
+        if space == 0 {
+            // Not even the token fits.
+            limit.input_end = start;
+            return Ok(output.pos() - output_start_pos);
+        }
+        // Start with an estimate of the number of literals which fit, which is at most off by
+        // one extra length byte.
+        let mut lit_len = (input.len() - start).min(space - 1 - space.saturating_sub(16) / 256);
+        while literal_run_size(lit_len) > space {
+            lit_len -= 1;
+        }
+        while start + lit_len < input.len() && literal_run_size(lit_len + 1) <= space {
+            lit_len += 1;
+        }
+        limit.input_end = start + lit_len;
+        handle_last_literals(output, &input[..start + lit_len], start);
+        return Ok(output.pos() - output_start_pos);
+    }
+    handle_last_literals(output, input, start);
+    let written = output.pos() - output_start_pos;
+    if LIMIT == LIMIT_FAIL && written > limit.max_output {
+        return Err(CompressError::OutputBudgetExceeded {
+            max_output: limit.max_output,
+        });
+    }
+    Ok(written)
+}
+
+/// Number of bytes needed for the extension of a length, which doesn't fit into the token.
+#[inline]
+fn extra_length_size(len: usize) -> usize {
+    if len >= 0xF {
+        (len - 0xF) / 255 + 1
+    } else {
+        0
     }
 }
 
+/// Number of bytes of a sequence with `lit_len` literals, up to (not including) the offset.
+#[inline]
+fn literal_run_size(lit_len: usize) -> usize {
+    1 + extra_length_size(lit_len) + lit_len
+}
+
 #[inline]
 #[cfg(feature = "safe-encode")]
 fn push_byte(output: &mut impl Sink, el: u8) {
@@ -584,6 +1127,19 @@ fn copy_literals_wild(output: &mut impl Sink, input: &[u8], input_start: usize,
     }
 }
 
+/// Returns the worst case number of bytes the compressed data of `input_len` bytes exceeds
+/// `input_len`, like `LZ4_COMPRESSBOUND` of the reference implementation.
+///
+/// Incompressible data is stored as literals, which need a length byte per 255 bytes in
+/// addition to the token. The compressed data is guaranteed to be at most
+/// `input_len + max_expansion(input_len)` bytes, e.g. to size storage. Note that
+/// `get_maximum_output_size` is larger, since the compressor needs some slack in the output
+/// buffer.
+#[inline]
+pub const fn max_expansion(input_len: usize) -> usize {
+    input_len / 255 + 16
+}
+
 /// Compress all bytes of `input` into `output`.
 /// The method chooses an appropriate hashtable to lookup duplicates.
 /// output should be preallocated with a size of
@@ -597,11 +1153,11 @@ pub(crate) fn compress_into_sink_with_dict<const USE_DICT: bool>(
     mut dict_data: &[u8],
 ) -> Result<usize, CompressError> {
     if dict_data.len() + input.len() < u16::MAX as usize {
-        let mut dict = HashTable4KU16::new();
+        let mut dict = SliceTableU16::new();
         init_dict(&mut dict, &mut dict_data);
         compress_internal::<_, USE_DICT, _>(input, 0, output, &mut dict, dict_data, dict_data.len())
     } else {
-        let mut dict = HashTable4K::new();
+        let mut dict = SliceTable::new();
         init_dict(&mut dict, &mut dict_data);
         compress_internal::<_, USE_DICT, _>(input, 0, output, &mut dict, dict_data, dict_data.len())
     }
@@ -635,11 +1191,114 @@ pub const fn get_maximum_output_size(input_len: usize) -> usize {
 /// `get_maximum_output_size`.
 ///
 /// Returns the number of bytes written (compressed) into `output`.
+///
+/// `input` and `output` must not overlap, which is checked in debug builds.
 #[inline]
 pub fn compress_into(input: &[u8], output: &mut [u8]) -> Result<usize, CompressError> {
+    super::debug_assert_disjoint(input, output);
     compress_into_sink_with_dict::<false>(input, &mut SliceSink::new(output, 0), b"")
 }
 
+/// Compress all bytes of `input` into `output` like `compress_into`, and split `output` after the
+/// compressed data.
+///
+/// Returns the written (compressed) part of `output` and the remaining unused part, e.g. to
+/// write more data into the same buffer afterwards.
+#[inline]
+pub fn compress_into_split<'a>(
+    input: &[u8],
+    output: &'a mut [u8],
+) -> Result<(&'a mut [u8], &'a mut [u8]), CompressError> {
+    let compressed_len = compress_into(input, output)?;
+    Ok(output.split_at_mut(compressed_len))
+}
+
+/// Compress all bytes of `input` into an array on the stack, without any heap allocation.
+///
+/// Returns the array and the number of bytes written (compressed) into it. `CAP` needs to be at
+/// least `get_maximum_output_size(input.len())`, otherwise `CompressError::OutputTooSmall` is
+/// returned. Intended for small inputs, as the array and the hash table (up to 16KB) live on the
+/// stack. Also available without the `alloc` feature.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_into_stack, decompress, get_maximum_output_size};
+/// let input: &[u8] = b"Hello people, what's up? Hello people, what's up?";
+/// let (compressed, len) = compress_into_stack::<{ get_maximum_output_size(64) }>(input).unwrap();
+/// assert_eq!(decompress(&compressed[..len], input.len()).unwrap(), input);
+/// ```
+#[inline]
+pub fn compress_into_stack<const CAP: usize>(
+    input: &[u8],
+) -> Result<([u8; CAP], usize), CompressError> {
+    let needed = get_maximum_output_size(input.len());
+    if CAP < needed {
+        return Err(CompressError::OutputTooSmall {
+            needed,
+            provided: CAP,
+        });
+    }
+    let mut output = [0u8; CAP];
+    let compressed_len = compress_into_without_alloc(input, &mut output)?;
+    Ok((output, compressed_len))
+}
+
+/// Compress all bytes of `input` into `output` like `compress_into`, with the hash table on the
+/// stack instead of the heap.
+#[inline]
+pub(crate) fn compress_into_without_alloc(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<usize, CompressError> {
+    let sink = &mut SliceSink::new(output, 0);
+    if input.len() < u16::MAX as usize {
+        let mut dict = HashTable4KU16Inline::new();
+        compress_internal::<_, false, _>(input, 0, sink, &mut dict, b"", 0)
+    } else {
+        let mut dict = HashTable4KInline::new();
+        compress_internal::<_, false, _>(input, 0, sink, &mut dict, b"", 0)
+    }
+}
+
+/// Compress each of `inputs` as a separate block, appended one after another into `output`.
+/// A single hash table is used for all inputs, so this is cheaper than calling `compress_into`
+/// for every input.
+///
+/// `output` should be preallocated with the sum of `get_maximum_output_size` of all inputs.
+///
+/// Returns the `(offset, len)` of each compressed block within `output`. Each block can be
+/// decompressed on its own, e.g. with `decompress(&output[offset..offset + len], ..)`.
+#[cfg(feature = "alloc")]
+pub fn compress_many_into(
+    inputs: &[&[u8]],
+    output: &mut [u8],
+) -> Result<Vec<(usize, usize)>, CompressError> {
+    let mut table = HashTable4K::new();
+    let mut ranges = Vec::with_capacity(inputs.len());
+    // The inputs are addressed as if they were one stream. Entries of previous inputs in the table
+    // are then out of reach, so the table doesn't need to be cleared between inputs.
+    let mut stream_offset = 0;
+    let mut pos = 0;
+    for input in inputs {
+        if stream_offset + input.len() >= u32::MAX as usize / 2 {
+            table.clear();
+            stream_offset = 0;
+        }
+        let len = compress_internal::<_, false, _>(
+            input,
+            0,
+            &mut SliceSink::new(output, pos),
+            &mut table,
+            b"",
+            stream_offset,
+        )?;
+        ranges.push((pos, len));
+        pos += len;
+        stream_offset += input.len();
+    }
+    Ok(ranges)
+}
+
 /// Compress all bytes of `input` into `output`.
 /// The method chooses an appropriate hashtable to lookup duplicates.
 /// output should be preallocated with a size of
@@ -655,6 +1314,7 @@ pub fn compress_into_with_dict(
     compress_into_sink_with_dict::<true>(input, &mut SliceSink::new(output, 0), dict_data)
 }
 
+#[cfg(feature = "alloc")]
 #[inline]
 fn compress_into_vec_with_dict<const USE_DICT: bool>(
     input: &[u8],
@@ -709,52 +1369,924 @@ fn compress_into_vec_with_dict<const USE_DICT: bool>(
 
 /// Compress all bytes of `input` into `output`. The uncompressed size will be prepended as a little
 /// endian u32. Can be used in conjunction with `decompress_size_prepended`
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn compress_prepend_size(input: &[u8]) -> Vec<u8> {
     compress_into_vec_with_dict::<false>(input, true, b"")
 }
 
-/// Compress all bytes of `input`.
-#[inline]
-pub fn compress(input: &[u8]) -> Vec<u8> {
-    compress_into_vec_with_dict::<false>(input, false, b"")
+/// Compress all bytes of `input` and prepend the uncompressed size and the compressed length of
+/// the block, each as a little endian u32.
+///
+/// Unlike with `compress_prepend_size`, the end of the block is known without decompressing it,
+/// so such blocks can be concatenated into a stream, which is read block by block with
+/// `decompress_next_from_cursor`.
+///
+/// Neither of the other formats with a header allows this: `compress_tagged` only writes the
+/// uncompressed size, for a single block stored on its own, and `compress_archive` compresses all
+/// items into one block, so reading any item decompresses the whole archive. The length prefix
+/// has no version tag, use `compress_tagged` for data that has to stay readable across versions.
+///
+/// # Panics
+/// Panics if the size of `input` doesn't fit into a u32.
+#[cfg(feature = "alloc")]
+pub fn compress_length_prefixed(input: &[u8]) -> Vec<u8> {
+    let size = u32::try_from(input.len()).expect("input is too large");
+    let mut compressed = vec![0u8; 8 + get_maximum_output_size(input.len())];
+    let compressed_len = compress_into(input, &mut compressed[8..]).unwrap();
+    compressed[..4].copy_from_slice(&size.to_le_bytes());
+    compressed[4..8].copy_from_slice(&(compressed_len as u32).to_le_bytes());
+    compressed.truncate(8 + compressed_len);
+    compressed.shrink_to_fit();
+    compressed
 }
 
-/// Compress all bytes of `input` with an external dictionary.
-#[inline]
-pub fn compress_with_dict(input: &[u8], ext_dict: &[u8]) -> Vec<u8> {
-    compress_into_vec_with_dict::<true>(input, false, ext_dict)
+/// Compress all bytes of `input` into a self-describing format for long-lived storage: A version
+/// tag byte, then the uncompressed size, then the block. Can be used in conjunction with
+/// `decompress_tagged`.
+///
+/// The size is written as a little endian u32 with `SizeTag::U32`, or as u64 with `SizeTag::U64`
+/// if it doesn't fit into a u32. Use `compress_tagged_with` to choose the encoding of the size.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_tagged, decompress_tagged};
+/// let input: &[u8] = b"Hello people, what's up?";
+/// let compressed = compress_tagged(input);
+/// assert_eq!(decompress_tagged(&compressed).unwrap(), input);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_tagged(input: &[u8]) -> Vec<u8> {
+    let tag = if u32::try_from(input.len()).is_ok() {
+        SizeTag::U32
+    } else {
+        SizeTag::U64
+    };
+    compress_tagged_with(input, tag)
 }
 
-/// Compress all bytes of `input` into `output`. The uncompressed size will be prepended as a little
-/// endian u32. Can be used in conjunction with `decompress_size_prepended_with_dict`
-#[inline]
-pub fn compress_prepend_size_with_dict(input: &[u8], ext_dict: &[u8]) -> Vec<u8> {
-    compress_into_vec_with_dict::<true>(input, true, ext_dict)
+/// Compress all bytes of `input` like `compress_tagged`, with the uncompressed size encoded as
+/// named by `tag`.
+///
+/// # Panics
+/// Panics if the size of `input` doesn't fit into a u32 with `SizeTag::U32`.
+#[cfg(feature = "alloc")]
+pub fn compress_tagged_with(input: &[u8], tag: SizeTag) -> Vec<u8> {
+    let mut header = [0u8; MAX_TAGGED_HEADER_SIZE];
+    let header_len = tag.write_header(input.len(), &mut header);
+    let mut compressed = vec![0u8; header_len + get_maximum_output_size(input.len())];
+    compressed[..header_len].copy_from_slice(&header[..header_len]);
+    let compressed_len = compress_into(input, &mut compressed[header_len..]).unwrap();
+    compressed.truncate(header_len + compressed_len);
+    compressed.shrink_to_fit();
+    compressed
 }
 
+/// Compress all bytes of `input`.
+#[cfg(feature = "alloc")]
 #[inline]
-#[cfg(not(feature = "safe-encode"))]
-fn read_u16_ptr(input: *const u8) -> u16 {
-    let mut num: u16 = 0;
-    unsafe {
-        core::ptr::copy_nonoverlapping(input, &mut num as *mut u16 as *mut u8, 2);
-    }
-    num
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    compress_into_vec_with_dict::<false>(input, false, b"")
 }
 
-#[inline]
-#[cfg(not(feature = "safe-encode"))]
-fn read_u32_ptr(input: *const u8) -> u32 {
-    let mut num: u32 = 0;
-    unsafe {
-        core::ptr::copy_nonoverlapping(input, &mut num as *mut u32 as *mut u8, 4);
+/// Compress the concatenation of `bufs` as a single block, e.g. a scatter list.
+///
+/// Matches may cross the boundaries between the slices, so the result is the same as compressing
+/// the concatenated data, and it is decompressed with `decompress` into contiguous data.
+///
+/// Currently the slices are copied into a contiguous buffer first, since the match finder works
+/// on a single slice. A match finder working on the slices directly may avoid that copy in the
+/// future.
+#[cfg(feature = "alloc")]
+pub fn compress_vectored(bufs: &[&[u8]]) -> Vec<u8> {
+    match bufs {
+        [] => compress(b""),
+        [input] => compress(input),
+        _ => compress(&bufs.concat()),
     }
-    num
 }
 
-#[inline]
-#[cfg(not(feature = "safe-encode"))]
+/// Compress `items` together as an archive, e.g. many small, similar records like JSON documents.
+///
+/// The items are concatenated, each prefixed with its length as a little endian u32, and the
+/// result is compressed with `compress_prepend_size`. Matches can reference previous items, which
+/// gives a much better ratio than compressing the items separately if they are similar. Use
+/// `decompress_archive` to get the items back.
+///
+/// # Panics
+/// Panics if the total size of the items, including the length prefixes, doesn't fit into a u32.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_archive, decompress_archive};
+/// let items: [&[u8]; 3] = [b"{\"id\": 1}", b"", b"{\"id\": 2}"];
+/// let compressed = compress_archive(&items);
+/// assert_eq!(decompress_archive(&compressed).unwrap(), items);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_archive(items: &[&[u8]]) -> Vec<u8> {
+    let size = items.iter().map(|item| 4 + item.len()).sum();
+    assert!(size <= u32::MAX as usize, "archive is too large");
+    let mut archive = Vec::with_capacity(size);
+    for item in items {
+        archive.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        archive.extend_from_slice(item);
+    }
+    compress_prepend_size(&archive)
+}
+
+/// Compress all bytes of `input` with the custom match finder table `table`, see [`HashTable`].
+///
+/// `table` is cleared first. The output is a regular LZ4 block and can be decompressed with
+/// `decompress`.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_with_table, decompress, HashTable};
+///
+/// /// Hashes only the first byte, into 256 entries.
+/// struct FirstByteTable([usize; 256]);
+/// impl HashTable for FirstByteTable {
+///     fn get_at(&self, hash: usize) -> usize {
+///         self.0[hash]
+///     }
+///     fn put_at(&mut self, hash: usize, val: usize) {
+///         self.0[hash] = val;
+///     }
+///     fn clear(&mut self) {
+///         self.0.fill(0);
+///     }
+///     fn get_hash_at(input: &[u8], pos: usize) -> usize {
+///         input[pos] as usize
+///     }
+/// }
+///
+/// let input = b"key=a;key=b;key=c;key=d;key=e;key=f;key=g;".repeat(10);
+/// let compressed = compress_with_table(&input, &mut FirstByteTable([0; 256]));
+/// assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_with_table<T: HashTable>(input: &[u8], table: &mut T) -> Vec<u8> {
+    table.clear();
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let sink = &mut SliceSink::new(&mut compressed, 0);
+    // The output is sized for the worst case, so compression can't fail.
+    let compressed_len = compress_internal::<_, false, _>(input, 0, sink, table, b"", 0).unwrap();
+    compressed.truncate(compressed_len);
+    compressed
+}
+
+/// Compress all bytes of `input` into `out`, replacing its contents.
+///
+/// Unlike `compress`, this reuses the allocation of `out`, so compressing many inputs in a loop
+/// into the same `Vec` only allocates when a larger output than before may be needed.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_reuse(input: &[u8], out: &mut Vec<u8>) {
+    let max_compressed_size = get_maximum_output_size(input.len());
+    out.clear();
+    #[cfg(feature = "safe-encode")]
+    {
+        out.resize(max_compressed_size, 0);
+        let compressed_len =
+            compress_into_sink_with_dict::<false>(input, &mut SliceSink::new(out, 0), b"").unwrap();
+        out.truncate(compressed_len);
+    }
+    #[cfg(not(feature = "safe-encode"))]
+    {
+        out.reserve(max_compressed_size);
+        let compressed_len =
+            compress_into_sink_with_dict::<false>(input, &mut PtrSink::from_vec(out, 0), b"")
+                .unwrap();
+        unsafe {
+            out.set_len(compressed_len);
+        }
+    }
+}
+
+/// Compress all bytes of `input`, like `compress`, but returns
+/// `CompressError::AllocationFailed` instead of aborting if the output or the hashtable can't be
+/// allocated.
+///
+/// The returned vec may have excess capacity.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn try_compress(input: &[u8]) -> Result<Vec<u8>, CompressError> {
+    let max_compressed_size = get_maximum_output_size(input.len());
+    let mut compressed: Vec<u8> = Vec::new();
+    compressed
+        .try_reserve_exact(max_compressed_size)
+        .map_err(|_| CompressError::AllocationFailed)?;
+    compressed.resize(max_compressed_size, 0);
+    let output = &mut SliceSink::new(&mut compressed, 0);
+    let compressed_len = if input.len() < u16::MAX as usize {
+        let mut dict = HashTable4KU16::try_new().ok_or(CompressError::AllocationFailed)?;
+        compress_internal::<_, false, _>(input, 0, output, &mut dict, b"", 0)?
+    } else {
+        let mut dict = HashTable4K::try_new().ok_or(CompressError::AllocationFailed)?;
+        compress_internal::<_, false, _>(input, 0, output, &mut dict, b"", 0)?
+    };
+    compressed.truncate(compressed_len);
+    Ok(compressed)
+}
+
+/// Compress all bytes of `input`, emitting only matches with an offset up to `max_distance`.
+///
+/// Limiting the distance of back references keeps them within the CPU cache during
+/// decompression, which can speed up decompression at the cost of compression ratio. The output is
+/// a regular LZ4 block and can be decompressed with `decompress`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_with_max_distance(input: &[u8], max_distance: u16) -> Vec<u8> {
+    compress_with_options(input, &CompressOptions::new().max_distance(max_distance))
+}
+
+/// Compress all bytes of `input`, tuned by `options`.
+///
+/// The output is a regular LZ4 block and can be decompressed with `decompress`. With the default
+/// options, this is the same as `compress`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_with_options(input: &[u8], options: &CompressOptions) -> Vec<u8> {
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let sink = &mut SliceSink::new(&mut compressed, 0);
+    let compressed_len = if input.len() < u16::MAX as usize && options.long_hash {
+        let mut dict = HashTable4KU16Hash5::new();
+        compress_internal_with_options::<_, false, LIMIT_NONE, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            &mut OutputLimit::new(usize::MAX),
+            &mut (),
+        )
+    } else if input.len() < u16::MAX as usize {
+        let mut dict = HashTable4KU16::new();
+        compress_internal_with_options::<_, false, LIMIT_NONE, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            &mut OutputLimit::new(usize::MAX),
+            &mut (),
+        )
+    } else {
+        let mut dict = HashTable4K::new();
+        compress_internal_with_options::<_, false, LIMIT_NONE, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            &mut OutputLimit::new(usize::MAX),
+            &mut (),
+        )
+    }
+    .unwrap();
+    compressed.truncate(compressed_len);
+    compressed.shrink_to_fit();
+    compressed
+}
+
+/// Compress all bytes of `input` into output which only depends on `input`, e.g. to store hashes
+/// of the compressed data for content-addressed storage.
+///
+/// LZ4 doesn't define a canonical encoding, so the output of `compress` may change between
+/// versions of this crate when the match finder is tuned. This function keeps its current output
+/// and will only change it in a major version. It's the same on all targets, regardless of
+/// endianness and pointer width, and with or without the `safe-encode` feature.
+///
+/// Currently this produces the same output as `compress`.
+///
+/// So the compressed blocks can serve as keys for their data, e.g. to deduplicate chunks with
+/// [`compressed_eq`](super::compressed_eq) and [`content_fingerprint`](super::content_fingerprint).
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_deterministic(input: &[u8]) -> Vec<u8> {
+    // Spelled out instead of `CompressOptions::new()`, so changed defaults don't apply here.
+    const OPTIONS: CompressOptions = CompressOptions {
+        step_size_bitshift: 5,
+        max_distance: u16::MAX,
+        long_hash: false,
+    };
+    compress_with_options(input, &OPTIONS)
+}
+
+/// Time spent in the phases of the compression of a block, returned by
+/// [`compress_with_analysis`].
+#[cfg(feature = "profiling")]
+#[cfg_attr(docsrs, doc(cfg(feature = "profiling")))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressProfile {
+    /// Searching matches in the hash table and determining their length.
+    pub match_finding: std::time::Duration,
+    /// Copying literals to the output, including the last literals of the block.
+    pub literal_copy: std::time::Duration,
+    /// Writing tokens, offsets and lengths to the output.
+    pub output: std::time::Duration,
+    /// The whole compression, including the setup of the hash table.
+    pub total: std::time::Duration,
+    /// Number of sequences, i.e. tokens, in the block.
+    pub sequences: usize,
+}
+
+/// Measures the phases of the compression for `compress_with_analysis`.
+#[cfg(feature = "profiling")]
+struct Profiler {
+    profile: CompressProfile,
+    phase: Phase,
+    phase_start: std::time::Instant,
+}
+
+#[cfg(feature = "profiling")]
+impl ConsumeInput for Profiler {
+    #[inline(always)]
+    fn consumed(&mut self, _input: &[u8], _end: usize) {
+        self.profile.sequences += 1;
+    }
+
+    #[inline]
+    fn phase(&mut self, phase: Phase) {
+        let now = std::time::Instant::now();
+        let elapsed = now - self.phase_start;
+        match self.phase {
+            Phase::MatchFinding => self.profile.match_finding += elapsed,
+            Phase::LiteralCopy => self.profile.literal_copy += elapsed,
+            Phase::Output => self.profile.output += elapsed,
+        }
+        self.phase = phase;
+        self.phase_start = now;
+    }
+}
+
+/// Compress all bytes of `input` like `compress`, and measure the time spent in the phases of the
+/// compression, e.g. to understand where the time goes on specific data.
+///
+/// The time is measured with `std::time::Instant` at each phase change, i.e. a few times per
+/// sequence, which makes the compression considerably slower than `compress`. The proportions of
+/// the phases are still indicative. The output is the same as of `compress`.
+#[cfg(feature = "profiling")]
+#[cfg_attr(docsrs, doc(cfg(feature = "profiling")))]
+pub fn compress_with_analysis(input: &[u8]) -> (Vec<u8>, CompressProfile) {
+    let start = std::time::Instant::now();
+    let mut profiler = Profiler {
+        profile: CompressProfile::default(),
+        phase: Phase::MatchFinding,
+        phase_start: start,
+    };
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let sink = &mut SliceSink::new(&mut compressed, 0);
+    // The output is sized for the worst case, so compression can't fail.
+    let compressed_len = if input.len() < u16::MAX as usize {
+        compress_internal_resumable(input, 0, sink, &mut HashTable4KU16::new(), &mut profiler)
+    } else {
+        compress_internal_resumable(input, 0, sink, &mut HashTable4K::new(), &mut profiler)
+    }
+    .unwrap();
+    // Attribute the time until the end to the last phase.
+    profiler.phase(Phase::Output);
+    compressed.truncate(compressed_len);
+    // The last sequence only has literals.
+    profiler.profile.sequences += 1;
+    profiler.profile.total = start.elapsed();
+    (compressed, profiler.profile)
+}
+
+/// Records the literal ranges for `compress_with_coverage`.
+#[cfg(feature = "alloc")]
+struct Coverage {
+    literals: Vec<Range<usize>>,
+    /// End of the last match.
+    end: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl ConsumeInput for Coverage {
+    #[inline]
+    fn consumed(&mut self, _input: &[u8], end: usize) {
+        self.end = end;
+    }
+
+    #[inline]
+    fn literals(&mut self, literals: Range<usize>) {
+        if !literals.is_empty() {
+            self.literals.push(literals);
+        }
+    }
+}
+
+/// Compress all bytes of `input` like `compress`, and return the ranges of `input` which were
+/// written as literals, e.g. to visualize which parts of the data are redundant.
+///
+/// The ranges are sorted and don't overlap, all other bytes of `input` are covered by matches,
+/// i.e. references to prior data. The output is the same as of `compress`.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::compress_with_coverage;
+/// let input = b"unique prefix, repeated part, repeated part, repeated part.";
+/// let (_compressed, literals) = compress_with_coverage(input);
+/// let literal_bytes: usize = literals.iter().map(|range| range.len()).sum();
+/// assert!(literal_bytes < input.len());
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_with_coverage(input: &[u8]) -> (Vec<u8>, Vec<Range<usize>>) {
+    let mut coverage = Coverage {
+        literals: Vec::new(),
+        end: 0,
+    };
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let sink = &mut SliceSink::new(&mut compressed, 0);
+    // The output is sized for the worst case, so compression can't fail.
+    let compressed_len = if input.len() < u16::MAX as usize {
+        compress_internal_resumable(input, 0, sink, &mut HashTable4KU16::new(), &mut coverage)
+    } else {
+        compress_internal_resumable(input, 0, sink, &mut HashTable4K::new(), &mut coverage)
+    }
+    .unwrap();
+    compressed.truncate(compressed_len);
+    // The last sequence only has literals.
+    if coverage.end < input.len() {
+        coverage.literals.push(coverage.end..input.len());
+    }
+    (compressed, coverage.literals)
+}
+
+/// Compress all bytes of `input`, unless the compressed block would be larger than `max_output`
+/// bytes, e.g. to drop or downsample data which doesn't fit a bandwidth budget.
+///
+/// Compression is aborted with `CompressError::OutputBudgetExceeded` as soon as the output so
+/// far plus the pending literals exceed `max_output`, so incompressible input is rejected after
+/// scanning about `max_output` bytes instead of the whole input. A later match could have taken
+/// over some of the pending literals, so in rare cases a block which would just fit is rejected.
+///
+/// Otherwise the output is the same as of `compress`.
+#[cfg(feature = "alloc")]
+pub fn compress_within(input: &[u8], max_output: usize) -> Result<Vec<u8>, CompressError> {
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let sink = &mut SliceSink::new(&mut compressed, 0);
+    let options = &CompressOptions::DEFAULT;
+    let compressed_len = if input.len() < u16::MAX as usize {
+        let mut dict = HashTable4KU16::new();
+        compress_internal_with_options::<_, false, LIMIT_FAIL, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            &mut OutputLimit::new(max_output),
+            &mut (),
+        )
+    } else {
+        let mut dict = HashTable4K::new();
+        compress_internal_with_options::<_, false, LIMIT_FAIL, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            &mut OutputLimit::new(max_output),
+            &mut (),
+        )
+    }?;
+    compressed.truncate(compressed_len);
+    compressed.shrink_to_fit();
+    Ok(compressed)
+}
+
+/// Compress as much of `input` as fits into `output`, e.g. to fill fixed size pages, like
+/// `LZ4_compress_destSize` of the reference implementation.
+///
+/// Returns the number of bytes of `input` which were compressed and the number of bytes written
+/// into `output`. The written part of `output` is a valid block, which decompresses to the
+/// compressed part of `input`. The block is ended at a sequence boundary with as many literals as
+/// fit, following the end of block rules. If `output` is empty, nothing is compressed.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_dest_size, decompress};
+/// let input = b"fill a page with as much data as fits. ".repeat(1000);
+/// let mut page = [0u8; 1024];
+/// let (consumed, written) = compress_dest_size(&input, &mut page);
+/// assert_eq!(decompress(&page[..written], consumed).unwrap(), &input[..consumed]);
+/// ```
+pub fn compress_dest_size(input: &[u8], output: &mut [u8]) -> (usize, usize) {
+    if output.len() >= get_maximum_output_size(input.len()) {
+        // Everything fits.
+        let written = compress_into(input, output).unwrap();
+        return (input.len(), written);
+    }
+    let limit = &mut OutputLimit::new(output.len());
+    let sink = &mut SliceSink::new(output, 0);
+    let options = &CompressOptions::DEFAULT;
+    let written = if input.len() < u16::MAX as usize {
+        let mut dict = SliceTableU16::new();
+        compress_internal_with_options::<_, false, LIMIT_FILL, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            limit,
+            &mut (),
+        )
+    } else {
+        let mut dict = SliceTable::new();
+        compress_internal_with_options::<_, false, LIMIT_FILL, _, _>(
+            input,
+            0,
+            sink,
+            &mut dict,
+            b"",
+            0,
+            options,
+            limit,
+            &mut (),
+        )
+    }
+    // The output only needs to hold `max_output` bytes with `LIMIT_FILL`.
+    .unwrap();
+    (limit.input_end, written)
+}
+
+/// Compress all bytes of `input` with the preset `level`, see [`CompressionLevel`].
+///
+/// The output is a regular LZ4 block and can be decompressed with `decompress`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_level(input: &[u8], level: CompressionLevel) -> Vec<u8> {
+    compress_with_options(input, &level.options())
+}
+
+/// Returns the size of the compressed data, i.e. `compress(input).len()`, without keeping the
+/// output.
+///
+/// With the `safe-encode` feature (default) the output isn't written at all, otherwise it's
+/// written to a temporary buffer.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compressed_len(input: &[u8]) -> usize {
+    #[cfg(feature = "safe-encode")]
+    {
+        compress_into_sink_with_dict::<false>(input, &mut CountingSink::new(), b"").unwrap()
+    }
+    #[cfg(not(feature = "safe-encode"))]
+    {
+        let mut vec = Vec::with_capacity(get_maximum_output_size(input.len()));
+        compress_into_sink_with_dict::<false>(input, &mut PtrSink::from_vec(&mut vec, 0), b"")
+            .unwrap()
+    }
+}
+
+/// Compress all bytes of `input` into `Bytes`.
+#[cfg(feature = "bytes")]
+#[inline]
+pub fn compress_bytes(input: &bytes::Bytes) -> bytes::Bytes {
+    // Converting the Vec into Bytes doesn't copy.
+    bytes::Bytes::from(compress(input))
+}
+
+/// Compress all bytes of `input` with an external dictionary.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_with_dict(input: &[u8], ext_dict: &[u8]) -> Vec<u8> {
+    compress_into_vec_with_dict::<true>(input, false, ext_dict)
+}
+
+/// Compress all bytes of `input` with an external dictionary, like `compress_with_dict`, but for
+/// a dictionary larger than 64KB, the 64KB window of it which shares the most 4-byte sequences
+/// with a sample of `input` is used, instead of the last 64KB.
+///
+/// This helps with large, diverse dictionaries, where the data related to `input` isn't
+/// necessarily at the end. Returns the compressed block and the range of `ext_dict` it was
+/// compressed with, which has to be passed to the decompressor, e.g.
+/// `decompress_with_dict(&compressed, len, &ext_dict[range])`.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_with_dict_smart, decompress_with_dict};
+/// let mut dict = b"the quick brown fox jumps over the lazy dog. ".repeat(1000);
+/// dict.extend((0..100_000u32).map(|i| i.wrapping_mul(2654435761).to_le_bytes()[3]));
+/// let input = b"the lazy dog jumps over the quick brown fox.";
+///
+/// let (compressed, range) = compress_with_dict_smart(input, &dict);
+/// assert_eq!(range.start, 0);
+/// let decompressed = decompress_with_dict(&compressed, input.len(), &dict[range]).unwrap();
+/// assert_eq!(decompressed, input);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_with_dict_smart(input: &[u8], ext_dict: &[u8]) -> (Vec<u8>, Range<usize>) {
+    let window = select_dict_window(input, ext_dict);
+    (compress_with_dict(input, &ext_dict[window.clone()]), window)
+}
+
+/// Selects the `WINDOW_SIZE` range of `ext_dict` which shares the most 4-byte sequences with a
+/// sample of `input`. Ties are resolved towards the end of the dictionary, which is the window
+/// `compress_with_dict` uses.
+#[cfg(feature = "alloc")]
+fn select_dict_window(input: &[u8], ext_dict: &[u8]) -> Range<usize> {
+    /// Number of sequences sampled from `input`, to keep the filter sparse.
+    const INPUT_SAMPLES: usize = 8 * 1024;
+    /// The windows are scored in steps of this size.
+    const SEGMENT_SIZE: usize = 1024;
+    const FILTER_BITS: u32 = 16;
+
+    if ext_dict.len() <= WINDOW_SIZE || input.len() < MINMATCH {
+        return ext_dict.len().saturating_sub(WINDOW_SIZE)..ext_dict.len();
+    }
+    let filter_hash = |sequence: u32| sequence.wrapping_mul(2654435761) >> (32 - FILTER_BITS);
+
+    // A bitset of the hashes of sampled sequences of `input`.
+    let mut filter = vec![0u64; (1 << FILTER_BITS) / 64];
+    let step = (input.len() / INPUT_SAMPLES).max(1);
+    for pos in (0..=input.len() - MINMATCH).step_by(step) {
+        let hash = filter_hash(get_batch(input, pos)) as usize;
+        filter[hash / 64] |= 1 << (hash % 64);
+    }
+
+    // Number of sequences in the filter per segment, counted from the end of the dictionary, so
+    // that the window at the end consists of whole segments.
+    let mut hits = vec![0u32; ext_dict.len().div_ceil(SEGMENT_SIZE)];
+    for pos in 0..=ext_dict.len() - MINMATCH {
+        let hash = filter_hash(get_batch(ext_dict, pos)) as usize;
+        if filter[hash / 64] & (1 << (hash % 64)) != 0 {
+            hits[(ext_dict.len() - 1 - pos) / SEGMENT_SIZE] += 1;
+        }
+    }
+
+    let window_segments = WINDOW_SIZE / SEGMENT_SIZE;
+    let mut score: u32 = hits[..window_segments].iter().sum();
+    let (mut best_score, mut best_offset) = (score, 0);
+    for offset in 1..=hits.len() - window_segments {
+        score = score + hits[offset + window_segments - 1] - hits[offset - 1];
+        if score > best_score {
+            (best_score, best_offset) = (score, offset);
+        }
+    }
+    let start = (ext_dict.len() - WINDOW_SIZE).saturating_sub(best_offset * SEGMENT_SIZE);
+    start..start + WINDOW_SIZE
+}
+
+/// Compress all bytes of `input` into `output`. The uncompressed size will be prepended as a little
+/// endian u32. Can be used in conjunction with `decompress_size_prepended_with_dict`
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_prepend_size_with_dict(input: &[u8], ext_dict: &[u8]) -> Vec<u8> {
+    compress_into_vec_with_dict::<true>(input, true, ext_dict)
+}
+
+/// Compress all bytes of `input` as the continuation of `prev_window`, e.g. the previously
+/// compressed data of a custom streaming format.
+///
+/// `prev_window` is not part of the output, but `input` may reference its last 64KB, like linked
+/// blocks of the frame format. Unlike `compress_with_dict`, `prev_window` is used as prefix of
+/// `input`, so matches can extend from the window into `input`. This copies the window and
+/// `input` into a contiguous buffer.
+///
+/// The block can be decompressed with `decompress_continue` and the same window.
+#[cfg(feature = "alloc")]
+pub fn compress_continue(prev_window: &[u8], input: &[u8]) -> Vec<u8> {
+    compress_after_window(prev_window, input, &mut ())
+}
+
+/// Compress all bytes of `input` as a block that is only valid when decoded after `prev`.
+///
+/// Like `compress_continue`, but drops the leading-literal requirement for short blocks: a block
+/// that is not the first of a stream may start with a match into `prev`, so `input` is searched
+/// for matches from `MFLIMIT` (12) bytes on, instead of `LZ4_MIN_LENGTH` (13). The end-of-block
+/// rules still apply, the last 5 bytes are always literals.
+///
+/// The block can only be decompressed with `decompress_continue` (or `decompress_with_dict`) and
+/// the same `prev`; decoding it on its own fails or produces wrong data. Useful for custom
+/// containers that always decode blocks in order.
+///
+/// ```
+/// let prev = b"a fairly long preamble, then the message";
+/// let compressed = lz4_flex::block::compress_dependent_block(prev, b"then the mes");
+/// assert!(compressed.len() < 12);
+/// let decompressed = lz4_flex::block::decompress_continue(&compressed, prev, 12).unwrap();
+/// assert_eq!(decompressed, b"then the mes");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_dependent_block(prev: &[u8], input: &[u8]) -> Vec<u8> {
+    compress_after_window(prev, input, &mut DependentBlock)
+}
+
+/// Searches blocks that follow a prefix for matches from `MFLIMIT` bytes on.
+#[cfg(feature = "alloc")]
+struct DependentBlock;
+
+#[cfg(feature = "alloc")]
+impl ConsumeInput for DependentBlock {
+    #[inline(always)]
+    fn consumed(&mut self, _input: &[u8], _end: usize) {}
+
+    #[inline(always)]
+    fn min_length(&self) -> usize {
+        MFLIMIT
+    }
+}
+
+/// Compresses `input` with the last 64KB of `prev_window` as prefix.
+#[cfg(feature = "alloc")]
+fn compress_after_window<C: ConsumeInput>(
+    prev_window: &[u8],
+    input: &[u8],
+    consume: &mut C,
+) -> Vec<u8> {
+    let window = &prev_window[prev_window.len().saturating_sub(WINDOW_SIZE)..];
+    let mut buffer = Vec::with_capacity(window.len() + input.len());
+    buffer.extend_from_slice(window);
+    buffer.extend_from_slice(input);
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let sink = &mut SliceSink::new(&mut compressed, 0);
+    let compressed_len = if buffer.len() < u16::MAX as usize {
+        let mut dict = HashTable4KU16::new();
+        init_dict(&mut dict, &mut &buffer[..window.len()]);
+        compress_internal_with_options::<_, false, LIMIT_NONE, _, _>(
+            &buffer,
+            window.len(),
+            sink,
+            &mut dict,
+            b"",
+            0,
+            &CompressOptions::DEFAULT,
+            &mut OutputLimit::new(usize::MAX),
+            consume,
+        )
+    } else {
+        let mut dict = HashTable4K::new();
+        init_dict(&mut dict, &mut &buffer[..window.len()]);
+        compress_internal_with_options::<_, false, LIMIT_NONE, _, _>(
+            &buffer,
+            window.len(),
+            sink,
+            &mut dict,
+            b"",
+            0,
+            &CompressOptions::DEFAULT,
+            &mut OutputLimit::new(usize::MAX),
+            consume,
+        )
+    }
+    .unwrap();
+    compressed.truncate(compressed_len);
+    compressed.shrink_to_fit();
+    compressed
+}
+
+/// Compress `target` as a delta against `base`, e.g. a new version of a file against the old one.
+///
+/// `base` is used as external dictionary, so data in `target` that also occurs in `base` is encoded
+/// as back references. Back references can reach at most 64KB, counted as if `target` directly
+/// followed `base`, so this works best for bases up to a few ten KB.
+///
+/// The original can be reconstructed from `base` and the patch with `decompress_delta`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    compress_with_dict(target, base)
+}
+
+/// A dictionary prepared for compression.
+///
+/// `compress_with_dict` builds the hash table from the dictionary on every call. `PreparedDict`
+/// does this once, so that many inputs can be compressed against the same dictionary, e.g. from a
+/// thread pool (`PreparedDict` is `Send + Sync`).
+///
+/// The compressed data can be decompressed with `decompress_with_dict` and the same dictionary.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_with_prepared_dict, decompress_with_dict, PreparedDict};
+/// let dict = b"{\"name\": \"\", \"id\": 0, \"tags\": []}";
+/// let prepared = PreparedDict::new(dict);
+/// let input: &[u8] = b"{\"name\": \"lz4\", \"id\": 12, \"tags\": [\"fast\"]}";
+/// let compressed = compress_with_prepared_dict(input, &prepared);
+/// let uncompressed = decompress_with_dict(&compressed, input.len(), dict).unwrap();
+/// assert_eq!(input, uncompressed);
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct PreparedDict {
+    /// The last `WINDOW_SIZE` bytes of the dictionary.
+    dict_data: Vec<u8>,
+    /// Hash table primed with the positions in `dict_data`.
+    table: HashTable4K,
+}
+
+#[cfg(feature = "alloc")]
+impl PreparedDict {
+    /// Prepares `dict` for compression. Only the last 64KB of `dict` can be referenced.
+    pub fn new(dict: &[u8]) -> Self {
+        let mut dict_data = dict;
+        if dict_data.len() <= 3 {
+            dict_data = b"";
+        }
+        let mut table = HashTable4K::new();
+        init_dict(&mut table, &mut dict_data);
+        Self {
+            dict_data: dict_data.to_vec(),
+            table,
+        }
+    }
+
+    /// The dictionary data used for compression.
+    pub fn dict(&self) -> &[u8] {
+        &self.dict_data
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Debug for PreparedDict {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("PreparedDict")
+            .field("dict_data", &"[...]")
+            .field("table", &"{ ... }")
+            .finish()
+    }
+}
+
+/// Compress all bytes of `input` into `output` with a prepared dictionary.
+/// output should be preallocated with a size of
+/// `get_maximum_output_size`.
+///
+/// Returns the number of bytes written (compressed) into `output`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_into_with_prepared_dict(
+    input: &[u8],
+    output: &mut [u8],
+    dict: &PreparedDict,
+) -> Result<usize, CompressError> {
+    let mut table = dict.table.clone();
+    compress_internal::<_, true, _>(
+        input,
+        0,
+        &mut SliceSink::new(output, 0),
+        &mut table,
+        &dict.dict_data,
+        dict.dict_data.len(),
+    )
+}
+
+/// Compress all bytes of `input` with a prepared dictionary.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn compress_with_prepared_dict(input: &[u8], dict: &PreparedDict) -> Vec<u8> {
+    let mut compressed: Vec<u8> = vec![0u8; get_maximum_output_size(input.len())];
+    let compressed_len = compress_into_with_prepared_dict(input, &mut compressed, dict).unwrap();
+    compressed.truncate(compressed_len);
+    compressed.shrink_to_fit();
+    compressed
+}
+
+#[inline]
+#[cfg(not(feature = "safe-encode"))]
+fn read_u16_ptr(input: *const u8) -> u16 {
+    let mut num: u16 = 0;
+    unsafe {
+        core::ptr::copy_nonoverlapping(input, &mut num as *mut u16 as *mut u8, 2);
+    }
+    num
+}
+
+#[inline]
+#[cfg(not(feature = "safe-encode"))]
+fn read_u32_ptr(input: *const u8) -> u32 {
+    let mut num: u32 = 0;
+    unsafe {
+        core::ptr::copy_nonoverlapping(input, &mut num as *mut u32 as *mut u8, 4);
+    }
+    num
+}
+
+#[inline]
+#[cfg(not(feature = "safe-encode"))]
+fn read_u64_ptr(input: *const u8) -> u64 {
+    let mut num: u64 = 0;
+    unsafe {
+        core::ptr::copy_nonoverlapping(input, &mut num as *mut u64 as *mut u8, 8);
+    }
+    num
+}
+
+#[inline]
+#[cfg(not(feature = "safe-encode"))]
 fn read_usize_ptr(input: *const u8) -> usize {
     let mut num: usize = 0;
     unsafe {
@@ -955,6 +2487,314 @@ mod tests {
         assert_le!(out.len(), 15);
     }
 
+    #[test]
+    fn test_output_independent_of_endianness() {
+        // The batches used for hashing are read as little-endian, so the compressed output must be
+        // byte-identical on little- and big-endian targets (and with safe and unsafe encoder).
+        let input: &[u8] = b"lz4_flex: the quick brown fox jumps over the lazy dog, the lazy dog \
+            sleeps. 0123456789 0123456789 lz4_flex: the end";
+        let expected: &[u8] = &[
+            241, 25, 108, 122, 52, 95, 102, 108, 101, 120, 58, 32, 116, 104, 101, 32, 113, 117,
+            105, 99, 107, 32, 98, 114, 111, 119, 110, 32, 102, 111, 120, 32, 106, 117, 109, 112,
+            115, 32, 111, 118, 101, 114, 31, 0, 145, 108, 97, 122, 121, 32, 100, 111, 103, 44, 45,
+            0, 4, 14, 0, 248, 4, 32, 115, 108, 101, 101, 112, 115, 46, 32, 48, 49, 50, 51, 52, 53,
+            54, 55, 56, 57, 11, 0, 7, 98, 0, 96, 104, 101, 32, 101, 110, 100,
+        ];
+        assert_eq!(compress(input), expected);
+    }
+
+    #[test]
+    fn test_prepared_dict() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PreparedDict>();
+
+        let dict: Vec<u8> = (0..100_000u32)
+            .flat_map(|i| (i % 1000).to_le_bytes())
+            .collect();
+        let prepared = PreparedDict::new(&dict);
+        assert_eq!(prepared.dict(), &dict[dict.len() - WINDOW_SIZE..]);
+        for input in [
+            &dict[dict.len() - 100..],
+            &dict[..1000],
+            b"" as &[u8],
+            b"abc",
+        ] {
+            let compressed = compress_with_prepared_dict(input, &prepared);
+            assert_eq!(compressed, compress_with_prepared_dict(input, &prepared));
+            let uncompressed =
+                crate::block::decompress::decompress_with_dict(&compressed, input.len(), &dict)
+                    .unwrap();
+            assert_eq!(input, uncompressed);
+        }
+        // the dictionary is used
+        let input = &dict[dict.len() - 100..];
+        assert_lt!(
+            compress_with_prepared_dict(input, &prepared).len(),
+            compress(input).len()
+        );
+
+        let prepared = PreparedDict::new(b"ab");
+        assert!(prepared.dict().is_empty());
+        let compressed = compress_with_prepared_dict(b"abababababababababab", &prepared);
+        let uncompressed = crate::block::decompress::decompress(&compressed, 20).unwrap();
+        assert_eq!(uncompressed, b"abababababababababab");
+    }
+
+    /// Returns the largest match offset in a compressed block.
+    fn max_match_offset(compressed: &[u8]) -> usize {
+        let read_len = |pos: &mut usize, len: usize| {
+            let mut len = len;
+            if len == 15 {
+                loop {
+                    let byte = compressed[*pos];
+                    *pos += 1;
+                    len += byte as usize;
+                    if byte != 255 {
+                        break;
+                    }
+                }
+            }
+            len
+        };
+        let mut max_offset = 0;
+        let mut pos = 0;
+        loop {
+            let token = compressed[pos];
+            pos += 1;
+            let literal_len = read_len(&mut pos, (token >> 4) as usize);
+            pos += literal_len;
+            if pos == compressed.len() {
+                return max_offset;
+            }
+            let offset = u16::from_le_bytes([compressed[pos], compressed[pos + 1]]);
+            max_offset = max_offset.max(offset as usize);
+            pos += 2;
+            read_len(&mut pos, (token & 0xF) as usize);
+        }
+    }
+
+    #[test]
+    fn test_compress_with_options() {
+        let inputs: [&[u8]; 4] = [
+            b"",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            include_bytes!("../../benches/compression_34k.txt"),
+            include_bytes!("../../benches/compression_66k_JSON.txt"),
+        ];
+        for input in inputs {
+            assert_eq!(
+                compress_with_options(input, &CompressOptions::default()),
+                compress(input)
+            );
+            for options in [
+                CompressOptions::new().step_size_bitshift(0),
+                CompressOptions::new().step_size_bitshift(u8::MAX),
+                CompressOptions::new().max_distance(100),
+                CompressOptions::new().long_hash(true),
+            ] {
+                let compressed = compress_with_options(input, &options);
+                let uncompressed = crate::block::decompress::decompress(&compressed, input.len());
+                assert_eq!(uncompressed.unwrap(), input);
+            }
+        }
+
+        // a more thorough search compresses better
+        let input = inputs[3];
+        assert_lt!(
+            compress_with_options(input, &CompressOptions::new().step_size_bitshift(10)).len(),
+            compress_with_options(input, &CompressOptions::new().step_size_bitshift(0)).len()
+        );
+
+        let input = inputs[2];
+        let long_hash = CompressOptions::new().long_hash(true);
+        assert_lt!(
+            compress_with_options(input, &long_hash).len(),
+            compress(input).len()
+        );
+    }
+
+    #[test]
+    fn test_compress_level() {
+        let input = include_bytes!("../../benches/compression_34k.txt");
+        assert_eq!(
+            compress_level(input, CompressionLevel::Default),
+            compress(input)
+        );
+        let levels = [
+            CompressionLevel::Fastest,
+            CompressionLevel::Default,
+            CompressionLevel::Better,
+            CompressionLevel::Best,
+        ];
+        let mut last_len = usize::MAX;
+        for level in levels {
+            let compressed = compress_level(input, level);
+            let uncompressed = crate::block::decompress::decompress(&compressed, input.len());
+            assert_eq!(uncompressed.unwrap(), input);
+            assert_le!(compressed.len(), last_len);
+            last_len = compressed.len();
+        }
+    }
+
+    #[test]
+    fn test_auto_tune() {
+        let input = include_bytes!("../../benches/compression_34k.txt");
+        let options = auto_tune(input, TuneTarget::MaxRatio);
+        assert!(options.long_hash);
+        assert_ge!(options.step_size_bitshift, 8);
+        assert_eq!(
+            compress_with_options(input, &options).len(),
+            compress_level(input, CompressionLevel::Best).len()
+        );
+        assert_eq!(
+            auto_tune(input, TuneTarget::Balanced),
+            CompressionLevel::Default.options()
+        );
+        assert_eq!(
+            auto_tune(input, TuneTarget::MaxSpeed),
+            CompressionLevel::Fastest.options()
+        );
+
+        // Nothing to gain on incompressible data
+        let mut state = 0x2545_f491_u32;
+        let random: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        for target in [
+            TuneTarget::MaxRatio,
+            TuneTarget::Balanced,
+            TuneTarget::MaxSpeed,
+        ] {
+            assert_eq!(
+                auto_tune(&random, target),
+                CompressionLevel::Fastest.options()
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_distance() {
+        let input = include_bytes!("../../benches/compression_66k_JSON.txt");
+        let compressed_default = compress(input);
+        assert_gt!(max_match_offset(&compressed_default), 4096);
+        assert_eq!(
+            compress_with_max_distance(input, u16::MAX),
+            compressed_default
+        );
+
+        let mut last_len = compressed_default.len();
+        for max_distance in [4096, 256, 16] {
+            let compressed = compress_with_max_distance(input, max_distance);
+            assert_le!(max_match_offset(&compressed), max_distance as usize);
+            assert_ge!(compressed.len(), last_len);
+            last_len = compressed.len();
+            let uncompressed = crate::block::decompress::decompress(&compressed, input.len());
+            assert_eq!(uncompressed.unwrap(), input);
+        }
+
+        let compressed = compress_with_max_distance(input, 0);
+        assert_eq!(max_match_offset(&compressed), 0);
+        let uncompressed = crate::block::decompress::decompress(&compressed, input.len());
+        assert_eq!(uncompressed.unwrap(), input);
+    }
+
+    #[test]
+    fn test_max_distance_boundary() {
+        // The pattern only matches its repetition `distance` bytes later. The run of a byte not in
+        // the pattern in between compresses into matches with offset 1.
+        let pattern: Vec<u8> = (0..128).collect();
+        let input_with_distance = |distance: usize| {
+            let mut input = pattern.clone();
+            input.resize(distance, 0xFF);
+            input.extend_from_slice(&pattern);
+            input.extend_from_slice(&[0xFF; 16]);
+            input
+        };
+        for max_distance in [MAX_DISTANCE, 1000, 1] {
+            // exactly at the limit, the repetition is a match
+            let input = input_with_distance(max_distance.max(pattern.len()));
+            let compressed = compress_with_max_distance(&input, max_distance as u16);
+            if max_distance >= pattern.len() {
+                assert_eq!(max_match_offset(&compressed), max_distance);
+                assert_lt!(compressed.len(), input.len() - pattern.len());
+            }
+            let uncompressed = crate::block::decompress::decompress(&compressed, input.len());
+            assert_eq!(uncompressed.unwrap(), input);
+
+            // one byte further, the repetition is stored as literals
+            let input = input_with_distance(max_distance.max(pattern.len()) + 1);
+            let compressed = compress_with_max_distance(&input, max_distance as u16);
+            assert_le!(max_match_offset(&compressed), max_distance);
+            assert_gt!(compressed.len(), 2 * pattern.len());
+            let uncompressed = crate::block::decompress::decompress(&compressed, input.len());
+            assert_eq!(uncompressed.unwrap(), input);
+        }
+        assert_eq!(
+            compress(&input_with_distance(MAX_DISTANCE)),
+            compress_with_max_distance(&input_with_distance(MAX_DISTANCE), u16::MAX)
+        );
+    }
+
+    #[test]
+    fn test_output_too_small() {
+        let input = include_bytes!("../../benches/compression_1k.txt");
+        let mut output = vec![0u8; 100];
+        let err = compress_into(input, &mut output).unwrap_err();
+        let needed = get_maximum_output_size(input.len());
+        assert!(matches!(
+            err,
+            CompressError::OutputTooSmall { needed: n, provided: 100 } if n == needed
+        ));
+        assert!(err.to_string().contains(&format!("needed {needed}")));
+    }
+
+    #[test]
+    fn test_compress_into_stack() {
+        let input: &[u8] =
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let (compressed, len) =
+            compress_into_stack::<{ get_maximum_output_size(100) }>(input).unwrap();
+        assert_eq!(&compressed[..len], compress(input));
+
+        let input = include_bytes!("../../benches/compression_66k_JSON.txt");
+        let (compressed, len) =
+            compress_into_stack::<{ get_maximum_output_size(70_000) }>(input).unwrap();
+        let uncompressed = crate::block::decompress::decompress(&compressed[..len], input.len());
+        assert_eq!(uncompressed.unwrap(), input);
+
+        assert!(matches!(
+            compress_into_stack::<16>(b""),
+            Err(CompressError::OutputTooSmall {
+                needed: 20,
+                provided: 16
+            })
+        ));
+        let (_, len) = compress_into_stack::<20>(b"").unwrap();
+        assert_eq!(len, 1);
+    }
+
+    #[test]
+    fn test_compressed_len() {
+        let inputs: [&[u8]; 6] = [
+            b"",
+            b"a",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            include_bytes!("../../benches/compression_1k.txt"),
+            include_bytes!("../../benches/compression_34k.txt"),
+            include_bytes!("../../benches/compression_66k_JSON.txt"),
+        ];
+        for input in inputs {
+            assert_eq!(compressed_len(input), compress(input).len());
+        }
+        let zeros = vec![0u8; 1_000_000];
+        assert_eq!(compressed_len(&zeros), compress(&zeros).len());
+    }
+
     #[test]
     fn test_dict_size() {
         let dict = vec![b'a'; 1024 * 1024];
@@ -964,4 +2804,22 @@ mod tests {
             crate::block::decompress_size_prepended_with_dict(&compressed, &dict).unwrap();
         assert_eq!(decompressed, input);
     }
+
+    #[test]
+    fn disjoint_input_output() {
+        let buffer = [0u8; 64];
+        crate::block::debug_assert_disjoint(&buffer[..32], &buffer[32..]);
+        crate::block::debug_assert_disjoint(&buffer[32..], &buffer[..32]);
+        crate::block::debug_assert_disjoint(&buffer[16..16], &buffer[..32]);
+    }
+
+    // Overlapping `&[u8]` and `&mut [u8]` can only be created with unsafe code (e.g. FFI), so
+    // the check is tested on shared slices.
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overlap")]
+    fn overlapping_input_output() {
+        let buffer = [0u8; 64];
+        crate::block::debug_assert_disjoint(&buffer[..33], &buffer[32..]);
+    }
 }
@@ -0,0 +1,142 @@
+//! Compression of a block in bounded steps.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::task::Poll;
+
+use crate::block::compress::{
+    compress_internal_resumable, get_maximum_output_size, ConsumeInput, SearchState,
+};
+use crate::block::hashtable::{HashTable4K, HashTable4KU16};
+use crate::sink::SliceSink;
+
+/// Compresses a block in steps of a bounded amount of input, e.g. to yield to the executor of a
+/// single-threaded async runtime between steps instead of blocking it for a large input.
+///
+/// The output is the same as [`compress`](super::compress) of the whole input.
+///
+/// # Example
+/// ```
+/// use core::task::Poll;
+/// use lz4_flex::block::{compress, CompressJob};
+/// let input = b"compressed in steps, with other tasks running in between".repeat(1000);
+///
+/// let mut job = CompressJob::new(&input);
+/// let compressed = loop {
+///     match job.step(4096) {
+///         Poll::Ready(compressed) => break compressed,
+///         // e.g. `yield_now().await`
+///         Poll::Pending => {}
+///     }
+/// };
+/// assert_eq!(compressed, compress(&input));
+/// ```
+#[derive(Debug)]
+pub struct CompressJob<'a> {
+    input: &'a [u8],
+    /// Same table as `compress` chooses for the input size.
+    table: JobTable,
+    /// Sized for the worst case, the first `written` bytes are the output so far.
+    output: Vec<u8>,
+    written: usize,
+    /// Where the match search paused, `None` before the first step.
+    state: Option<SearchState>,
+    finished: bool,
+}
+
+#[derive(Debug)]
+enum JobTable {
+    Small(HashTable4KU16),
+    Large(HashTable4K),
+}
+
+impl<'a> CompressJob<'a> {
+    /// Creates a job compressing all bytes of `input`. Nothing is compressed before the first
+    /// `step`.
+    pub fn new(input: &'a [u8]) -> Self {
+        let table = if input.len() < u16::MAX as usize {
+            JobTable::Small(HashTable4KU16::new())
+        } else {
+            JobTable::Large(HashTable4K::new())
+        };
+        CompressJob {
+            input,
+            table,
+            output: vec![0u8; get_maximum_output_size(input.len())],
+            written: 0,
+            state: None,
+            finished: false,
+        }
+    }
+
+    /// Compresses roughly the next `budget` bytes of the input. Returns the compressed block once
+    /// the whole input is compressed, `Poll::Pending` otherwise.
+    ///
+    /// A step may compress more than `budget` bytes to complete a match or the last literals of
+    /// the block, and always makes progress, also with a `budget` of 0.
+    ///
+    /// # Panics
+    /// Panics if called again after the compressed block was returned.
+    pub fn step(&mut self, budget: usize) -> Poll<Vec<u8>> {
+        assert!(!self.finished, "CompressJob::step called after completion");
+        let (input_pos, cur) = match self.state {
+            Some(state) => (state.literal_start, state.cur),
+            None => (0, 0),
+        };
+        let mut step = Step {
+            resume: self.state.take(),
+            pause_at: cur.saturating_add(budget.max(1)),
+            paused: None,
+        };
+        let sink = &mut SliceSink::new(&mut self.output, self.written);
+        // The output is sized for the worst case, so compression can't fail.
+        self.written += match &mut self.table {
+            JobTable::Small(table) => {
+                compress_internal_resumable(self.input, input_pos, sink, table, &mut step)
+            }
+            JobTable::Large(table) => {
+                compress_internal_resumable(self.input, input_pos, sink, table, &mut step)
+            }
+        }
+        .unwrap();
+        match step.paused {
+            Some(state) => {
+                self.state = Some(state);
+                Poll::Pending
+            }
+            None => {
+                self.finished = true;
+                let mut compressed = core::mem::take(&mut self.output);
+                compressed.truncate(self.written);
+                compressed.shrink_to_fit();
+                Poll::Ready(compressed)
+            }
+        }
+    }
+}
+
+/// Pauses the match search of a `CompressJob` step once it reaches `pause_at`.
+struct Step {
+    resume: Option<SearchState>,
+    pause_at: usize,
+    paused: Option<SearchState>,
+}
+
+impl ConsumeInput for Step {
+    #[inline(always)]
+    fn consumed(&mut self, _input: &[u8], _end: usize) {}
+
+    #[inline(always)]
+    fn resume(&mut self) -> Option<SearchState> {
+        self.resume.take()
+    }
+
+    #[inline(always)]
+    fn pause(&mut self, state: SearchState) -> bool {
+        if state.cur < self.pause_at {
+            return false;
+        }
+        self.paused = Some(state);
+        true
+    }
+}
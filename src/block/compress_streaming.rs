@@ -0,0 +1,418 @@
+//! Compression of a stream into linked blocks.
+
+use alloc::vec::Vec;
+
+use crate::block::compress::{compress_internal, get_maximum_output_size};
+#[cfg(feature = "frame")]
+use crate::block::hashtable::HashTable;
+use crate::block::hashtable::HashTable4K;
+use crate::block::{CompressError, WINDOW_SIZE};
+use crate::sink::vec_sink_for_compression;
+
+/// The largest block size supported by `StreamCompressor`, same as the largest block size of the
+/// frame format.
+const MAX_BLOCK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Compresses a stream of data into a sequence of linked blocks.
+///
+/// Each block may reference the last 64KB of data of the previous blocks, so a block is
+/// decompressed with the previously decompressed data as dictionary, e.g. with
+/// `decompress_with_dict`. The blocks carry no framing, a framing layer (e.g. a custom container
+/// format) has to store the block boundaries and uncompressed sizes.
+///
+/// The input is buffered until a full block of `block_size` bytes is available. The
+/// [`FrameEncoder`] of the frame format is built on top of this.
+///
+/// [`FrameEncoder`]: crate::frame::FrameEncoder
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{decompress_with_dict, StreamCompressor};
+/// let input = b"linked blocks reference the data of previous blocks".repeat(1000);
+///
+/// let mut compressor = StreamCompressor::new(16 * 1024);
+/// let mut blocks = compressor.push(&input);
+/// blocks.extend(compressor.finish());
+///
+/// let mut decompressed = Vec::new();
+/// for block in &blocks {
+///     let dict = &decompressed[decompressed.len().saturating_sub(64 * 1024)..];
+///     let data = decompress_with_dict(block, 16 * 1024, dict).unwrap();
+///     decompressed.extend_from_slice(&data);
+/// }
+/// assert_eq!(decompressed, input);
+/// ```
+pub struct StreamCompressor {
+    /// Our buffer of uncompressed bytes.
+    src: Vec<u8>,
+    /// Index into src: starting point of bytes not yet compressed
+    src_start: usize,
+    /// Index into src: end point of bytes not not yet compressed
+    src_end: usize,
+    /// Index into src: starting point of external dictionary (applicable when blocks are linked)
+    ext_dict_offset: usize,
+    /// Length of external dictionary
+    ext_dict_len: usize,
+    /// Counter of bytes already compressed to the compression_table
+    /// This is reset every to 2GB.
+    src_stream_offset: usize,
+    /// Encoder table
+    compression_table: HashTable4K,
+    /// Maximum number of bytes per block.
+    block_size: usize,
+    /// Whether blocks may reference previous blocks.
+    linked: bool,
+}
+
+impl StreamCompressor {
+    /// Creates a compressor producing linked blocks of up to `block_size` uncompressed bytes.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is 0 or larger than 8MB.
+    pub fn new(block_size: usize) -> Self {
+        assert!(
+            block_size > 0 && block_size <= MAX_BLOCK_SIZE,
+            "block size must be between 1 byte and 8MB"
+        );
+        let mut compressor = Self::unconfigured();
+        compressor.configure(block_size, true);
+        compressor
+    }
+
+    /// Adds `data` to the stream and returns the blocks which became full.
+    ///
+    /// Data not filling a full block is buffered until more data is pushed or [`flush()`] is
+    /// called.
+    ///
+    /// [`flush()`]: Self::flush
+    pub fn push(&mut self, mut data: &[u8]) -> Vec<Vec<u8>> {
+        let mut blocks = Vec::new();
+        while !data.is_empty() {
+            let filled = self.fill(data);
+            data = &data[filled..];
+            if self.pending_len() == self.block_size {
+                blocks.push(self.compress_block());
+            }
+        }
+        blocks
+    }
+
+    /// Compresses the buffered data as a (possibly smaller) block, if there is any.
+    ///
+    /// The stream continues afterwards, subsequent blocks may still reference the data of the
+    /// returned block.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        if self.pending_len() == 0 {
+            return None;
+        }
+        Some(self.compress_block())
+    }
+
+    /// Consumes the compressor and compresses the buffered data as the last block, if there is
+    /// any.
+    pub fn finish(mut self) -> Option<Vec<u8>> {
+        self.flush()
+    }
+
+    fn compress_block(&mut self) -> Vec<u8> {
+        let mut block = Vec::new();
+        // The output is sized for the worst case, so compression can't fail.
+        let comp_len = self.compress_pending(&mut block, false).unwrap();
+        block.truncate(comp_len);
+        self.consume_pending();
+        block
+    }
+
+    /// Creates a compressor without a buffer, which needs to be set up with `configure` before
+    /// use.
+    pub(crate) fn unconfigured() -> Self {
+        StreamCompressor {
+            src: Vec::new(),
+            src_start: 0,
+            src_end: 0,
+            ext_dict_offset: 0,
+            ext_dict_len: 0,
+            src_stream_offset: 0,
+            // 16 KB hash table for matches, same as the reference implementation.
+            compression_table: HashTable4K::new(),
+            block_size: 0,
+            linked: false,
+        }
+    }
+
+    /// Sets the block size and whether blocks are linked, and allocates the buffer accordingly.
+    ///
+    /// May only switch to linked blocks while at most one block has been filled and none has been
+    /// compressed yet.
+    pub(crate) fn configure(&mut self, block_size: usize, linked: bool) {
+        self.block_size = block_size;
+        self.linked = linked;
+        let src_size = if linked {
+            // In linked mode we consume the input (bumping src_start) but leave the
+            // beginning of src to be used as a prefix in subsequent blocks.
+            // That is at least until we have at least `block_size + WINDOW_SIZE`
+            // bytes in src, then we setup an ext_dict with the last WINDOW_SIZE bytes
+            // and the input goes to the beginning of src again.
+            // Since we always want to be able to write a full block (up to block_size)
+            // we need a buffer with at least `block_size * 2 + WINDOW_SIZE` bytes.
+            block_size * 2 + WINDOW_SIZE
+        } else {
+            block_size
+        };
+        // Since this method is called potentially multiple times, don't reserve _additional_
+        // capacity if not required.
+        self.src
+            .reserve_exact(src_size.saturating_sub(self.src.len()));
+    }
+
+    /// Forgets all data of the stream, the next block starts a new stream.
+    #[cfg(feature = "frame")]
+    pub(crate) fn reset(&mut self) {
+        self.src_stream_offset = 0;
+        self.src.clear();
+        self.src_start = 0;
+        self.src_end = 0;
+        self.ext_dict_len = 0;
+        self.compression_table.clear();
+    }
+
+    /// The buffered data, which is not compressed yet.
+    #[cfg(feature = "frame")]
+    pub(crate) fn pending(&self) -> &[u8] {
+        &self.src[self.src_start..self.src_end]
+    }
+
+    pub(crate) fn pending_len(&self) -> usize {
+        self.src_end - self.src_start
+    }
+
+    /// Buffers as much of `data` as fits into the current block, returns the number of bytes
+    /// taken.
+    pub(crate) fn fill(&mut self, data: &[u8]) -> usize {
+        let fill_len = (self.block_size - self.pending_len()).min(data.len());
+        vec_copy_overwriting(&mut self.src, self.src_end, &data[..fill_len]);
+        self.src_end += fill_len;
+        fill_len
+    }
+
+    /// Compresses the buffered data into `dst` and returns the compressed length.
+    ///
+    /// The data stays buffered until `consume_pending` is called, so the caller can still access
+    /// it e.g. to store it uncompressed. With `skip_compression`, the match search is skipped and
+    /// the length of the buffered data is returned.
+    pub(crate) fn compress_pending(
+        &mut self,
+        dst: &mut Vec<u8>,
+        skip_compression: bool,
+    ) -> Result<usize, CompressError> {
+        debug_assert!(self.pending_len() <= self.block_size);
+        self.reposition_table_if_needed();
+        if skip_compression {
+            return Ok(self.pending_len());
+        }
+        // input to the compressor, which may include a prefix when blocks are linked
+        let input = &self.src[..self.src_end];
+        let ext_dict = &self.src[self.ext_dict_offset..self.ext_dict_offset + self.ext_dict_len];
+        compress_block_into(
+            &mut self.compression_table,
+            input,
+            self.src_start,
+            ext_dict,
+            self.src_stream_offset,
+            dst,
+        )
+    }
+
+    /// Marks the buffered data as compressed, so that the next block is filled.
+    pub(crate) fn consume_pending(&mut self) {
+        let src_len = self.src_end - self.src_start;
+
+        // Buffer and offsets maintenance
+        self.src_start += src_len;
+        debug_assert_eq!(self.src_start, self.src_end);
+        if self.linked {
+            // In linked mode we consume the input (bumping src_start) but leave the
+            // beginning of src to be used as a prefix in subsequent blocks.
+            // That is at least until we have at least `block_size + WINDOW_SIZE`
+            // bytes in src, then we setup an ext_dict with the last WINDOW_SIZE bytes
+            // and the input goes to the beginning of src again.
+            debug_assert_eq!(self.src.capacity(), self.block_size * 2 + WINDOW_SIZE);
+            if self.src_start >= self.block_size + WINDOW_SIZE {
+                // The ext_dict will become the last WINDOW_SIZE bytes
+                self.ext_dict_offset = self.src_end - WINDOW_SIZE;
+                self.ext_dict_len = WINDOW_SIZE;
+                // Input goes in the beginning of the buffer again.
+                self.src_stream_offset += self.src_end;
+                self.src_start = 0;
+                self.src_end = 0;
+            } else if self.src_start + self.ext_dict_len > WINDOW_SIZE {
+                // There's more than WINDOW_SIZE bytes of lookback adding the prefix and ext_dict.
+                // Since we have a limited buffer we must shrink ext_dict in favor of the prefix,
+                // so that we can fit up to block_size bytes between dst_start and ext_dict
+                // start.
+                let delta = self
+                    .ext_dict_len
+                    .min(self.src_start + self.ext_dict_len - WINDOW_SIZE);
+                self.ext_dict_offset += delta;
+                self.ext_dict_len -= delta;
+                debug_assert!(self.src_start + self.ext_dict_len >= WINDOW_SIZE)
+            }
+            debug_assert!(
+                self.ext_dict_len == 0 || self.src_start + self.block_size <= self.ext_dict_offset
+            );
+        } else {
+            // In independent block mode we consume the entire src buffer
+            // which is sized equal to the block_size.
+            debug_assert_eq!(self.ext_dict_len, 0);
+            debug_assert_eq!(self.src.capacity(), self.block_size);
+            self.src_start = 0;
+            self.src_end = 0;
+            // Advance stream offset so we don't have to reset the match dict
+            // for the next block.
+            self.src_stream_offset += src_len;
+        }
+        debug_assert!(self.src_start <= self.src_end);
+        debug_assert!(self.src_start + self.block_size <= self.src.capacity());
+    }
+
+    /// Compresses `block` into `dst` without copying it into the buffer, see `compress_pending`.
+    /// Expects no buffered data.
+    #[cfg(feature = "frame")]
+    pub(crate) fn compress_borrowed(
+        &mut self,
+        block: &[u8],
+        dst: &mut Vec<u8>,
+        skip_compression: bool,
+    ) -> Result<usize, CompressError> {
+        debug_assert_eq!(self.pending_len(), 0);
+        debug_assert!(block.len() <= self.block_size);
+        if self.linked {
+            self.make_window_contiguous();
+        }
+        self.reposition_table_if_needed();
+        if skip_compression {
+            return Ok(block.len());
+        }
+        // The lookback window is used as ext_dict, the block starts right after it.
+        let window = &self.src[self.src_end.saturating_sub(WINDOW_SIZE)..self.src_end];
+        compress_block_into(
+            &mut self.compression_table,
+            block,
+            0,
+            window,
+            self.src_stream_offset + self.src_end,
+            dst,
+        )
+    }
+
+    /// Marks `block`, passed to `compress_borrowed` before, as compressed.
+    #[cfg(feature = "frame")]
+    pub(crate) fn consume_borrowed(&mut self, block: &[u8]) {
+        if !self.linked {
+            // Advance stream offset so we don't have to reset the match dict
+            // for the next block.
+            self.src_stream_offset += block.len();
+            return;
+        }
+        // Keep the last WINDOW_SIZE bytes of the stream as prefix for the next block.
+        let block_stream_offset = self.src_stream_offset + self.src_end;
+        let block_tail = &block[block.len().saturating_sub(WINDOW_SIZE)..];
+        let keep = (WINDOW_SIZE - block_tail.len()).min(self.src_end);
+        self.src.copy_within(self.src_end - keep..self.src_end, 0);
+        vec_copy_overwriting(&mut self.src, keep, block_tail);
+        self.src_stream_offset = block_stream_offset + block.len() - block_tail.len() - keep;
+        self.src_start = keep + block_tail.len();
+        self.src_end = self.src_start;
+    }
+
+    /// Moves the lookback of linked blocks (the ext_dict and the prefix) to the beginning of src,
+    /// so that the last WINDOW_SIZE bytes of the stream are contiguous in `src[..src_end]`.
+    /// Expects no pending input, i.e. `src_start == src_end`.
+    #[cfg(feature = "frame")]
+    fn make_window_contiguous(&mut self) {
+        debug_assert_eq!(self.src_start, self.src_end);
+        if self.ext_dict_len != 0 && self.src_end < WINDOW_SIZE {
+            // The ext_dict is located after the prefix and at least block_size bytes
+            // after src_end, so the copies below don't overlap with the ext_dict tail.
+            let ext_dict_tail_len = self.ext_dict_len.min(WINDOW_SIZE - self.src_end);
+            let ext_dict_end = self.ext_dict_offset + self.ext_dict_len;
+            self.src.copy_within(..self.src_end, ext_dict_tail_len);
+            self.src
+                .copy_within(ext_dict_end - ext_dict_tail_len..ext_dict_end, 0);
+            self.src_stream_offset -= ext_dict_tail_len;
+            self.src_end += ext_dict_tail_len;
+            self.src_start = self.src_end;
+        }
+        self.ext_dict_len = 0;
+    }
+
+    /// Repositions the compression table if we're anywhere near an overflowing hazard.
+    fn reposition_table_if_needed(&mut self) {
+        if self.src_stream_offset + self.block_size + WINDOW_SIZE >= u32::MAX as usize / 2 {
+            self.compression_table
+                .reposition((self.src_stream_offset - self.ext_dict_len) as _);
+            self.src_stream_offset = self.ext_dict_len;
+        }
+    }
+}
+
+impl core::fmt::Debug for StreamCompressor {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("StreamCompressor")
+            .field("src", &"[...]")
+            .field("src_start", &self.src_start)
+            .field("src_end", &self.src_end)
+            .field("ext_dict_offset", &self.ext_dict_offset)
+            .field("ext_dict_len", &self.ext_dict_len)
+            .field("src_stream_offset", &self.src_stream_offset)
+            .field("compression_table", &"{ ... }")
+            .field("block_size", &self.block_size)
+            .field("linked", &self.linked)
+            .finish()
+    }
+}
+
+/// Compresses `input[input_pos..]` into `dst`, `input[..input_pos]` and `ext_dict` are used as
+/// lookback, see `compress_internal`.
+fn compress_block_into(
+    table: &mut HashTable4K,
+    input: &[u8],
+    input_pos: usize,
+    ext_dict: &[u8],
+    input_stream_offset: usize,
+    dst: &mut Vec<u8>,
+) -> Result<usize, CompressError> {
+    let dst_required_size = get_maximum_output_size(input.len() - input_pos);
+    let mut sink = vec_sink_for_compression(dst, 0, 0, dst_required_size);
+    if ext_dict.is_empty() {
+        compress_internal::<_, false, _>(
+            input,
+            input_pos,
+            &mut sink,
+            table,
+            b"",
+            input_stream_offset,
+        )
+    } else {
+        compress_internal::<_, true, _>(
+            input,
+            input_pos,
+            &mut sink,
+            table,
+            ext_dict,
+            input_stream_offset,
+        )
+    }
+}
+
+/// Copy `src` into `target` starting from the `start` index, overwriting existing data if any.
+#[inline]
+fn vec_copy_overwriting(target: &mut Vec<u8>, target_start: usize, src: &[u8]) {
+    debug_assert!(target_start + src.len() <= target.capacity());
+
+    // By combining overwriting (copy_from_slice) and extending (extend_from_slice)
+    // we can fill the ring buffer without initializing it (eg. filling with 0).
+    let overwrite_len = (target.len() - target_start).min(src.len());
+    target[target_start..target_start + overwrite_len].copy_from_slice(&src[..overwrite_len]);
+    target.extend_from_slice(&src[overwrite_len..]);
+}
@@ -1,9 +1,11 @@
 //! The block decompression algorithm.
 use crate::block::{DecompressError, MINMATCH};
 use crate::fastcpy_unsafe;
+#[cfg(feature = "alloc")]
+use crate::sink::PtrSink;
+use crate::sink::Sink;
 use crate::sink::SliceSink;
-use crate::sink::{PtrSink, Sink};
-#[allow(unused_imports)]
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
 /// Copies data to output_ptr by self-referential copy from start and match_length
@@ -448,11 +450,28 @@ pub(crate) fn decompress_internal<const USE_DICT: bool, S: Sink>(
 
 /// Decompress all bytes of `input` into `output`.
 /// `output` should be preallocated with a size of of the uncompressed data.
+///
+/// `input` and `output` must not overlap, which is checked in debug builds.
 #[inline]
 pub fn decompress_into(input: &[u8], output: &mut [u8]) -> Result<usize, DecompressError> {
+    super::debug_assert_disjoint(input, output);
     decompress_internal::<false, _>(input, &mut SliceSink::new(output, 0), b"")
 }
 
+/// Decompress all bytes of `input` into the fixed size buffer `output`, without allocating.
+///
+/// `output` needs to be large enough to hold the uncompressed data, otherwise
+/// `DecompressError::OutputTooSmall` is returned. This is suitable for `no_std` environments
+/// without a global allocator.
+///
+/// Same as [`decompress_into`], which doesn't allocate either.
+///
+/// Returns the number of bytes written (decompressed) into `output`.
+#[inline]
+pub fn decompress_into_fixed(input: &[u8], output: &mut [u8]) -> Result<usize, DecompressError> {
+    decompress_into(input, output)
+}
+
 /// Decompress all bytes of `input` into `output`.
 ///
 /// Returns the number of bytes written (decompressed) into `output`.
@@ -472,6 +491,7 @@ pub fn decompress_into_with_dict(
 /// May panic if the parameter `min_uncompressed_size` is smaller than the
 /// uncompressed data.
 
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress_with_dict(
     input: &[u8],
@@ -490,10 +510,11 @@ pub fn decompress_with_dict(
 
 /// Decompress all bytes of `input` into a new vec. The first 4 bytes are the uncompressed size in
 /// little endian. Can be used in conjunction with `compress_prepend_size`
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress_size_prepended(input: &[u8]) -> Result<Vec<u8>, DecompressError> {
     let (uncompressed_size, input) = super::uncompressed_size(input)?;
-    decompress(input, uncompressed_size)
+    super::check_uncompressed_size(decompress(input, uncompressed_size)?, uncompressed_size)
 }
 
 /// Decompress all bytes of `input` into a new vec.
@@ -502,6 +523,7 @@ pub fn decompress_size_prepended(input: &[u8]) -> Result<Vec<u8>, DecompressErro
 /// # Panics
 /// May panic if the parameter `min_uncompressed_size` is smaller than the
 /// uncompressed data.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress(input: &[u8], min_uncompressed_size: usize) -> Result<Vec<u8>, DecompressError> {
     // Allocate a vector to contain the decompressed stream.
@@ -514,15 +536,98 @@ pub fn decompress(input: &[u8], min_uncompressed_size: usize) -> Result<Vec<u8>,
     Ok(vec)
 }
 
+/// Decompress all bytes of `input` and append them to `out`, e.g. to reassemble data which was
+/// compressed in chunks. The existing contents of `out` are left untouched and aren't visible to
+/// matches of `input`.
+///
+/// The passed parameter `known_size` needs to be equal or larger than the uncompressed size, and
+/// is reserved beyond `out.len()`. On error, `out` keeps its previous length.
+///
+/// Returns the number of bytes appended.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn decompress_append(
+    input: &[u8],
+    known_size: usize,
+    out: &mut Vec<u8>,
+) -> Result<usize, DecompressError> {
+    out.reserve(known_size);
+    let decomp_len =
+        decompress_internal::<false, _>(input, &mut PtrSink::from_vec_spare_capacity(out), b"")?;
+    unsafe {
+        out.set_len(out.len() + decomp_len);
+    }
+    Ok(decomp_len)
+}
+
+/// Decompress all bytes of `input` into `Bytes`.
+/// The passed parameter `min_uncompressed_size` needs to be equal or larger than the uncompressed size.
+///
+/// # Panics
+/// May panic if the parameter `min_uncompressed_size` is smaller than the
+/// uncompressed data.
+#[cfg(feature = "bytes")]
+#[inline]
+pub fn decompress_bytes(
+    input: &bytes::Bytes,
+    min_uncompressed_size: usize,
+) -> Result<bytes::Bytes, DecompressError> {
+    // Converting the Vec into Bytes doesn't copy.
+    decompress(input, min_uncompressed_size).map(bytes::Bytes::from)
+}
+
+/// Decompress all bytes of `input` into a new vec, like `decompress`, but returns
+/// `DecompressError::AllocationFailed` instead of aborting if the output can't be allocated.
+///
+/// # Panics
+/// May panic if the parameter `min_uncompressed_size` is smaller than the
+/// uncompressed data.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn try_decompress(
+    input: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(min_uncompressed_size)
+        .map_err(|_| DecompressError::AllocationFailed)?;
+    let decomp_len =
+        decompress_internal::<true, _>(input, &mut PtrSink::from_vec(&mut vec, 0), b"")?;
+    unsafe {
+        vec.set_len(decomp_len);
+    }
+    Ok(vec)
+}
+
 /// Decompress all bytes of `input` into a new vec. The first 4 bytes are the uncompressed size in
 /// little endian. Can be used in conjunction with `compress_prepend_size_with_dict`
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress_size_prepended_with_dict(
     input: &[u8],
     ext_dict: &[u8],
 ) -> Result<Vec<u8>, DecompressError> {
     let (uncompressed_size, input) = super::uncompressed_size(input)?;
-    decompress_with_dict(input, uncompressed_size, ext_dict)
+    super::check_uncompressed_size(
+        decompress_with_dict(input, uncompressed_size, ext_dict)?,
+        uncompressed_size,
+    )
+}
+
+/// Reconstructs the target from a `patch` created by `compress_delta` and the same `base`.
+/// `target_size` needs to be equal or larger than the size of the target.
+///
+/// # Panics
+/// May panic if the parameter `target_size` is smaller than the
+/// size of the target.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn decompress_delta(
+    patch: &[u8],
+    base: &[u8],
+    target_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress_with_dict(patch, target_size, base)
 }
 
 #[cfg(test)]
@@ -4,11 +4,10 @@ use crate::block::DecompressError;
 use crate::block::MINMATCH;
 use crate::sink::Sink;
 use crate::sink::SliceSink;
+use crate::sink::StridedSink;
 
-#[allow(unused_imports)]
-use alloc::vec;
-#[allow(unused_imports)]
-use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 
 /// Read an integer.
 ///
@@ -60,6 +59,9 @@ fn read_u16(input: &[u8], input_pos: &mut usize) -> Result<u16, DecompressError>
     Ok(u16::from_le_bytes(dst.try_into().unwrap()))
 }
 
+/// Number of bytes copied at once for literals up to this length outside of the hot loop.
+const WIDE_LITERAL_COPY: usize = 32;
+
 const FIT_TOKEN_MASK_LITERAL: u8 = 0b00001111;
 const FIT_TOKEN_MASK_MATCH: u8 = 0b11110000;
 
@@ -189,7 +191,20 @@ pub(crate) fn decompress_internal<const USE_DICT: bool, S: Sink>(
                     actual: output.capacity(),
                 });
             }
-            output.extend_from_slice(&input[input_pos..input_pos + literal_length]);
+            if literal_length <= WIDE_LITERAL_COPY
+                && input_pos + WIDE_LITERAL_COPY <= input.len()
+                && output.pos() + WIDE_LITERAL_COPY <= output.capacity()
+            {
+                // Short literals which didn't fit in the token. Far away enough from the end, copy
+                // a fixed 32 bytes, which the compiler can vectorize, instead of calling memcpy.
+                let wide: &[u8; WIDE_LITERAL_COPY] = input
+                    [input_pos..input_pos + WIDE_LITERAL_COPY]
+                    .try_into()
+                    .unwrap();
+                output.extend_from_slice_wild(wide, literal_length);
+            } else {
+                output.extend_from_slice(&input[input_pos..input_pos + literal_length]);
+            }
             input_pos += literal_length;
         }
 
@@ -311,11 +326,50 @@ fn duplicate_overlapping_slice(
 
 /// Decompress all bytes of `input` into `output`.
 /// `output` should be preallocated with a size of of the uncompressed data.
+///
+/// `input` and `output` must not overlap, which is checked in debug builds.
 #[inline]
 pub fn decompress_into(input: &[u8], output: &mut [u8]) -> Result<usize, DecompressError> {
+    super::debug_assert_disjoint(input, output);
     decompress_internal::<false, _>(input, &mut SliceSink::new(output, 0), b"")
 }
 
+/// Decompress all bytes of `input` into the fixed size buffer `output`, without allocating.
+///
+/// `output` needs to be large enough to hold the uncompressed data, otherwise
+/// `DecompressError::OutputTooSmall` is returned. This is suitable for `no_std` environments
+/// without a global allocator.
+///
+/// Same as [`decompress_into`], which doesn't allocate either.
+///
+/// Returns the number of bytes written (decompressed) into `output`.
+#[inline]
+pub fn decompress_into_fixed(input: &[u8], output: &mut [u8]) -> Result<usize, DecompressError> {
+    decompress_into(input, output)
+}
+
+/// Decompress all bytes of `input` into rows of `row_len` bytes, which start every `stride`
+/// bytes in `output`.
+///
+/// This allows decompressing e.g. an image tile directly into a larger framebuffer. The bytes
+/// between the rows are left untouched and the last row doesn't need to be padded to `stride`.
+/// If `output` can't hold the uncompressed data, `DecompressError::OutputTooSmall` is returned,
+/// with sizes counted in row bytes.
+///
+/// Returns the number of bytes decompressed.
+///
+/// # Panics
+/// Panics if `row_len` is 0 or larger than `stride`.
+#[inline]
+pub fn decompress_into_strided(
+    input: &[u8],
+    output: &mut [u8],
+    row_len: usize,
+    stride: usize,
+) -> Result<usize, DecompressError> {
+    decompress_internal::<false, _>(input, &mut StridedSink::new(output, row_len, stride), b"")
+}
+
 /// Decompress all bytes of `input` into `output`.
 ///
 /// Returns the number of bytes written (decompressed) into `output`.
@@ -330,10 +384,11 @@ pub fn decompress_into_with_dict(
 
 /// Decompress all bytes of `input` into a new vec. The first 4 bytes are the uncompressed size in
 /// little endian. Can be used in conjunction with `compress_prepend_size`
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress_size_prepended(input: &[u8]) -> Result<Vec<u8>, DecompressError> {
     let (uncompressed_size, input) = super::uncompressed_size(input)?;
-    decompress(input, uncompressed_size)
+    super::check_uncompressed_size(decompress(input, uncompressed_size)?, uncompressed_size)
 }
 
 /// Decompress all bytes of `input` into a new vec.
@@ -342,6 +397,7 @@ pub fn decompress_size_prepended(input: &[u8]) -> Result<Vec<u8>, DecompressErro
 /// # Panics
 /// May panic if the parameter `min_uncompressed_size` is smaller than the
 /// uncompressed data.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress(input: &[u8], min_uncompressed_size: usize) -> Result<Vec<u8>, DecompressError> {
     let mut decompressed: Vec<u8> = vec![0; min_uncompressed_size];
@@ -351,15 +407,81 @@ pub fn decompress(input: &[u8], min_uncompressed_size: usize) -> Result<Vec<u8>,
     Ok(decompressed)
 }
 
+/// Decompress all bytes of `input` and append them to `out`, e.g. to reassemble data which was
+/// compressed in chunks. The existing contents of `out` are left untouched and aren't visible to
+/// matches of `input`.
+///
+/// The passed parameter `known_size` needs to be equal or larger than the uncompressed size, and
+/// is reserved beyond `out.len()`. On error, `out` keeps its previous length.
+///
+/// Returns the number of bytes appended.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn decompress_append(
+    input: &[u8],
+    known_size: usize,
+    out: &mut Vec<u8>,
+) -> Result<usize, DecompressError> {
+    let start = out.len();
+    out.resize(start + known_size, 0);
+    let result =
+        decompress_internal::<false, _>(input, &mut SliceSink::new(&mut out[start..], 0), b"");
+    out.truncate(start + *result.as_ref().unwrap_or(&0));
+    result
+}
+
+/// Decompress all bytes of `input` into `Bytes`.
+/// The passed parameter `min_uncompressed_size` needs to be equal or larger than the uncompressed size.
+///
+/// # Panics
+/// May panic if the parameter `min_uncompressed_size` is smaller than the
+/// uncompressed data.
+#[cfg(feature = "bytes")]
+#[inline]
+pub fn decompress_bytes(
+    input: &bytes::Bytes,
+    min_uncompressed_size: usize,
+) -> Result<bytes::Bytes, DecompressError> {
+    // Converting the Vec into Bytes doesn't copy.
+    decompress(input, min_uncompressed_size).map(bytes::Bytes::from)
+}
+
+/// Decompress all bytes of `input` into a new vec, like `decompress`, but returns
+/// `DecompressError::AllocationFailed` instead of aborting if the output can't be allocated.
+///
+/// # Panics
+/// May panic if the parameter `min_uncompressed_size` is smaller than the
+/// uncompressed data.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn try_decompress(
+    input: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut decompressed: Vec<u8> = Vec::new();
+    decompressed
+        .try_reserve_exact(min_uncompressed_size)
+        .map_err(|_| DecompressError::AllocationFailed)?;
+    decompressed.resize(min_uncompressed_size, 0);
+    let decomp_len =
+        decompress_internal::<false, _>(input, &mut SliceSink::new(&mut decompressed, 0), b"")?;
+    decompressed.truncate(decomp_len);
+    Ok(decompressed)
+}
+
 /// Decompress all bytes of `input` into a new vec. The first 4 bytes are the uncompressed size in
 /// little endian. Can be used in conjunction with `compress_prepend_size_with_dict`
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress_size_prepended_with_dict(
     input: &[u8],
     ext_dict: &[u8],
 ) -> Result<Vec<u8>, DecompressError> {
     let (uncompressed_size, input) = super::uncompressed_size(input)?;
-    decompress_with_dict(input, uncompressed_size, ext_dict)
+    super::check_uncompressed_size(
+        decompress_with_dict(input, uncompressed_size, ext_dict)?,
+        uncompressed_size,
+    )
 }
 
 /// Decompress all bytes of `input` into a new vec.
@@ -368,6 +490,7 @@ pub fn decompress_size_prepended_with_dict(
 /// # Panics
 /// May panic if the parameter `min_uncompressed_size` is smaller than the
 /// uncompressed data.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn decompress_with_dict(
     input: &[u8],
@@ -381,6 +504,22 @@ pub fn decompress_with_dict(
     Ok(decompressed)
 }
 
+/// Reconstructs the target from a `patch` created by `compress_delta` and the same `base`.
+/// `target_size` needs to be equal or larger than the size of the target.
+///
+/// # Panics
+/// May panic if the parameter `target_size` is smaller than the
+/// size of the target.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn decompress_delta(
+    patch: &[u8],
+    base: &[u8],
+    target_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress_with_dict(patch, target_size, base)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,137 @@
+//! Block decompression into a callback, using a buffer of bounded size.
+
+use std::io;
+
+use crate::block::{read_length, DecompressError, MINMATCH, WINDOW_SIZE};
+
+/// Size of the buffer, which holds the window for back references and the decompressed data not
+/// passed to the callback yet.
+const BUFFER_SIZE: usize = 2 * WINDOW_SIZE;
+
+/// Decompressed data, of which the last `WINDOW_SIZE` bytes are retained for back references.
+struct Window<F> {
+    buffer: Vec<u8>,
+    /// Number of bytes passed to `sink` from the start of `buffer`.
+    flushed: usize,
+    /// Total number of decompressed bytes.
+    total: usize,
+    max_total: usize,
+    sink: F,
+}
+
+impl<F: FnMut(&[u8]) -> io::Result<()>> Window<F> {
+    /// Passes the pending data to the sink and retains the last `WINDOW_SIZE` bytes.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.flushed < self.buffer.len() {
+            (self.sink)(&self.buffer[self.flushed..])?;
+        }
+        let discarded = self.buffer.len().saturating_sub(WINDOW_SIZE);
+        self.buffer.drain(..discarded);
+        self.flushed = self.buffer.len();
+        Ok(())
+    }
+
+    /// Returns how many bytes can be added before the buffer needs to be flushed, flushing if
+    /// the buffer is full.
+    fn reserve(&mut self, len: usize) -> io::Result<usize> {
+        if self.total + len > self.max_total {
+            return Err(invalid_data(DecompressError::OutputTooSmall {
+                expected: self.total + len,
+                actual: self.max_total,
+            }));
+        }
+        if self.buffer.len() == BUFFER_SIZE {
+            self.flush()?;
+        }
+        Ok(len.min(BUFFER_SIZE - self.buffer.len()))
+    }
+
+    fn push_literals(&mut self, mut literals: &[u8]) -> io::Result<()> {
+        while !literals.is_empty() {
+            let len = self.reserve(literals.len())?;
+            self.buffer.extend_from_slice(&literals[..len]);
+            self.total += len;
+            literals = &literals[len..];
+        }
+        Ok(())
+    }
+
+    fn push_match(&mut self, offset: usize, mut match_length: usize) -> io::Result<()> {
+        if offset == 0 || offset > self.total {
+            return Err(invalid_data(DecompressError::OffsetOutOfBounds));
+        }
+        let mut copied = 0;
+        while match_length > 0 {
+            let len = self.reserve(match_length)?;
+            // The match repeats with a period of `offset`, so it can be copied from any multiple of
+            // `offset` back, as long as the source is either before the match or already copied.
+            // Copying from at least as far back as the length avoids overlapping copies.
+            let distance = (copied + offset).min(self.buffer.len()) / offset * offset;
+            let len = len.min(distance);
+            let start = self.buffer.len() - distance;
+            self.buffer.extend_from_within(start..start + len);
+            self.total += len;
+            copied += len;
+            match_length -= len;
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(err: DecompressError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Decompress all bytes of `input`, passing the decompressed data to `sink` in chunks.
+///
+/// Only a buffer of 128KB is used for the decompressed data, so this is suitable for feeding a
+/// parser or writer incrementally without materializing the whole output. The concatenation of
+/// the chunks equals the output of `decompress`. `max_uncompressed_size` needs to be equal or
+/// larger than the uncompressed size.
+///
+/// Errors returned by `sink` are passed through, invalid input is reported as
+/// `io::ErrorKind::InvalidData` wrapping the `DecompressError`.
+pub fn decompress_streaming<F: FnMut(&[u8]) -> io::Result<()>>(
+    input: &[u8],
+    max_uncompressed_size: usize,
+    sink: F,
+) -> io::Result<()> {
+    let mut window = Window {
+        buffer: Vec::with_capacity(BUFFER_SIZE.min(max_uncompressed_size)),
+        flushed: 0,
+        total: 0,
+        max_total: max_uncompressed_size,
+        sink,
+    };
+    let mut input_pos = 0;
+    loop {
+        let token = *input
+            .get(input_pos)
+            .ok_or_else(|| invalid_data(DecompressError::ExpectedAnotherByte))?;
+        input_pos += 1;
+
+        let literal_length =
+            read_length(input, &mut input_pos, (token >> 4) as usize).map_err(invalid_data)?;
+        let literals = input
+            .get(input_pos..input_pos + literal_length)
+            .ok_or_else(|| invalid_data(DecompressError::LiteralOutOfBounds))?;
+        window.push_literals(literals)?;
+        input_pos += literal_length;
+
+        // The last sequence has only literals.
+        if input_pos >= input.len() {
+            break;
+        }
+
+        let offset = input
+            .get(input_pos..input_pos + 2)
+            .ok_or_else(|| invalid_data(DecompressError::ExpectedAnotherByte))?;
+        let offset = u16::from_le_bytes(offset.try_into().unwrap()) as usize;
+        input_pos += 2;
+
+        let match_length = MINMATCH
+            + read_length(input, &mut input_pos, (token & 0xF) as usize).map_err(invalid_data)?;
+        window.push_match(offset, match_length)?;
+    }
+    window.flush()
+}
@@ -1,4 +1,4 @@
-#[allow(unused_imports)]
+#[cfg(feature = "alloc")]
 use alloc::boxed::Box;
 
 /// The Hashtable trait used by the compression to store hashed bytes to their position.
@@ -20,44 +20,63 @@ fn hash(sequence: u32) -> u32 {
     (sequence.wrapping_mul(2654435761_u32)) >> 16
 }
 
-/// hashes and right shifts to a maximum value of 16bit, 65535
+/// hashes the lowest 5 bytes and right shifts to a maximum value of 16bit, 65535
 /// The right shift is done in order to not exceed, the hashtables capacity
-#[cfg(target_pointer_width = "64")]
+///
+/// `sequence` is read as little-endian (see `get_batch_u64`), so the lowest 5 bytes are the
+/// first 5 bytes of input on every target. It's a `u64` also on 32-bit targets, so the hash and
+/// therefore the compressed output doesn't depend on the pointer width.
 #[inline]
-fn hash5(sequence: usize) -> u32 {
-    let primebytes = if cfg!(target_endian = "little") {
-        889523592379_usize
-    } else {
-        11400714785074694791_usize
-    };
+fn hash5(sequence: u64) -> u32 {
+    let primebytes = 889523592379_u64;
     (((sequence << 24).wrapping_mul(primebytes)) >> 48) as u32
 }
 
+/// The table of the compressor, which maps the hash of the bytes at a position to the last
+/// position with that hash, to find match candidates.
+///
+/// Implement it to experiment with domain specific hashing, e.g. for fixed-stride records hash
+/// only the bytes which repeat between records, and compress with `compress_with_table`.
+///
+/// The hash returned by `get_hash_at` is only passed to `get_at` and `put_at` of the same table,
+/// so it has to be masked or shifted to the size of the table, e.g. `hash & (SIZE - 1)`.
+/// Candidates are verified by the compressor, so a poor hash or an outdated position only costs
+/// compression ratio, but the output is always valid.
 pub trait HashTable {
-    fn get_at(&self, pos: usize) -> usize;
-    fn put_at(&mut self, pos: usize, val: usize);
-    #[allow(dead_code)]
+    /// Returns the position last stored for `hash` with `put_at`, or 0.
+    fn get_at(&self, hash: usize) -> usize;
+    /// Stores the position `val` for `hash`.
+    fn put_at(&mut self, hash: usize, val: usize);
+    /// Resets all positions to 0.
     fn clear(&mut self);
+    /// Returns the hash of the bytes at `input[pos..]`. At least 8 bytes of `input` are available
+    /// at `pos`. By default the first 5 bytes are hashed into 16 bits.
     #[inline]
-    #[cfg(target_pointer_width = "64")]
     fn get_hash_at(input: &[u8], pos: usize) -> usize {
-        hash5(super::compress::get_batch_arch(input, pos)) as usize
-    }
-    #[inline]
-    #[cfg(target_pointer_width = "32")]
-    fn get_hash_at(input: &[u8], pos: usize) -> usize {
-        hash(super::compress::get_batch(input, pos)) as usize
+        hash5(super::compress::get_batch_u64(input, pos)) as usize
     }
 }
 
 const HASHTABLE_SIZE_4K: usize = 4 * 1024;
+
+/// Allocates a zeroed table, returning `None` instead of aborting if the allocation fails.
+#[cfg(feature = "alloc")]
+#[inline]
+fn try_alloc_table<T: Copy + Default, const N: usize>() -> Option<Box<[T; N]>> {
+    let mut dict = alloc::vec::Vec::new();
+    dict.try_reserve_exact(N).ok()?;
+    dict.resize(N, T::default());
+    dict.into_boxed_slice().try_into().ok()
+}
 const HASHTABLE_BIT_SHIFT_4K: usize = 4;
 
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 #[repr(align(64))]
 pub struct HashTable4KU16 {
     dict: Box<[u16; HASHTABLE_SIZE_4K]>,
 }
+#[cfg(feature = "alloc")]
 impl HashTable4KU16 {
     #[inline]
     pub fn new() -> Self {
@@ -70,7 +89,14 @@ impl HashTable4KU16 {
             .unwrap();
         Self { dict }
     }
+
+    /// Same as `new`, but returns `None` if the table can't be allocated.
+    #[inline]
+    pub fn try_new() -> Option<Self> {
+        try_alloc_table().map(|dict| Self { dict })
+    }
 }
+#[cfg(feature = "alloc")]
 impl HashTable for HashTable4KU16 {
     #[inline]
     fn get_at(&self, hash: usize) -> usize {
@@ -90,10 +116,100 @@ impl HashTable for HashTable4KU16 {
     }
 }
 
+/// Same as `HashTable4KU16`, but hashes 5 instead of 4 bytes, like the tables for larger inputs.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct HashTable4KU16Hash5(HashTable4KU16);
+#[cfg(feature = "alloc")]
+impl HashTable4KU16Hash5 {
+    #[inline]
+    pub fn new() -> Self {
+        Self(HashTable4KU16::new())
+    }
+}
+#[cfg(feature = "alloc")]
+impl HashTable for HashTable4KU16Hash5 {
+    #[inline]
+    fn get_at(&self, hash: usize) -> usize {
+        self.0.get_at(hash)
+    }
+    #[inline]
+    fn put_at(&mut self, hash: usize, val: usize) {
+        self.0.put_at(hash, val)
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.0.clear()
+    }
+}
+
+/// Same as `HashTable4KU16`, but stored inline instead of on the heap.
 #[derive(Debug)]
+#[repr(align(64))]
+pub struct HashTable4KU16Inline {
+    dict: [u16; HASHTABLE_SIZE_4K],
+}
+impl HashTable4KU16Inline {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            dict: [0; HASHTABLE_SIZE_4K],
+        }
+    }
+}
+impl HashTable for HashTable4KU16Inline {
+    #[inline]
+    fn get_at(&self, hash: usize) -> usize {
+        self.dict[hash >> HASHTABLE_BIT_SHIFT_4K] as usize
+    }
+    #[inline]
+    fn put_at(&mut self, hash: usize, val: usize) {
+        self.dict[hash >> HASHTABLE_BIT_SHIFT_4K] = val as u16;
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.dict.fill(0);
+    }
+    #[inline]
+    fn get_hash_at(input: &[u8], pos: usize) -> usize {
+        hash(super::get_batch(input, pos)) as usize
+    }
+}
+
+/// Same as `HashTable4K`, but stored inline instead of on the heap.
+#[derive(Debug)]
+pub struct HashTable4KInline {
+    dict: [u32; HASHTABLE_SIZE_4K],
+}
+impl HashTable4KInline {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            dict: [0; HASHTABLE_SIZE_4K],
+        }
+    }
+}
+impl HashTable for HashTable4KInline {
+    #[inline]
+    fn get_at(&self, hash: usize) -> usize {
+        self.dict[hash >> HASHTABLE_BIT_SHIFT_4K] as usize
+    }
+    #[inline]
+    fn put_at(&mut self, hash: usize, val: usize) {
+        self.dict[hash >> HASHTABLE_BIT_SHIFT_4K] = val as u32;
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.dict.fill(0);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
 pub struct HashTable4K {
     dict: Box<[u32; HASHTABLE_SIZE_4K]>,
 }
+#[cfg(feature = "alloc")]
 impl HashTable4K {
     #[inline]
     pub fn new() -> Self {
@@ -104,6 +220,12 @@ impl HashTable4K {
         Self { dict }
     }
 
+    /// Same as `new`, but returns `None` if the table can't be allocated.
+    #[inline]
+    pub fn try_new() -> Option<Self> {
+        try_alloc_table().map(|dict| Self { dict })
+    }
+
     #[cold]
     #[allow(dead_code)]
     pub fn reposition(&mut self, offset: u32) {
@@ -112,6 +234,7 @@ impl HashTable4K {
         }
     }
 }
+#[cfg(feature = "alloc")]
 impl HashTable for HashTable4K {
     #[inline]
     fn get_at(&self, hash: usize) -> usize {
@@ -127,13 +250,17 @@ impl HashTable for HashTable4K {
     }
 }
 
+#[cfg(feature = "alloc")]
 const HASHTABLE_SIZE_8K: usize = 8 * 1024;
+#[cfg(feature = "alloc")]
 const HASH_TABLE_BIT_SHIFT_8K: usize = 3;
 
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 pub struct HashTable8K {
     dict: Box<[u32; HASHTABLE_SIZE_8K]>,
 }
+#[cfg(feature = "alloc")]
 #[allow(dead_code)]
 impl HashTable8K {
     #[inline]
@@ -146,6 +273,7 @@ impl HashTable8K {
         Self { dict }
     }
 }
+#[cfg(feature = "alloc")]
 impl HashTable for HashTable8K {
     #[inline]
     fn get_at(&self, hash: usize) -> usize {
@@ -0,0 +1,420 @@
+//! A stream of compressed blocks with periodic checksums over the uncompressed data.
+//!
+//! This is a lighter alternative to the frame format for protocols which have their own framing
+//! but lack integrity checks. The [`IntegrityWriter`] splits the data into independent blocks and
+//! adds a checkpoint with the checksum of the uncompressed data after every `checksum_interval`
+//! bytes. The [`IntegrityReader`] only returns data once the checksum of its checkpoint matched,
+//! so corrupted data is never handed to the caller.
+//!
+//! The checksum defaults to xxhash32 (seed 0), any `Hasher` implementing `Default` can be used
+//! instead, e.g. a CRC32, the writer and the reader have to use the same.
+//!
+//! # Format
+//! The stream is a sequence of records, each starting with a little endian u32:
+//! - Block: the uncompressed length of the block (1 to 8MB), followed by the compressed length as
+//!   u32 and the compressed block.
+//! - Checkpoint: `u32::MAX`, followed by the checksum (`Hasher::finish`) of the uncompressed data
+//!   since the previous checkpoint as u64.
+//! - End: `0`, the last record. It's preceded by a checkpoint if there's any data after the
+//!   previous one.
+//!
+//! # Example
+//! ```
+//! use std::io::{Read, Write};
+//! use lz4_flex::block::integrity::{IntegrityReader, IntegrityWriter};
+//!
+//! let input = b"checked every 16KB".repeat(10_000);
+//! let mut writer = IntegrityWriter::with_options(Vec::new(), 4 * 1024, 16 * 1024);
+//! writer.write_all(&input).unwrap();
+//! let compressed = writer.finish().unwrap();
+//!
+//! let mut uncompressed = Vec::new();
+//! IntegrityReader::new(&compressed[..])
+//!     .read_to_end(&mut uncompressed)
+//!     .unwrap();
+//! assert_eq!(uncompressed, input);
+//! ```
+
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+
+use super::compress::{compress_internal, get_maximum_output_size};
+use super::hashtable::{HashTable, HashTable4K};
+use super::DecompressError;
+use crate::sink::{ChecksumSink, SliceSink};
+
+/// Record tag of a checkpoint.
+const CHECKPOINT: u32 = u32::MAX;
+/// Record tag of the end of the stream.
+const END: u32 = 0;
+/// The largest block size, same as the largest block size of the frame format.
+const MAX_BLOCK_SIZE: usize = 8 * 1024 * 1024;
+/// The largest checksum interval, which bounds the data buffered by the reader.
+const MAX_CHECKSUM_INTERVAL: usize = 64 * 1024 * 1024;
+
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+const DEFAULT_CHECKSUM_INTERVAL: usize = 1024 * 1024;
+
+/// The xxhash32 with seed 0, the default checksum of the integrity stream.
+#[derive(Clone)]
+pub struct XxHash32(twox_hash::XxHash32);
+
+impl Default for XxHash32 {
+    fn default() -> Self {
+        XxHash32(twox_hash::XxHash32::with_seed(0))
+    }
+}
+
+impl Hasher for XxHash32 {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+impl fmt::Debug for XxHash32 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("XxHash32").finish()
+    }
+}
+
+/// Errors of the [`IntegrityReader`]. `read` returns them wrapped in an `io::Error` of kind
+/// `InvalidData`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum IntegrityError {
+    /// The checksum of a checkpoint doesn't match the data.
+    ChecksumMismatch {
+        /// Index of the checkpoint, starting at 0.
+        checkpoint: u64,
+        /// Offset of the first uncompressed byte covered by the checkpoint.
+        start: u64,
+        /// Offset after the last uncompressed byte covered by the checkpoint.
+        end: u64,
+    },
+    /// A block failed to decompress.
+    DecompressionError(DecompressError),
+    /// A record is malformed, e.g. a block is larger than 8MB or decompresses to a different
+    /// length than stated.
+    InvalidRecord,
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntegrityError::ChecksumMismatch {
+                checkpoint,
+                start,
+                end,
+            } => write!(
+                f,
+                "checksum of checkpoint {checkpoint} doesn't match the data at {start}..{end}"
+            ),
+            IntegrityError::DecompressionError(err) => write!(f, "{err}"),
+            IntegrityError::InvalidRecord => f.write_str("invalid record"),
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+impl From<IntegrityError> for io::Error {
+    fn from(e: IntegrityError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// A writer compressing data into a stream of blocks with checkpoints, see the
+/// [module documentation](self).
+///
+/// The stream is only complete after [`finish`](Self::finish), which writes the end record.
+pub struct IntegrityWriter<W: Write, H: Hasher + Default = XxHash32> {
+    w: W,
+    /// Uncompressed data of the current block.
+    src: Vec<u8>,
+    /// Buffer of a compressed block.
+    dst: Vec<u8>,
+    table: HashTable4K,
+    /// Checksum of the data since the last checkpoint.
+    hasher: H,
+    /// Number of uncompressed bytes since the last checkpoint.
+    unchecked: usize,
+    block_size: usize,
+    checksum_interval: usize,
+}
+
+impl<W: Write> IntegrityWriter<W> {
+    /// Creates a writer with xxhash32 checksums, 64KB blocks and a checkpoint every 1MB.
+    pub fn new(wtr: W) -> Self {
+        Self::with_options(wtr, DEFAULT_BLOCK_SIZE, DEFAULT_CHECKSUM_INTERVAL)
+    }
+
+    /// Creates a writer with xxhash32 checksums, blocks of `block_size` bytes, and a checkpoint
+    /// after the first block which completes `checksum_interval` bytes since the previous
+    /// checkpoint.
+    ///
+    /// # Panics
+    /// Panics if `block_size` isn't between 1 byte and 8MB, or `checksum_interval` isn't between
+    /// 1 byte and 64MB.
+    pub fn with_options(wtr: W, block_size: usize, checksum_interval: usize) -> Self {
+        Self::with_hasher(wtr, block_size, checksum_interval)
+    }
+}
+
+impl<W: Write, H: Hasher + Default> IntegrityWriter<W, H> {
+    /// Same as `with_options`, but with checksums of `H`, e.g.
+    /// `IntegrityWriter::<_, MyHasher>::with_hasher(wtr, block_size, checksum_interval)`.
+    ///
+    /// # Panics
+    /// Panics if `block_size` isn't between 1 byte and 8MB, or `checksum_interval` isn't between
+    /// 1 byte and 64MB.
+    pub fn with_hasher(wtr: W, block_size: usize, checksum_interval: usize) -> Self {
+        assert!(
+            block_size > 0 && block_size <= MAX_BLOCK_SIZE,
+            "block size must be between 1 byte and 8MB"
+        );
+        assert!(
+            checksum_interval > 0 && checksum_interval <= MAX_CHECKSUM_INTERVAL,
+            "checksum interval must be between 1 byte and 64MB"
+        );
+        IntegrityWriter {
+            w: wtr,
+            src: Vec::with_capacity(block_size),
+            dst: vec![0; get_maximum_output_size(block_size)],
+            table: HashTable4K::new(),
+            hasher: H::default(),
+            unchecked: 0,
+            block_size,
+            checksum_interval,
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Writes the buffered data and the end record, and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        self.w.write_all(&END.to_le_bytes())?;
+        Ok(self.w)
+    }
+
+    /// Compresses the buffered data into a block, followed by a checkpoint if `checkpoint` is set
+    /// or `checksum_interval` is reached.
+    fn write_block(&mut self, checkpoint: bool) -> io::Result<()> {
+        if !self.src.is_empty() {
+            self.table.clear();
+            let sink = &mut SliceSink::new(&mut self.dst, 0);
+            // The output is sized for the worst case, so compression can't fail.
+            let compressed_len =
+                compress_internal::<_, false, _>(&self.src, 0, sink, &mut self.table, b"", 0)
+                    .unwrap();
+            self.hasher.write(&self.src);
+            self.w.write_all(&(self.src.len() as u32).to_le_bytes())?;
+            self.w.write_all(&(compressed_len as u32).to_le_bytes())?;
+            self.w.write_all(&self.dst[..compressed_len])?;
+            self.unchecked += self.src.len();
+            self.src.clear();
+        }
+        if self.unchecked >= self.checksum_interval || (checkpoint && self.unchecked > 0) {
+            self.w.write_all(&CHECKPOINT.to_le_bytes())?;
+            self.w.write_all(&self.hasher.finish().to_le_bytes())?;
+            self.hasher = H::default();
+            self.unchecked = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, H: Hasher + Default> Write for IntegrityWriter<W, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.block_size - self.src.len());
+        self.src.extend_from_slice(&buf[..len]);
+        if self.src.len() == self.block_size {
+            self.write_block(false)?;
+        }
+        Ok(len)
+    }
+
+    /// Writes the buffered data as a block followed by a checkpoint, so that the reader can
+    /// return all data written so far, and flushes the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block(true)?;
+        self.w.flush()
+    }
+}
+
+impl<W: Write + fmt::Debug, H: Hasher + Default> fmt::Debug for IntegrityWriter<W, H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IntegrityWriter")
+            .field("w", &self.w)
+            .field("block_size", &self.block_size)
+            .field("checksum_interval", &self.checksum_interval)
+            .finish()
+    }
+}
+
+/// A reader decompressing a stream written by [`IntegrityWriter`], see the
+/// [module documentation](self).
+///
+/// The data of a checkpoint is buffered until its checksum is verified. A stream ending without
+/// the end record fails with an `UnexpectedEof` error.
+pub struct IntegrityReader<R: Read, H: Hasher + Default = XxHash32> {
+    r: R,
+    /// Decompressed data, the data before `verified` has been verified.
+    buffer: Vec<u8>,
+    /// Index into buffer: the data not read yet by the caller.
+    pos: usize,
+    /// Index into buffer: the end of the verified data.
+    verified: usize,
+    /// Buffer of a compressed block.
+    src: Vec<u8>,
+    /// Checksum of the data after `verified`.
+    hasher: H,
+    /// Index of the next checkpoint.
+    checkpoint: u64,
+    /// Uncompressed offset of `verified`.
+    offset: u64,
+    /// Whether the end record has been read.
+    finished: bool,
+}
+
+impl<R: Read> IntegrityReader<R> {
+    /// Creates a reader for a stream with xxhash32 checksums.
+    pub fn new(rdr: R) -> Self {
+        Self::with_hasher(rdr)
+    }
+}
+
+impl<R: Read, H: Hasher + Default> IntegrityReader<R, H> {
+    /// Creates a reader for a stream with checksums of `H`, e.g.
+    /// `IntegrityReader::<_, MyHasher>::with_hasher(rdr)`.
+    pub fn with_hasher(rdr: R) -> Self {
+        IntegrityReader {
+            r: rdr,
+            buffer: Vec::new(),
+            pos: 0,
+            verified: 0,
+            src: Vec::new(),
+            hasher: H::default(),
+            checkpoint: 0,
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Consumes the reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0u8; 4];
+        self.r.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    /// Reads the next record.
+    fn read_record(&mut self) -> io::Result<()> {
+        match self.read_u32()? {
+            END => {
+                if self.buffer.len() != self.verified {
+                    return Err(IntegrityError::InvalidRecord.into());
+                }
+                self.finished = true;
+            }
+            CHECKPOINT => {
+                let mut buf = [0u8; 8];
+                self.r.read_exact(&mut buf)?;
+                let end = self.offset + (self.buffer.len() - self.verified) as u64;
+                if u64::from_le_bytes(buf) != self.hasher.finish() {
+                    return Err(IntegrityError::ChecksumMismatch {
+                        checkpoint: self.checkpoint,
+                        start: self.offset,
+                        end,
+                    }
+                    .into());
+                }
+                self.hasher = H::default();
+                self.checkpoint += 1;
+                self.offset = end;
+                self.verified = self.buffer.len();
+            }
+            uncompressed_len => {
+                let uncompressed_len = uncompressed_len as usize;
+                let compressed_len = self.read_u32()? as usize;
+                let unverified = self.buffer.len() - self.verified;
+                if uncompressed_len > MAX_BLOCK_SIZE
+                    || compressed_len > get_maximum_output_size(uncompressed_len)
+                    || unverified + uncompressed_len > MAX_CHECKSUM_INTERVAL + MAX_BLOCK_SIZE
+                {
+                    return Err(IntegrityError::InvalidRecord.into());
+                }
+                self.src.resize(compressed_len, 0);
+                self.r.read_exact(&mut self.src)?;
+
+                let start = self.buffer.len();
+                self.buffer.resize(start + uncompressed_len, 0);
+                let mut sink = ChecksumSink::with_hasher(
+                    SliceSink::new(&mut self.buffer, start),
+                    &mut self.hasher,
+                );
+                let result =
+                    super::decompress::decompress_internal::<false, _>(&self.src, &mut sink, b"");
+                sink.into_hasher();
+                match result {
+                    Ok(len) if len == uncompressed_len => {}
+                    Ok(_) => return Err(IntegrityError::InvalidRecord.into()),
+                    Err(err) => return Err(IntegrityError::DecompressionError(err).into()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read, H: Hasher + Default> Read for IntegrityReader<R, H> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.verified {
+                let len = buf.len().min(self.verified - self.pos);
+                buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+                self.pos += len;
+                if self.pos == self.verified {
+                    self.buffer.drain(..self.verified);
+                    self.pos = 0;
+                    self.verified = 0;
+                }
+                return Ok(len);
+            }
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.read_record()?;
+        }
+    }
+}
+
+impl<R: Read + fmt::Debug, H: Hasher + Default> fmt::Debug for IntegrityReader<R, H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IntegrityReader")
+            .field("r", &self.r)
+            .field("checkpoint", &self.checkpoint)
+            .field("offset", &self.offset)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
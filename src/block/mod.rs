@@ -2,7 +2,8 @@
 //!
 //! As defined in <https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md>
 //!
-//! Currently for no_std support only the block format is supported.
+//! Currently for no_std support only the block format is supported. Without the `alloc` feature
+//! only the functions compressing and decompressing into a slice are available.
 //!
 //! # Example: block format roundtrip
 //! ```
@@ -16,10 +17,17 @@
 
 #[cfg_attr(feature = "safe-encode", forbid(unsafe_code))]
 pub(crate) mod compress;
+#[cfg(feature = "alloc")]
+#[forbid(unsafe_code)]
+mod compress_job;
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "safe-encode", forbid(unsafe_code))]
+mod compress_streaming;
 pub(crate) mod hashtable;
 
-#[cfg(feature = "safe-decode")]
-#[cfg_attr(feature = "safe-decode", forbid(unsafe_code))]
+// Without `safe-decode`, the safe decoder is only used by `decompress_checked`.
+#[cfg_attr(not(feature = "safe-decode"), allow(dead_code))]
+#[forbid(unsafe_code)]
 pub(crate) mod decompress_safe;
 #[cfg(feature = "safe-decode")]
 pub(crate) use decompress_safe as decompress;
@@ -27,12 +35,54 @@ pub(crate) use decompress_safe as decompress;
 #[cfg(not(feature = "safe-decode"))]
 pub(crate) mod decompress;
 
+#[cfg(feature = "std")]
+#[forbid(unsafe_code)]
+mod decompress_streaming;
+
+#[cfg(feature = "frame")]
+#[forbid(unsafe_code)]
+pub mod integrity;
+
 pub use compress::*;
+#[cfg(feature = "alloc")]
+pub use compress_job::CompressJob;
+#[cfg(feature = "alloc")]
+pub use compress_streaming::StreamCompressor;
 pub use decompress::*;
+// Only implemented by the safe decoder, which is always compiled.
+#[cfg(not(feature = "safe-decode"))]
+pub use decompress_safe::decompress_into_strided;
+#[cfg(feature = "std")]
+pub use decompress_streaming::decompress_streaming;
+pub use hashtable::HashTable;
 
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 use core::fmt;
+#[cfg(feature = "alloc")]
+use core::ops::Range;
 
-pub(crate) const WINDOW_SIZE: usize = 64 * 1024;
+/// The size of the LZ4 window. A match can reference at most the last `WINDOW_SIZE` bytes before
+/// it, so that much history has to be retained to compress or decompress linked blocks, e.g. with
+/// [`compress_continue`] and [`decompress_continue`].
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_continue, decompress_continue, WINDOW_SIZE};
+/// let blocks = [&b"hello world, "[..], b"hello world, hello world"];
+/// // Only the tail of the decompressed data is retained between blocks
+/// let mut history = Vec::with_capacity(WINDOW_SIZE + blocks[1].len());
+/// let mut output = Vec::new();
+/// for block in blocks {
+///     let compressed = compress_continue(&history, block);
+///     let decompressed = decompress_continue(&compressed, &history, block.len()).unwrap();
+///     history.extend_from_slice(&decompressed);
+///     history.drain(..history.len().saturating_sub(WINDOW_SIZE));
+///     output.extend_from_slice(&decompressed);
+/// }
+/// assert_eq!(output, blocks.concat());
+/// ```
+pub const WINDOW_SIZE: usize = 64 * 1024;
 
 /// https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md#end-of-block-restrictions
 /// The last match must start at least 12 bytes before the end of block. The last match is part of
@@ -52,22 +102,28 @@ const LAST_LITERALS: usize = 5;
 /// Due the way the compression loop is arrange we may read up to (register_size - 2) bytes from the
 /// current position. So we must end the matches 6 bytes before the end, 1 more than required by the
 /// spec.
+#[allow(dead_code)]
 const END_OFFSET: usize = LAST_LITERALS + 1;
 
 /// https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md#end-of-block-restrictions
 /// Minimum length of a block
 ///
 /// MFLIMIT + 1 for the token.
+#[allow(dead_code)]
 const LZ4_MIN_LENGTH: usize = MFLIMIT + 1;
 
+#[allow(dead_code)]
 const MAXD_LOG: usize = 16;
-const MAX_DISTANCE: usize = (1 << MAXD_LOG) - 1;
+/// The maximum distance of a match, i.e. the largest offset which can be encoded. A match can start
+/// at most `MAX_DISTANCE` bytes before the current position, which is within the last
+/// [`WINDOW_SIZE`] bytes.
+pub const MAX_DISTANCE: usize = (1 << MAXD_LOG) - 1;
 
 #[allow(dead_code)]
 const MATCH_LENGTH_MASK: u32 = (1_u32 << 4) - 1; // 0b1111 / 15
 
-/// The minimum length of a duplicate
-const MINMATCH: usize = 4;
+/// The minimum length of a match. Shorter duplicates are encoded as literals.
+pub const MINMATCH: usize = 4;
 
 #[allow(dead_code)]
 const FASTLOOP_SAFE_DISTANCE: usize = 64;
@@ -93,6 +149,47 @@ pub enum DecompressError {
     ExpectedAnotherByte,
     /// Deduplication offset out of bounds (not in buffer).
     OffsetOutOfBounds,
+    /// The output buffer could not be allocated.
+    AllocationFailed,
+    /// The decompressed size differs from the size prepended to the input.
+    UncompressedSizeDiffers {
+        /// Size prepended to the input
+        expected: usize,
+        /// Actual size of the decompressed data
+        actual: usize,
+    },
+    /// The decompressed data differs from the original input, see `verify_roundtrip`.
+    RoundtripMismatch {
+        /// Position of the first differing byte
+        position: usize,
+    },
+    /// The version tag of the input is unknown, see `decompress_tagged`.
+    UnknownTag {
+        /// The first byte of the input
+        tag: u8,
+    },
+    /// The range of the input is out of bounds of the buffer, see `decompress_range`.
+    RangeOutOfBounds {
+        /// Start of the range
+        start: usize,
+        /// End of the range
+        end: usize,
+        /// Length of the buffer
+        len: usize,
+    },
+    /// An item of the decompressed archive is truncated, see `decompress_archive`.
+    InvalidArchive {
+        /// Index of the item
+        item: usize,
+    },
+    /// The input continues after the block is complete, see `decompress_strict`.
+    TrailingBytes {
+        /// Number of bytes after the end of the block
+        count: usize,
+    },
+    /// The uncompressed size read from the header of the input is invalid, see
+    /// `tagged_uncompressed_size`. It overflows, or is larger than the block can decompress to.
+    InvalidSize,
 }
 
 #[derive(Debug)]
@@ -100,7 +197,19 @@ pub enum DecompressError {
 /// Errors that can happen during compression.
 pub enum CompressError {
     /// The provided output is too small.
-    OutputTooSmall,
+    OutputTooSmall {
+        /// Output size needed in the worst case, see `get_maximum_output_size`
+        needed: usize,
+        /// Actual size of the provided output
+        provided: usize,
+    },
+    /// The output buffer could not be allocated.
+    AllocationFailed,
+    /// The compressed data exceeds the size budget, see `compress_within`.
+    OutputBudgetExceeded {
+        /// The maximum size of the compressed data
+        max_output: usize,
+    },
 }
 
 impl fmt::Display for DecompressError {
@@ -122,6 +231,41 @@ impl fmt::Display for DecompressError {
             DecompressError::OffsetOutOfBounds => {
                 f.write_str("the offset to copy is not contained in the decompressed buffer")
             }
+            DecompressError::AllocationFailed => {
+                f.write_str("failed to allocate the output buffer")
+            }
+            DecompressError::UncompressedSizeDiffers { expected, actual } => {
+                write!(
+                    f,
+                    "the decompressed size differs from the prepended size, actual {actual}, \
+                     expected {expected}"
+                )
+            }
+            DecompressError::RoundtripMismatch { position } => {
+                write!(
+                    f,
+                    "the decompressed data differs from the input at position {position}"
+                )
+            }
+            DecompressError::UnknownTag { tag } => {
+                write!(f, "unknown version tag {tag}")
+            }
+            DecompressError::RangeOutOfBounds { start, end, len } => {
+                write!(
+                    f,
+                    "the input range {start}..{end} is out of bounds of the buffer of length {len}"
+                )
+            }
+            DecompressError::InvalidArchive { item } => {
+                write!(f, "item {item} of the archive is truncated")
+            }
+            DecompressError::TrailingBytes { count } => {
+                write!(
+                    f,
+                    "{count} bytes remain in the input after the end of the block"
+                )
+            }
+            DecompressError::InvalidSize => f.write_str("the uncompressed size is invalid"),
         }
     }
 }
@@ -129,10 +273,18 @@ impl fmt::Display for DecompressError {
 impl fmt::Display for CompressError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            CompressError::OutputTooSmall => f.write_str(
-                "output is too small for the compressed data, use get_maximum_output_size to \
-                 reserve enough space",
+            CompressError::OutputTooSmall { needed, provided } => write!(
+                f,
+                "output is too small for the compressed data, provided {provided}, needed \
+                 {needed} (see get_maximum_output_size)"
             ),
+            CompressError::AllocationFailed => f.write_str("failed to allocate the output buffer"),
+            CompressError::OutputBudgetExceeded { max_output } => {
+                write!(
+                    f,
+                    "compressed data exceeds the budget of {max_output} bytes"
+                )
+            }
         }
     }
 }
@@ -154,3 +306,941 @@ pub fn uncompressed_size(input: &[u8]) -> Result<(usize, &[u8]), DecompressError
     let rest = &input[4..];
     Ok((uncompressed_size, rest))
 }
+
+/// The version tag written by `compress_tagged` as the first byte, which names the encoding of
+/// the uncompressed size following it.
+///
+/// The values of the tags are part of the format and never change, new encodings get new tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SizeTag {
+    /// The size is a little endian u32, like with `compress_prepend_size`.
+    U32 = 0,
+    /// The size is a little endian u64.
+    U64 = 1,
+    /// The size is a LEB128 varint, i.e. 7 bits per byte, least significant first, with the high
+    /// bit set on all but the last byte.
+    Varint = 2,
+}
+
+impl SizeTag {
+    /// Returns the tag with the value `tag`, if it's known.
+    pub fn from_u8(tag: u8) -> Option<SizeTag> {
+        match tag {
+            0 => Some(SizeTag::U32),
+            1 => Some(SizeTag::U64),
+            2 => Some(SizeTag::Varint),
+            _ => None,
+        }
+    }
+
+    /// Writes the tag followed by `size` into `buf` and returns the number of bytes written.
+    ///
+    /// # Panics
+    /// Panics if `size` doesn't fit into a u32 with `SizeTag::U32`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn write_header(self, size: usize, buf: &mut [u8; MAX_TAGGED_HEADER_SIZE]) -> usize {
+        buf[0] = self as u8;
+        let size = size as u64;
+        match self {
+            SizeTag::U32 => {
+                let size = u32::try_from(size).expect("size exceeds u32::MAX");
+                buf[1..5].copy_from_slice(&size.to_le_bytes());
+                5
+            }
+            SizeTag::U64 => {
+                buf[1..9].copy_from_slice(&size.to_le_bytes());
+                9
+            }
+            SizeTag::Varint => {
+                let mut size = size;
+                let mut len = 1;
+                while size >= 0x80 {
+                    buf[len] = size as u8 | 0x80;
+                    size >>= 7;
+                    len += 1;
+                }
+                buf[len] = size as u8;
+                len + 1
+            }
+        }
+    }
+}
+
+/// Maximum size of the tag and the size written by `compress_tagged`, a tag and a varint of a u64.
+#[cfg(feature = "alloc")]
+pub(crate) const MAX_TAGGED_HEADER_SIZE: usize = 1 + 10;
+
+/// This can be used in conjunction with `decompress_tagged`.
+/// It will read the version tag and the uncompressed size following it, and return them with the
+/// rest of the bytes after the size.
+///
+/// Returns `DecompressError::UnknownTag` for an unknown tag, `DecompressError::ExpectedAnotherByte`
+/// if the size is truncated, and `DecompressError::InvalidSize` if the varint overflows a u64 or
+/// the size doesn't fit into a usize.
+pub fn tagged_uncompressed_size(input: &[u8]) -> Result<(SizeTag, usize, &[u8]), DecompressError> {
+    let (&tag, input) = input
+        .split_first()
+        .ok_or(DecompressError::ExpectedAnotherByte)?;
+    let tag = SizeTag::from_u8(tag).ok_or(DecompressError::UnknownTag { tag })?;
+    let (size, rest) = match tag {
+        SizeTag::U32 => {
+            let (size, rest) = uncompressed_size(input)?;
+            (size as u64, rest)
+        }
+        SizeTag::U64 => {
+            let size = input.get(..8).ok_or(DecompressError::ExpectedAnotherByte)?;
+            (u64::from_le_bytes(size.try_into().unwrap()), &input[8..])
+        }
+        SizeTag::Varint => {
+            let mut size = 0u64;
+            let mut pos = 0;
+            loop {
+                let byte = *input.get(pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+                let bits = (byte & 0x7F) as u64;
+                let shift = 7 * pos as u32;
+                if shift >= u64::BITS || (bits << shift) >> shift != bits {
+                    return Err(DecompressError::InvalidSize);
+                }
+                size |= bits << shift;
+                pos += 1;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            (size, &input[pos..])
+        }
+    };
+    let size = usize::try_from(size).map_err(|_| DecompressError::InvalidSize)?;
+    Ok((tag, size, rest))
+}
+
+/// Decompress all bytes of `input` written by `compress_tagged`, dispatching on its version tag.
+///
+/// Returns `DecompressError::UnknownTag` if the tag is unknown, e.g. written by a newer version
+/// of this crate, see `tagged_uncompressed_size`. The size is untrusted, so
+/// `DecompressError::InvalidSize` is returned before the output is allocated if it's larger than
+/// the block can decompress to.
+#[cfg(feature = "alloc")]
+pub fn decompress_tagged(input: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    let (_tag, uncompressed_size, input) = tagged_uncompressed_size(input)?;
+    check_max_uncompressed_size(uncompressed_size, input.len())?;
+    check_uncompressed_size(decompress(input, uncompressed_size)?, uncompressed_size)
+}
+
+/// Checks an untrusted uncompressed size against the largest size a block of `compressed_len`
+/// bytes can decompress to, before the output is allocated.
+#[cfg(feature = "alloc")]
+#[inline]
+fn check_max_uncompressed_size(
+    uncompressed_size: usize,
+    compressed_len: usize,
+) -> Result<(), DecompressError> {
+    // Every byte of a match length extension adds at most 255 bytes to the output
+    if uncompressed_size > compressed_len.saturating_mul(255) {
+        return Err(DecompressError::InvalidSize);
+    }
+    Ok(())
+}
+
+/// Checks that the output of decompressing a size prepended input has the prepended size.
+#[cfg(feature = "alloc")]
+#[inline]
+fn check_uncompressed_size(
+    decompressed: Vec<u8>,
+    uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    if decompressed.len() != uncompressed_size {
+        return Err(DecompressError::UncompressedSizeDiffers {
+            expected: uncompressed_size,
+            actual: decompressed.len(),
+        });
+    }
+    Ok(decompressed)
+}
+
+/// Panics in debug builds if `input` and `output` overlap.
+///
+/// Safe Rust can't create overlapping `&[u8]` and `&mut [u8]`, but FFI callers building them from
+/// raw pointers can. The (de)compressor reads `input` after writing to `output`, so the result
+/// would be silently corrupted, or undefined behavior in the unsafe implementations.
+#[inline]
+#[track_caller]
+pub(crate) fn debug_assert_disjoint(input: &[u8], output: &[u8]) {
+    let (input, output) = (input.as_ptr_range(), output.as_ptr_range());
+    debug_assert!(
+        input.is_empty()
+            || output.is_empty()
+            || input.end <= output.start
+            || output.end <= input.start,
+        "lz4_flex: input {input:?} and output {output:?} overlap, they must be separate buffers"
+    );
+}
+
+/// Decompress all bytes of `input`, which are expected to decompress to exactly
+/// `uncompressed_size` bytes.
+///
+/// Unlike `decompress`, which treats the size as an upper bound, this returns
+/// `DecompressError::UncompressedSizeDiffers` if the block decompresses to fewer bytes. The
+/// decoder always consumes the whole input and checks all bounds, so together with the size this
+/// catches truncated or otherwise corrupted blocks without extra cost.
+#[cfg(feature = "alloc")]
+pub fn decompress_exact(
+    input: &[u8],
+    uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    check_uncompressed_size(decompress(input, uncompressed_size)?, uncompressed_size)
+}
+
+/// Decompress all bytes of `input`, which are expected to be exactly one block decompressing to
+/// `uncompressed_size` bytes.
+///
+/// Like `decompress_exact`, but also returns `DecompressError::TrailingBytes` if the input
+/// continues after the sequence which completes the `uncompressed_size` bytes. A block has no
+/// end marker, so `decompress` reads such bytes as further sequences, which fails with an
+/// unrelated error at best, or appends their output if the size is only an upper bound. This
+/// detects framing bugs of containers, at the cost of a pass over the sequence headers (literals
+/// are skipped) before decompressing.
+///
+/// ```
+/// use lz4_flex::block::{compress, decompress_strict, DecompressError};
+/// let mut compressed = compress(b"Hello, strict mode!");
+/// assert_eq!(decompress_strict(&compressed, 19).unwrap(), b"Hello, strict mode!");
+/// compressed.push(0);
+/// assert!(matches!(
+///     decompress_strict(&compressed, 19),
+///     Err(DecompressError::TrailingBytes { count: 1 })
+/// ));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_strict(
+    input: &[u8],
+    uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let end = block_end(input, uncompressed_size)?;
+    if end < input.len() {
+        return Err(DecompressError::TrailingBytes {
+            count: input.len() - end,
+        });
+    }
+    decompress_exact(input, uncompressed_size)
+}
+
+/// Returns the end of the sequence in `input` after which the block decompressed to
+/// `uncompressed_size` bytes, without decompressing it.
+///
+/// Only the sequence headers are validated, offsets are checked when decompressing. If the block
+/// exceeds `uncompressed_size`, the whole input is returned, so decompressing reports the error.
+#[cfg(feature = "alloc")]
+fn block_end(input: &[u8], uncompressed_size: usize) -> Result<usize, DecompressError> {
+    let mut pos = 0;
+    let mut decompressed_len = 0usize;
+    loop {
+        let token = *input.get(pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+        pos += 1;
+        let literal_length = read_length(input, &mut pos, (token >> 4) as usize)?;
+        if literal_length > input.len() - pos {
+            return Err(DecompressError::LiteralOutOfBounds);
+        }
+        pos += literal_length;
+        decompressed_len = decompressed_len.saturating_add(literal_length);
+        if decompressed_len > uncompressed_size {
+            return Ok(input.len());
+        }
+        if pos == input.len() || decompressed_len == uncompressed_size {
+            return Ok(pos);
+        }
+        if input.len() - pos < 2 {
+            return Err(DecompressError::ExpectedAnotherByte);
+        }
+        pos += 2;
+        let match_length = MINMATCH + read_length(input, &mut pos, (token & 0xF) as usize)?;
+        decompressed_len = decompressed_len.saturating_add(match_length);
+        if decompressed_len > uncompressed_size {
+            return Ok(input.len());
+        }
+    }
+}
+
+/// Decompress the next block of a stream of `compress_length_prefixed` blocks at the position of
+/// `cursor`, and advance `cursor` past it.
+///
+/// Returns `None` if `cursor` is at the end of its data. If the block is truncated or doesn't
+/// decompress to the prepended size, an error is returned and `cursor` isn't moved.
+///
+/// The prepended size is untrusted, so it's checked before the output is allocated: A block can't
+/// decompress to more than 255 times its compressed length, larger sizes return
+/// `DecompressError::InvalidSize`.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_length_prefixed, decompress_next_from_cursor};
+/// use std::io::Cursor;
+/// let stream = [compress_length_prefixed(b"first"), compress_length_prefixed(b"second")].concat();
+/// let mut cursor = Cursor::new(&stream[..]);
+/// assert_eq!(decompress_next_from_cursor(&mut cursor).unwrap().unwrap(), b"first");
+/// assert_eq!(decompress_next_from_cursor(&mut cursor).unwrap().unwrap(), b"second");
+/// assert_eq!(decompress_next_from_cursor(&mut cursor).unwrap(), None);
+/// ```
+#[cfg(feature = "std")]
+pub fn decompress_next_from_cursor<T: AsRef<[u8]>>(
+    cursor: &mut std::io::Cursor<T>,
+) -> Result<Option<Vec<u8>>, DecompressError> {
+    let data = cursor.get_ref().as_ref();
+    let pos = usize::try_from(cursor.position()).map_or(data.len(), |pos| pos.min(data.len()));
+    let input = &data[pos..];
+    if input.is_empty() {
+        return Ok(None);
+    }
+    // Both lengths are read like the size of `compress_prepend_size`
+    let (size, rest) = uncompressed_size(input)?;
+    let (compressed_len, rest) = uncompressed_size(rest)?;
+    let block = rest
+        .get(..compressed_len)
+        .ok_or(DecompressError::ExpectedAnotherByte)?;
+    check_max_uncompressed_size(size, compressed_len)?;
+    let decompressed = decompress_exact(block, size)?;
+    cursor.set_position((pos + 8 + compressed_len) as u64);
+    Ok(Some(decompressed))
+}
+
+/// Decompress a block of a linked block sequence into `output`, without allocating.
+///
+/// `window` is the data decompressed from the previous block(s). Matches in `input` may
+/// reference up to `WINDOW_SIZE` (64KB) bytes back into it, so the last 64KB of prior output are
+/// sufficient. Together with `output` this allows decoding multi-block streams with two
+/// caller-provided buffers.
+///
+/// Same as [`decompress_into_with_dict`] with `window` as dictionary.
+///
+/// Returns the number of bytes written (decompressed) into `output`.
+#[inline]
+pub fn decompress_block_linked(
+    input: &[u8],
+    output: &mut [u8],
+    window: &[u8],
+) -> Result<usize, DecompressError> {
+    decompress_into_with_dict(input, output, window)
+}
+
+/// Decompress the block at `buffer[range]`, e.g. a block embedded in a container, whose position
+/// is tracked as offsets.
+///
+/// `min_uncompressed_size` is handled like in `decompress`. Unlike slicing `buffer`, this returns
+/// `DecompressError::RangeOutOfBounds` instead of panicking if `range` is out of bounds.
+#[cfg(feature = "alloc")]
+pub fn decompress_range(
+    buffer: &[u8],
+    range: Range<usize>,
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let input = buffer
+        .get(range.clone())
+        .ok_or(DecompressError::RangeOutOfBounds {
+            start: range.start,
+            end: range.end,
+            len: buffer.len(),
+        })?;
+    decompress(input, min_uncompressed_size)
+}
+
+/// Decompress an archive of `compress_archive` into its items.
+#[cfg(feature = "alloc")]
+pub fn decompress_archive(input: &[u8]) -> Result<Vec<Vec<u8>>, DecompressError> {
+    let archive = decompress_size_prepended(input)?;
+    let mut items = Vec::new();
+    let mut rest = &archive[..];
+    while !rest.is_empty() {
+        let invalid = || DecompressError::InvalidArchive { item: items.len() };
+        let (len, data) = uncompressed_size(rest).map_err(|_| invalid())?;
+        let item = data.get(..len).ok_or_else(invalid)?;
+        items.push(item.to_vec());
+        rest = &data[len..];
+    }
+    Ok(items)
+}
+
+/// Decompress all bytes of `input` into a new vec with the safe decoder, which is written in safe
+/// Rust only. This is the decoder `decompress` uses with the `safe-decode` feature.
+///
+/// Without `safe-decode`, both decoders are compiled, so the decoder can be chosen per call, e.g.
+/// this one for untrusted data and the faster `decompress_unchecked` for trusted data.
+#[cfg(feature = "alloc")]
+pub fn decompress_checked(
+    input: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress_safe::decompress(input, min_uncompressed_size)
+}
+
+/// Decompress all bytes of `input` into a new vec with the decoder which uses unsafe code. This is
+/// the decoder `decompress` uses without the `safe-decode` feature, so this is only available
+/// without it, see `decompress_checked`.
+///
+/// # Safety contract
+/// The decoder copies with raw pointers. Before each copy, the lengths and offsets read from
+/// `input` are checked against, or clamped to, the bounds of `input` and the output. So invalid
+/// input doesn't cause undefined behavior, but an error or garbage output, like with
+/// `decompress_checked`. Unlike for the safe decoder, this isn't guaranteed by the compiler, but
+/// only by these checks, which are covered by tests and fuzzing. Prefer `decompress_checked` for
+/// untrusted data.
+#[cfg(all(feature = "alloc", not(feature = "safe-decode")))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "safe-decode"))))]
+pub fn decompress_unchecked(
+    input: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress::decompress(input, min_uncompressed_size)
+}
+
+/// Decompress all bytes of `input`, using `size_hint` as the initial output size and growing the
+/// output if it's too small.
+///
+/// Unlike `decompress`, which returns `DecompressError::OutputTooSmall` if the uncompressed data
+/// is larger than its size argument, this works with a rough estimate of the uncompressed size.
+/// If the output is too small, decoding restarts with an output at least twice as large, so an
+/// underestimate costs at most about twice the decoding work. A block can't decompress to more
+/// than about 255 times its size, which bounds the growth for corrupted input.
+#[cfg(feature = "alloc")]
+pub fn decompress_grow(input: &[u8], size_hint: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut output = vec![0u8; size_hint];
+    loop {
+        match decompress_into(input, &mut output) {
+            Ok(len) => {
+                output.truncate(len);
+                return Ok(output);
+            }
+            Err(DecompressError::OutputTooSmall { expected, .. }) => {
+                let new_len = expected.max(output.len().saturating_mul(2));
+                output.resize(new_len, 0);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Decompress a block compressed with `compress_continue`, which continues `prev_window`.
+///
+/// `prev_window` needs to end with the same (up to 64KB of) data passed to `compress_continue`.
+/// `min_uncompressed_size` is handled like in `decompress`.
+#[cfg(feature = "alloc")]
+pub fn decompress_continue(
+    input: &[u8],
+    prev_window: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress_with_dict(input, min_uncompressed_size, prev_window)
+}
+
+/// Decompress all bytes of `input` into a new vec, like [`decompress`], specialized for small
+/// outputs of up to around 100 bytes, e.g. many small values of a database.
+///
+/// For small outputs, the fast loop of the general decoder is rarely taken, since it has to stay
+/// 34 bytes away from the end of the output, so its setup only adds overhead. This decodes every
+/// sequence with a simple loop instead, and doesn't zero the output first. For outputs of a few
+/// hundred bytes or more, [`decompress`] is faster.
+///
+/// Returns the same errors as `decompress(input, min_uncompressed_size)`, except that matches
+/// with offset 0 are rejected with `DecompressError::OffsetOutOfBounds`.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress, decompress_small};
+/// let input = b"cell value, cell value, cell value";
+/// let compressed = compress(input);
+/// assert_eq!(decompress_small(&compressed, input.len()).unwrap(), input);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_small(
+    input: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut output = Vec::with_capacity(min_uncompressed_size);
+    let check_output_size = |output_len: usize, len: usize| {
+        if len > min_uncompressed_size - output_len {
+            return Err(DecompressError::OutputTooSmall {
+                expected: output_len + len,
+                actual: min_uncompressed_size,
+            });
+        }
+        Ok(())
+    };
+    let mut pos = 0;
+    loop {
+        let token = *input.get(pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+        pos += 1;
+        let literal_length = read_length(input, &mut pos, (token >> 4) as usize)?;
+        if literal_length > input.len() - pos {
+            return Err(DecompressError::LiteralOutOfBounds);
+        }
+        check_output_size(output.len(), literal_length)?;
+        output.extend_from_slice(&input[pos..pos + literal_length]);
+        pos += literal_length;
+        if pos == input.len() {
+            return Ok(output);
+        }
+
+        let offset = input
+            .get(pos..pos + 2)
+            .ok_or(DecompressError::ExpectedAnotherByte)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        let match_length = MINMATCH + read_length(input, &mut pos, (token & 0xF) as usize)?;
+        check_output_size(output.len(), match_length)?;
+        if offset == 0 || offset > output.len() {
+            return Err(DecompressError::OffsetOutOfBounds);
+        }
+        // If the match overlaps the data it produces, the copied part doubles with every copy.
+        let start = output.len() - offset;
+        let mut remaining = match_length;
+        while remaining != 0 {
+            let len = remaining.min(output.len() - start);
+            output.extend_from_within(start..start + len);
+            remaining -= len;
+        }
+    }
+}
+
+/// Decompress all bytes of `input` into `output` and compute the xxhash32 (seed 0) of the
+/// decompressed data, e.g. to verify a checksum stored alongside the block.
+///
+/// The output is hashed in small chunks while decompressing, so this avoids a second pass over
+/// the decompressed data. Requires the `frame` feature, which provides the xxhash implementation.
+///
+/// Returns the number of bytes written (decompressed) into `output` and the checksum.
+#[cfg(feature = "frame")]
+#[cfg_attr(docsrs, doc(cfg(feature = "frame")))]
+pub fn decompress_into_with_checksum(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<(usize, u32), DecompressError> {
+    let mut sink = crate::sink::ChecksumSink::new(crate::sink::SliceSink::new(output, 0));
+    let len = decompress::decompress_internal::<false, _>(input, &mut sink, b"")?;
+    Ok((len, sink.finish()))
+}
+
+/// Guesses whether `input` is an LZ4 block (without dictionary), as opposed to raw data, e.g.
+/// for container formats which store either.
+///
+/// This is a heuristic, not a guarantee: the sequences of the block are checked for consistency
+/// without decompressing them. Every match has to reference already decompressed data, and the
+/// block has to end with a sequence of only literals, which is at least 5 bytes long after a
+/// match, as required by the block format. Raw data rarely passes these checks. The block may
+/// still fail to decompress, e.g. if its size is larger than the expected uncompressed size.
+pub fn is_likely_compressed(input: &[u8]) -> bool {
+    is_consistent_block(input).unwrap_or(false)
+}
+
+/// Checks the sequences of a block for `is_likely_compressed`. Returns `None` if the sequences
+/// can't be read, e.g. if the input ends in the middle of a sequence.
+fn is_consistent_block(input: &[u8]) -> Option<bool> {
+    let mut pos = 0;
+    let mut output_len = 0usize;
+    loop {
+        let sequence = read_sequence(input, &mut pos).ok()?;
+        output_len += sequence.literal_length;
+        match sequence.matched {
+            Some((offset, match_length)) => {
+                if offset == 0 || offset > output_len {
+                    return Some(false);
+                }
+                output_len += match_length;
+            }
+            None => {
+                let has_matches = output_len != sequence.literal_length;
+                return Some(
+                    sequence.token & 0xF == 0
+                        && (!has_matches || sequence.literal_length >= LAST_LITERALS),
+                );
+            }
+        }
+    }
+}
+
+/// A sequence of a block, see `read_sequence`.
+struct Sequence {
+    token: u8,
+    literal_length: usize,
+    /// Offset and length of the match, `None` for the last sequence of the block.
+    matched: Option<(usize, usize)>,
+}
+
+/// Reads the sequence starting at `pos` and advances `pos` past it. Fails if the input ends in
+/// the middle of the sequence.
+fn read_sequence(input: &[u8], pos: &mut usize) -> Result<Sequence, DecompressError> {
+    let token = *input
+        .get(*pos)
+        .ok_or(DecompressError::ExpectedAnotherByte)?;
+    *pos += 1;
+    let literal_length = read_length(input, pos, (token >> 4) as usize)?;
+    if literal_length > input.len() - *pos {
+        return Err(DecompressError::LiteralOutOfBounds);
+    }
+    *pos += literal_length;
+    if *pos == input.len() {
+        return Ok(Sequence {
+            token,
+            literal_length,
+            matched: None,
+        });
+    }
+    let offset = input
+        .get(*pos..*pos + 2)
+        .ok_or(DecompressError::ExpectedAnotherByte)?;
+    let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+    *pos += 2;
+    let match_length = MINMATCH + read_length(input, pos, (token & 0xF) as usize)?;
+    Ok(Sequence {
+        token,
+        literal_length,
+        matched: Some((offset, match_length)),
+    })
+}
+
+/// The start of a compressed block: its first token and the length of the leading literal run.
+///
+/// Parsed from the first bytes of a block with `TryFrom<&[u8]>`, without decompressing it, e.g.
+/// to peek at a known prefix of the data in a container format. Only the token and the length
+/// bytes need to be present, the literals themselves are at
+/// `block[literals_start..literals_start + literal_length]`.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress, BlockHeader};
+/// let compressed = compress(b"MAGIC: some data, some data, some data");
+/// let header = BlockHeader::try_from(&compressed[..]).unwrap();
+/// let start = header.literals_start;
+/// assert!(compressed[start..start + header.literal_length].starts_with(b"MAGIC"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// The first token, with the literal length in the high 4 bits and the match length in the
+    /// low 4 bits.
+    pub token: u8,
+    /// Number of literals the block starts with.
+    pub literal_length: usize,
+    /// Position of the first literal in the block, after the token and the length bytes.
+    pub literals_start: usize,
+}
+
+impl TryFrom<&[u8]> for BlockHeader {
+    type Error = DecompressError;
+
+    /// Returns `DecompressError::ExpectedAnotherByte` if `block` ends before the length is
+    /// complete.
+    fn try_from(block: &[u8]) -> Result<Self, Self::Error> {
+        let token = *block.first().ok_or(DecompressError::ExpectedAnotherByte)?;
+        let mut literals_start = 1;
+        let literal_length = read_length(block, &mut literals_start, (token >> 4) as usize)?;
+        Ok(BlockHeader {
+            token,
+            literal_length,
+            literals_start,
+        })
+    }
+}
+
+/// Reads the extra bytes of a length at `pos`, if the 4 bits of the token `len` are 15.
+pub(crate) fn read_length(
+    input: &[u8],
+    pos: &mut usize,
+    mut len: usize,
+) -> Result<usize, DecompressError> {
+    if len == 15 {
+        loop {
+            let extra = *input
+                .get(*pos)
+                .ok_or(DecompressError::ExpectedAnotherByte)?;
+            *pos += 1;
+            len += extra as usize;
+            if extra != 0xFF {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+/// Checks that `input` is a block which decompresses into at most `expected_size` bytes, without
+/// decompressing it.
+///
+/// The sequences are walked like in `disassemble`, and their lengths and offsets are checked
+/// against `expected_size` and the decompressed size so far. Nothing is copied or allocated, so
+/// untrusted data can be rejected cheaply before allocating `expected_size` bytes to decompress
+/// it.
+///
+/// Returns the same error as `decompress(input, expected_size)`. In addition, matches with offset
+/// 0 or which reference data before the start of the output are rejected with
+/// `DecompressError::OffsetOutOfBounds`, which the decoder doesn't detect in all cases. So if the
+/// block is valid, `decompress` succeeds.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress, validate};
+/// let compressed = compress(b"validate before allocating, validate before allocating");
+/// assert!(validate(&compressed, 54).is_ok());
+/// assert!(validate(&compressed, 53).is_err());
+/// assert!(validate(&compressed[..10], 54).is_err());
+/// ```
+pub fn validate(input: &[u8], expected_size: usize) -> Result<(), DecompressError> {
+    let check_output_size = |output_len: usize, len: usize| {
+        if len > expected_size - output_len {
+            return Err(DecompressError::OutputTooSmall {
+                expected: output_len + len,
+                actual: expected_size,
+            });
+        }
+        Ok(())
+    };
+    // The checks are in the same order as in the decoder, to return the same errors.
+    let mut pos = 0;
+    let mut output_len = 0;
+    loop {
+        let token = *input.get(pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+        pos += 1;
+        let literal_length = read_length(input, &mut pos, (token >> 4) as usize)?;
+        if literal_length > input.len() - pos {
+            return Err(DecompressError::LiteralOutOfBounds);
+        }
+        check_output_size(output_len, literal_length)?;
+        pos += literal_length;
+        output_len += literal_length;
+        if pos == input.len() {
+            return Ok(());
+        }
+
+        let offset = input
+            .get(pos..pos + 2)
+            .ok_or(DecompressError::ExpectedAnotherByte)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        let match_length = MINMATCH + read_length(input, &mut pos, (token & 0xF) as usize)?;
+        check_output_size(output_len, match_length)?;
+        if offset == 0 || offset > output_len {
+            return Err(DecompressError::OffsetOutOfBounds);
+        }
+        output_len += match_length;
+    }
+}
+
+/// Number of length buckets in [`DecodeStats`].
+pub const LENGTH_BUCKETS: usize = 8;
+
+/// Statistics about the sequences of a block, returned by [`decompress_with_stats`].
+///
+/// The length buckets are powers of two: bucket `i` counts lengths in `2^(i-1)..2^i`, bucket 0
+/// counts zero lengths and the last bucket all lengths of at least `2^(LENGTH_BUCKETS - 2)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Number of sequences, i.e. tokens, in the block.
+    pub sequences: usize,
+    /// Number of bytes copied from the block as literals.
+    pub literal_bytes: usize,
+    /// Number of bytes copied from previous output by matches.
+    pub match_bytes: usize,
+    /// Number of sequences by literal length bucket.
+    pub literal_lengths: [usize; LENGTH_BUCKETS],
+    /// Number of matches by match length bucket.
+    pub match_lengths: [usize; LENGTH_BUCKETS],
+    /// Number of matches with offsets 1 to 8, at index `offset - 1`. These matches overlap their
+    /// own output, which makes them slower to copy.
+    pub short_offsets: [usize; 8],
+    /// Number of matches with offsets larger than 8.
+    pub other_offsets: usize,
+}
+
+impl DecodeStats {
+    /// Number of matches in the block.
+    pub fn matches(&self) -> usize {
+        self.match_lengths.iter().sum()
+    }
+
+    #[cfg(feature = "alloc")]
+    fn add_sequence(&mut self, sequence: &Sequence) {
+        fn bucket(len: usize) -> usize {
+            ((usize::BITS - len.leading_zeros()) as usize).min(LENGTH_BUCKETS - 1)
+        }
+        self.sequences += 1;
+        self.literal_bytes += sequence.literal_length;
+        self.literal_lengths[bucket(sequence.literal_length)] += 1;
+        if let Some((offset, match_length)) = sequence.matched {
+            self.match_bytes += match_length;
+            self.match_lengths[bucket(match_length)] += 1;
+            match self.short_offsets.get_mut(offset.wrapping_sub(1)) {
+                Some(count) => *count += 1,
+                None => self.other_offsets += 1,
+            }
+        }
+    }
+}
+
+/// Decompress all bytes of `input` into a new vec like `decompress`, and collect statistics about
+/// the sequences of the block, e.g. to understand the characteristics of compressed data.
+///
+/// The block is decompressed by the regular decoder, the statistics are collected in a second
+/// pass over the sequences, so the output is the same as of `decompress`.
+#[cfg(feature = "alloc")]
+pub fn decompress_with_stats(
+    input: &[u8],
+    min_uncompressed_size: usize,
+) -> Result<(Vec<u8>, DecodeStats), DecompressError> {
+    let decompressed = decompress(input, min_uncompressed_size)?;
+    let mut stats = DecodeStats::default();
+    let mut pos = 0;
+    while let Ok(sequence) = read_sequence(input, &mut pos) {
+        stats.add_sequence(&sequence);
+        if sequence.matched.is_none() {
+            break;
+        }
+    }
+    Ok((decompressed, stats))
+}
+
+/// An element of a block, see [`disassemble`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    /// Copy the given number of bytes from the block to the output.
+    Literals(usize),
+    /// Copy `length` bytes starting `offset` bytes before the end of the output. The match may
+    /// overlap the bytes it produces, e.g. an offset of 1 repeats the last byte.
+    Match {
+        /// Distance back from the current end of the output.
+        offset: u16,
+        /// Number of bytes to copy, including the implicit minimum match length of 4.
+        length: usize,
+    },
+}
+
+/// Parses the sequences of a block into a list of tokens, without decompressing it.
+///
+/// This is a debugging aid, e.g. to understand what the compressor produced for some input, see
+/// also `decompress_with_stats`. Extended lengths are resolved, so the lengths are the actual
+/// number of bytes copied, and literal runs of length 0 are omitted. The block ends with a run of
+/// only literals, as required by the format.
+///
+/// Matches are not checked to reference decompressed data, since a block can reference a
+/// dictionary. Fails if the input ends in the middle of a sequence, or a match has offset 0.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress, disassemble, Token};
+/// let tokens = disassemble(&compress(b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")).unwrap();
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Literals(1),
+///         Token::Match { offset: 1, length: 25 },
+///         Token::Literals(6),
+///     ]
+/// );
+/// ```
+#[cfg(feature = "alloc")]
+pub fn disassemble(input: &[u8]) -> Result<Vec<Token>, DecompressError> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    loop {
+        let sequence = read_sequence(input, &mut pos)?;
+        if sequence.literal_length != 0 {
+            tokens.push(Token::Literals(sequence.literal_length));
+        }
+        match sequence.matched {
+            Some((0, _)) => return Err(DecompressError::OffsetOutOfBounds),
+            Some((offset, length)) => tokens.push(Token::Match {
+                offset: offset as u16,
+                length,
+            }),
+            None => return Ok(tokens),
+        }
+    }
+}
+
+/// Compresses and decompresses `input`, and checks that the result equals `input`.
+///
+/// This is a debugging aid, e.g. to check user data in tests or fuzzing of downstream crates. It
+/// does the work of both `compress` and `decompress`, so keep it out of hot paths.
+#[cfg(feature = "alloc")]
+pub fn verify_roundtrip(input: &[u8]) -> Result<(), DecompressError> {
+    let compressed = compress(input);
+    let decompressed = decompress(&compressed, input.len())?;
+    if decompressed.len() != input.len() {
+        return Err(DecompressError::UncompressedSizeDiffers {
+            expected: input.len(),
+            actual: decompressed.len(),
+        });
+    }
+    if let Some(position) = input.iter().zip(&decompressed).position(|(a, b)| a != b) {
+        return Err(DecompressError::RoundtripMismatch { position });
+    }
+    Ok(())
+}
+
+/// Returns whether the compressed blocks `a` and `b` are byte-identical, e.g. to deduplicate
+/// compressed chunks without decompressing them.
+///
+/// This is just `a == b`. LZ4 has no canonical encoding, so the same data may compress to
+/// different blocks, e.g. with different compression settings or versions of this crate. Only
+/// blocks compressed with [`compress_deterministic`] are equal exactly if their data is equal.
+///
+/// [`compress_deterministic`]: crate::block::compress_deterministic
+#[inline]
+pub fn compressed_eq(a: &[u8], b: &[u8]) -> bool {
+    a == b
+}
+
+/// Returns a 64-bit fingerprint of the compressed block `compressed`, e.g. as a key to
+/// deduplicate compressed chunks without decompressing them.
+///
+/// The fingerprint is the 64-bit FNV-1a hash of the compressed bytes, which is the same on all
+/// targets and won't change. It's not a cryptographic hash, so compare chunks with equal
+/// fingerprints with [`compressed_eq`] before treating them as duplicates. Like
+/// `compressed_eq`, this is only a key for the data of the block if it's compressed with
+/// [`compress_deterministic`].
+///
+/// [`compress_deterministic`]: crate::block::compress_deterministic
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{compress_deterministic, compressed_eq, content_fingerprint};
+/// let a = compress_deterministic(b"chunk of data, chunk of data");
+/// let b = compress_deterministic(b"chunk of data, chunk of data");
+/// assert_eq!(content_fingerprint(&a), content_fingerprint(&b));
+/// assert!(compressed_eq(&a, &b));
+/// ```
+pub fn content_fingerprint(compressed: &[u8]) -> u64 {
+    compressed
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Compresses `input` into the front of `scratch`, decompresses it into the rest of `scratch`, and
+/// returns whether the result equals `input`.
+///
+/// Unlike `verify_roundtrip`, this doesn't allocate: the hash table of the compressor lives on
+/// the stack (up to 16KB) and all data in `scratch`. This allows e.g. embedded targets to
+/// self-check. `scratch` needs to hold `get_maximum_output_size(input.len()) + input.len()` bytes,
+/// otherwise `DecompressError::OutputTooSmall` is returned.
+///
+/// # Example
+/// ```
+/// use lz4_flex::block::{get_maximum_output_size, roundtrip_in_place};
+/// let input = b"self-test, self-test, self-test";
+/// let mut scratch = [0u8; get_maximum_output_size(31) + 31];
+/// assert!(roundtrip_in_place(&mut scratch, input).unwrap());
+/// ```
+pub fn roundtrip_in_place(scratch: &mut [u8], input: &[u8]) -> Result<bool, DecompressError> {
+    let max_compressed_size = get_maximum_output_size(input.len());
+    let needed = max_compressed_size + input.len();
+    if scratch.len() < needed {
+        return Err(DecompressError::OutputTooSmall {
+            expected: needed,
+            actual: scratch.len(),
+        });
+    }
+    let (compressed, decompressed) = scratch[..needed].split_at_mut(max_compressed_size);
+    // `compressed` is sized for the worst case, so compression can't fail.
+    let compressed_len = compress::compress_into_without_alloc(input, compressed).unwrap();
+    let decompressed_len = decompress_into(&compressed[..compressed_len], decompressed)?;
+    Ok(decompressed_len == input.len() && decompressed == input)
+}
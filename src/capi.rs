@@ -0,0 +1,120 @@
+//! C ABI of the block format, to use lz4_flex from C or C++, e.g. as a replacement of
+//! `LZ4_compress_default`/`LZ4_decompress_safe` of liblz4.
+//!
+//! The functions wrap [`compress_into`](crate::block::compress_into) and
+//! [`decompress_into`](crate::block::decompress_into). They return the number of bytes written
+//! into `dst`, or one of the negative `LZ4FLEX_ERROR_*` codes on error.
+//!
+//! The C header `include/lz4flex.h` is generated from this module with
+//! [cbindgen](https://github.com/mozilla/cbindgen):
+//! ```text
+//! cbindgen --config cbindgen.toml --output include/lz4flex.h src/capi.rs
+//! ```
+//! To link against lz4_flex, build it as a static or dynamic library, e.g. with
+//! `cargo rustc --release --features capi --crate-type staticlib`.
+
+use crate::block::{compress_into, decompress_into, get_maximum_output_size, DecompressError};
+
+/// A pointer is null, while its length is not 0.
+pub const LZ4FLEX_ERROR_NULL_POINTER: isize = -1;
+/// `dst_cap` is too small. For compression, `dst_cap` needs to be at least
+/// `lz4flex_compress_bound(src_len)`.
+pub const LZ4FLEX_ERROR_OUTPUT_TOO_SMALL: isize = -2;
+/// The compressed data is invalid.
+pub const LZ4FLEX_ERROR_INVALID_INPUT: isize = -3;
+/// `src` and `dst` overlap.
+pub const LZ4FLEX_ERROR_OVERLAP: isize = -4;
+
+/// Returns the size `dst` needs to have to compress `size` bytes with `lz4flex_compress`, or 0 if
+/// `size` is too large.
+#[no_mangle]
+pub extern "C" fn lz4flex_compress_bound(size: usize) -> usize {
+    if size > isize::MAX as usize / 2 {
+        return 0;
+    }
+    get_maximum_output_size(size)
+}
+
+/// Compresses the `src_len` bytes at `src` into a block at `dst`, which has room for `dst_cap`
+/// bytes. Returns the number of bytes written, or a negative `LZ4FLEX_ERROR_*` code.
+///
+/// # Safety
+/// `src` has to be valid for reads of `src_len` bytes and `dst` for writes of `dst_cap` bytes.
+/// They may be null if their length is 0. The two ranges must not overlap, which is checked and
+/// reported as `LZ4FLEX_ERROR_OVERLAP`.
+#[no_mangle]
+pub unsafe extern "C" fn lz4flex_compress(
+    src: *const u8,
+    src_len: usize,
+    dst: *mut u8,
+    dst_cap: usize,
+) -> isize {
+    if overlaps(src, src_len, dst, dst_cap) {
+        return LZ4FLEX_ERROR_OVERLAP;
+    }
+    let (Some(input), Some(output)) = (unsafe { slice(src, src_len) }, unsafe {
+        slice_mut(dst, dst_cap)
+    }) else {
+        return LZ4FLEX_ERROR_NULL_POINTER;
+    };
+    match compress_into(input, output) {
+        Ok(written) => written as isize,
+        Err(_) => LZ4FLEX_ERROR_OUTPUT_TOO_SMALL,
+    }
+}
+
+/// Decompresses the block of `src_len` bytes at `src` into `dst`, which has room for `dst_cap`
+/// bytes. Returns the number of bytes written, or a negative `LZ4FLEX_ERROR_*` code.
+///
+/// # Safety
+/// `src` has to be valid for reads of `src_len` bytes and `dst` for writes of `dst_cap` bytes.
+/// They may be null if their length is 0. The two ranges must not overlap, which is checked and
+/// reported as `LZ4FLEX_ERROR_OVERLAP`.
+#[no_mangle]
+pub unsafe extern "C" fn lz4flex_decompress(
+    src: *const u8,
+    src_len: usize,
+    dst: *mut u8,
+    dst_cap: usize,
+) -> isize {
+    if overlaps(src, src_len, dst, dst_cap) {
+        return LZ4FLEX_ERROR_OVERLAP;
+    }
+    let (Some(input), Some(output)) = (unsafe { slice(src, src_len) }, unsafe {
+        slice_mut(dst, dst_cap)
+    }) else {
+        return LZ4FLEX_ERROR_NULL_POINTER;
+    };
+    match decompress_into(input, output) {
+        Ok(written) => written as isize,
+        Err(DecompressError::OutputTooSmall { .. }) => LZ4FLEX_ERROR_OUTPUT_TOO_SMALL,
+        Err(_) => LZ4FLEX_ERROR_INVALID_INPUT,
+    }
+}
+
+/// Returns whether the `src_len` bytes at `src` and the `dst_cap` bytes at `dst` overlap.
+fn overlaps(src: *const u8, src_len: usize, dst: *const u8, dst_cap: usize) -> bool {
+    let (src, dst) = (src as usize, dst as usize);
+    src_len != 0
+        && dst_cap != 0
+        && src < dst.saturating_add(dst_cap)
+        && dst < src.saturating_add(src_len)
+}
+
+/// Returns `None` if `ptr` is null and `len` is not 0.
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match len {
+        0 => Some(&[]),
+        _ if ptr.is_null() => None,
+        _ => Some(unsafe { core::slice::from_raw_parts(ptr, len) }),
+    }
+}
+
+/// Returns `None` if `ptr` is null and `len` is not 0.
+unsafe fn slice_mut<'a>(ptr: *mut u8, len: usize) -> Option<&'a mut [u8]> {
+    match len {
+        0 => Some(&mut []),
+        _ if ptr.is_null() => None,
+        _ => Some(unsafe { core::slice::from_raw_parts_mut(ptr, len) }),
+    }
+}
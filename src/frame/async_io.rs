@@ -0,0 +1,212 @@
+//! `futures::io::AsyncRead`/`AsyncWrite` adapters for the frame format.
+//!
+//! The adapters wrap the synchronous [`FrameDecoder`] and [`FrameEncoder`] and only hand them
+//! in-memory buffers, so de/compression never blocks on the underlying reader or writer.
+
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use super::{FrameDecoder, FrameEncoder, FrameInfo};
+
+/// Size of the chunks read from the underlying reader.
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// An [`AsyncRead`] adapter which decompresses a LZ4 frame stream from an underlying
+/// [`AsyncRead`].
+///
+/// This is the asynchronous counterpart of [`FrameDecoder`]. Compressed data is buffered
+/// until a complete block is available, then decompressed.
+#[derive(Debug)]
+pub struct AsyncFrameDecoder<R> {
+    /// The underlying reader.
+    r: R,
+    /// Decoder over the compressed bytes read from `r` but not consumed yet.
+    decoder: FrameDecoder<io::Cursor<Vec<u8>>>,
+    /// Whether `r` reached EOF.
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncFrameDecoder<R> {
+    /// Creates a new decoder which reads compressed data from `rdr`.
+    pub fn new(rdr: R) -> Self {
+        AsyncFrameDecoder {
+            r: rdr,
+            decoder: FrameDecoder::new(io::Cursor::new(Vec::new())),
+            eof: false,
+        }
+    }
+
+    /// Gets a reference to the underlying reader in this decoder.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Gets a mutable reference to the underlying reader in this decoder.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.r
+    }
+
+    /// Consumes the decoder, returning the underlying reader.
+    ///
+    /// Compressed data which was read from the reader but not decompressed yet is lost.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Returns whether the next `read` of `decoder` can complete without further input.
+    fn can_decode(&self) -> bool {
+        if self.eof || self.decoder.buffered_len() != 0 {
+            return true;
+        }
+        let cursor = self.decoder.get_ref();
+        let input = &cursor.get_ref()[cursor.position() as usize..];
+        matches!(self.decoder.next_read_len(input), Some(len) if len <= input.len())
+    }
+
+    /// Reads the next chunk of compressed data from the underlying reader.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let cursor = self.decoder.get_mut();
+        let consumed = cursor.position() as usize;
+        let buffer = cursor.get_mut();
+        buffer.drain(..consumed);
+        let len = buffer.len();
+        buffer.resize(len + READ_CHUNK_SIZE, 0);
+        let result = Pin::new(&mut self.r).poll_read(cx, &mut buffer[len..]);
+        let read = match &result {
+            Poll::Ready(Ok(read)) => *read,
+            _ => 0,
+        };
+        buffer.truncate(len + read);
+        cursor.set_position(0);
+        match ready!(result) {
+            Ok(0) => self.eof = true,
+            Ok(_) => {}
+            Err(err) => return Poll::Ready(Err(err)),
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncFrameDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        while !this.can_decode() {
+            ready!(this.poll_fill(cx))?;
+        }
+        Poll::Ready(this.decoder.read(buf))
+    }
+}
+
+/// An [`AsyncWrite`] adapter which compresses data into the LZ4 frame format and writes it to
+/// an underlying [`AsyncWrite`].
+///
+/// This is the asynchronous counterpart of [`FrameEncoder`]. The frame is only complete after
+/// the encoder was closed with [`AsyncWriteExt::close`](https://docs.rs/futures/latest/futures/io/trait.AsyncWriteExt.html#method.close)
+/// or an equivalent, which also closes the underlying writer.
+#[derive(Debug)]
+pub struct AsyncFrameEncoder<W> {
+    /// The underlying writer.
+    w: W,
+    /// Encoder writing compressed data into an in-memory buffer.
+    encoder: FrameEncoder<Vec<u8>>,
+    /// Number of bytes of the encoder output already written to `w`.
+    written: usize,
+    /// Whether the frame was finished.
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncFrameEncoder<W> {
+    /// Creates a new encoder with the default [`FrameInfo`].
+    pub fn new(wtr: W) -> Self {
+        Self::with_frame_info(Default::default(), wtr)
+    }
+
+    /// Creates a new encoder using the specified [`FrameInfo`].
+    pub fn with_frame_info(frame_info: FrameInfo, wtr: W) -> Self {
+        AsyncFrameEncoder {
+            w: wtr,
+            encoder: FrameEncoder::with_frame_info(frame_info, Vec::new()),
+            written: 0,
+            finished: false,
+        }
+    }
+
+    /// Gets a reference to the underlying writer in this encoder.
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Gets a mutable reference to the underlying writer in this encoder.
+    ///
+    /// Note that mutating the output/input state of the stream may corrupt
+    /// this encoder, so care must be taken when using this method.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.w
+    }
+
+    /// Consumes the encoder, returning the underlying writer.
+    ///
+    /// Data which was not written to the writer yet is lost.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    /// Writes the pending output of the encoder to the underlying writer.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let output = self.encoder.get_mut();
+        while self.written < output.len() {
+            match ready!(Pin::new(&mut self.w).poll_write(cx, &output[self.written..])) {
+                Ok(0) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Ok(written) => self.written += written,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        output.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncFrameEncoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.encoder.write_all(buf)?;
+        // The input is accepted regardless of whether the output could be written already.
+        if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.encoder.flush()?;
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.w).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            this.encoder.try_finish().map_err(io::Error::from)?;
+            this.finished = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.w).poll_close(cx)
+    }
+}
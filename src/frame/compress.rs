@@ -6,19 +6,33 @@ use std::{
 use twox_hash::XxHash32;
 
 use crate::{
-    block::{
-        compress::compress_internal,
-        hashtable::{HashTable, HashTable4K},
-    },
+    block::{compress::compress_into_sink_with_dict, StreamCompressor, WINDOW_SIZE},
     sink::vec_sink_for_compression,
 };
 
 use super::Error;
 use super::{
-    header::{BlockInfo, BlockMode, FrameInfo, BLOCK_INFO_SIZE, MAX_FRAME_INFO_SIZE},
+    header::{
+        BlockInfo, BlockMode, FrameInfo, BLOCK_INFO_SIZE, MAGIC_NUMBER_SIZE, MAX_FRAME_INFO_SIZE,
+        MIN_FRAME_INFO_SIZE,
+    },
     BlockSize,
 };
-use crate::block::WINDOW_SIZE;
+
+/// In adaptive mode, the number of consecutive uncompressed blocks after which the match search is
+/// skipped.
+const ADAPTIVE_RAW_RUN_THRESHOLD: u32 = 4;
+/// In adaptive mode, while skipping the match search, a sample of this size from the start of each
+/// block is compressed to detect when the input becomes compressible again.
+const ADAPTIVE_PROBE_SIZE: usize = 4 * 1024;
+/// With block size growth, the size of the first blocks of a frame.
+const GROWTH_START_BLOCK_SIZE: usize = 64 * 1024;
+/// With block size growth, the number of consecutive well compressing blocks after which the
+/// block size grows.
+const GROWTH_COMPRESSIBLE_RUN_THRESHOLD: u32 = 4;
+
+/// Overwrites the frame header, see [`patch_frame_header`].
+type HeaderPatch<W> = fn(&mut W, u64, &[u8]) -> io::Result<()>;
 
 /// A writer for compressing a LZ4 stream.
 ///
@@ -59,22 +73,10 @@ use crate::block::WINDOW_SIZE;
 /// }
 /// compressor.finish().unwrap();
 /// ```
+#[must_use = "the frame is incomplete until `finish`, `try_finish` or `auto_finish` is called"]
 pub struct FrameEncoder<W: io::Write> {
-    /// Our buffer of uncompressed bytes.
-    src: Vec<u8>,
-    /// Index into src: starting point of bytes not yet compressed
-    src_start: usize,
-    /// Index into src: end point of bytes not not yet compressed
-    src_end: usize,
-    /// Index into src: starting point of external dictionary (applicable in Linked block mode)
-    ext_dict_offset: usize,
-    /// Length of external dictionary
-    ext_dict_len: usize,
-    /// Counter of bytes already compressed to the compression_table
-    /// _Not_ the same as `content_len` as this is reset every to 2GB.
-    src_stream_offset: usize,
-    /// Encoder table
-    compression_table: HashTable4K,
+    /// Buffers the uncompressed bytes and compresses them into (linked) blocks.
+    stream: StreamCompressor,
     /// The underlying writer.
     w: W,
     /// Xxhash32 used when content checksum is enabled.
@@ -90,33 +92,85 @@ pub struct FrameEncoder<W: io::Write> {
     data_to_frame_written: bool,
     /// The frame information to be used in this encoder.
     frame_info: FrameInfo,
+    /// Whether to skip the match search on sustained incompressible input.
+    adaptive: bool,
+    /// Number of consecutive blocks written uncompressed.
+    raw_block_run: u32,
+    /// Block mode used for `BlockMode::Auto` if the frame size can't be determined.
+    auto_block_mode_fallback: BlockMode,
+    /// Totals over all frames written by this encoder.
+    stats: EncodeStats,
+    /// Number of bytes compression has to save for a block to be stored compressed.
+    min_compression_gain: usize,
+    /// Rewrites the frame header in the underlying writer, set by [`FrameEncoder::with_seekable`].
+    /// Called with the number of bytes written since the start of the frame header.
+    header_patch: Option<HeaderPatch<W>>,
+    /// Value of `stats.output_bytes` at the start of the current frame header.
+    frame_start: u64,
+    /// Upper bound of the capacity of the buffer of uncompressed data, set by
+    /// [`FrameEncoder::with_capacity_limit`].
+    max_src_capacity: Option<usize>,
+    /// The size of the blocks written, set by [`FrameEncoder::set_block_size_growth`]. `None`
+    /// to always use the max block size.
+    grown_block_size: Option<usize>,
+    /// Number of consecutive blocks which compressed well, to grow `grown_block_size`.
+    compressible_run: u32,
+    /// Whether block checksums are only written for blocks of the max block size, set by
+    /// [`FrameEncoder::set_checksum_only_full_blocks`].
+    checksum_only_full_blocks: bool,
+}
+
+/// Statistics of a [`FrameEncoder`], returned by [`FrameEncoder::finish_with_stats`].
+///
+/// The totals cover all frames written by the encoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeStats {
+    /// Number of uncompressed bytes written to the encoder.
+    pub input_bytes: u64,
+    /// Number of bytes written to the underlying writer, including headers and checksums.
+    pub output_bytes: u64,
+    /// Number of data blocks written.
+    pub blocks: u64,
+    /// Number of data blocks which were stored uncompressed, because compressing them didn't
+    /// reduce their size.
+    pub raw_blocks: u64,
 }
 
 impl<W: io::Write> FrameEncoder<W> {
     fn init(&mut self) {
-        let max_block_size = self.frame_info.block_size.get_size();
-        let src_size = if self.frame_info.block_mode == BlockMode::Linked {
-            // In linked mode we consume the input (bumping src_start) but leave the
-            // beginning of src to be used as a prefix in subsequent blocks.
-            // That is at least until we have at least `max_block_size + WINDOW_SIZE`
-            // bytes in src, then we setup an ext_dict with the last WINDOW_SIZE bytes
-            // and the input goes to the beginning of src again.
-            // Since we always want to be able to write a full block (up to max_block_size)
-            // we need a buffer with at least `max_block_size * 2 + WINDOW_SIZE` bytes.
-            max_block_size * 2 + WINDOW_SIZE
-        } else {
-            max_block_size
-        };
+        let block_size = self.block_size();
+        self.stream
+            .configure(block_size, self.frame_info.block_mode == BlockMode::Linked);
         // Since this method is called potentially multiple times, don't reserve _additional_
         // capacity if not required.
-        self.src
-            .reserve(src_size.saturating_sub(self.src.capacity()));
         self.dst.reserve(
-            crate::block::compress::get_maximum_output_size(max_block_size)
+            crate::block::compress::get_maximum_output_size(block_size)
                 .saturating_sub(self.dst.capacity()),
         );
     }
 
+    /// The uncompressed size of the blocks written. This is the max block size of the frame,
+    /// unless it's reduced to stay within the limit of [`FrameEncoder::with_capacity_limit`].
+    fn block_size(&self) -> usize {
+        let max_block_size = self.frame_info.block_size.get_size();
+        match self.max_src_capacity {
+            // `BlockMode::Auto` may switch to linked blocks, which need the larger buffer.
+            Some(limit) if self.frame_info.block_mode != BlockMode::Independent => {
+                max_block_size.min((limit - WINDOW_SIZE) / 2)
+            }
+            Some(limit) => max_block_size.min(limit),
+            None => max_block_size,
+        }
+    }
+
+    /// The size of the blocks `write` buffers, which is smaller than `block_size` while the block
+    /// size grows.
+    fn working_block_size(&self) -> usize {
+        let block_size = self.block_size();
+        self.grown_block_size
+            .map_or(block_size, |grown| grown.min(block_size))
+    }
+
     /// Returns a wrapper around `self` that will finish the stream on drop.
     ///
     /// # Note
@@ -134,22 +188,78 @@ impl<W: io::Write> FrameEncoder<W> {
     /// Creates a new Encoder with the specified FrameInfo.
     pub fn with_frame_info(frame_info: FrameInfo, wtr: W) -> Self {
         FrameEncoder {
-            src: Vec::new(),
+            stream: StreamCompressor::unconfigured(),
             w: wtr,
-            // 16 KB hash table for matches, same as the reference implementation.
-            compression_table: HashTable4K::new(),
             content_hasher: XxHash32::with_seed(0),
             content_len: 0,
             dst: Vec::new(),
             is_frame_open: false,
             data_to_frame_written: false,
             frame_info,
-            src_start: 0,
-            src_end: 0,
-            ext_dict_offset: 0,
-            ext_dict_len: 0,
-            src_stream_offset: 0,
+            adaptive: false,
+            raw_block_run: 0,
+            auto_block_mode_fallback: BlockMode::Independent,
+            stats: EncodeStats::default(),
+            min_compression_gain: 0,
+            header_patch: None,
+            frame_start: 0,
+            max_src_capacity: None,
+            grown_block_size: None,
+            compressible_run: 0,
+            checksum_only_full_blocks: false,
+        }
+    }
+
+    /// Creates a new Encoder with the specified FrameInfo, which buffers at most
+    /// `max_src_capacity` bytes of uncompressed data.
+    ///
+    /// The encoder buffers a block of uncompressed data, and in linked block mode additionally the
+    /// previous block and 64KB of lookback, i.e. `2 * block size + 64KB`. E.g. with
+    /// `BlockSize::Max4MB` and linked blocks that's over 8MB per encoder. If the block size of
+    /// `frame_info` exceeds the limit, the encoder writes smaller blocks instead, which fit into
+    /// the limit. The frame header still declares the block size of `frame_info`, decoders
+    /// allocate accordingly. The output buffer for the compressed blocks shrinks with the block
+    /// size too.
+    ///
+    /// Smaller blocks cost some compression ratio, since each block ends with literals and
+    /// independent blocks can't reference data of other blocks. Linked blocks still reference the
+    /// previous 64KB, so they lose less.
+    ///
+    /// The blocks have to be at least 64KB, the smallest block size of the frame format. Returns
+    /// [`Error::CapacityLimitTooSmall`] if that doesn't fit into `max_src_capacity`, which needs
+    /// to be at least 192KB for linked blocks (or `BlockMode::Auto`) and 64KB for independent
+    /// blocks.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use lz4_flex::frame::{BlockMode, BlockSize, FrameEncoder, FrameInfo};
+    /// let frame_info = FrameInfo::new()
+    ///     .block_size(BlockSize::Max4MB)
+    ///     .block_mode(BlockMode::Linked);
+    /// // Writes linked blocks of 256KB, with 576KB of buffer instead of over 8MB.
+    /// let mut encoder =
+    ///     FrameEncoder::with_capacity_limit(frame_info, Vec::new(), 576 * 1024).unwrap();
+    /// encoder.write_all(&vec![7u8; 1024 * 1024]).unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    /// ```
+    pub fn with_capacity_limit(
+        frame_info: FrameInfo,
+        wtr: W,
+        max_src_capacity: usize,
+    ) -> Result<Self, Error> {
+        const MIN_BLOCK_SIZE: usize = 64 * 1024;
+        let needed = if frame_info.block_mode == BlockMode::Independent {
+            MIN_BLOCK_SIZE
+        } else {
+            MIN_BLOCK_SIZE * 2 + WINDOW_SIZE
+        };
+        if max_src_capacity < needed {
+            return Err(Error::CapacityLimitTooSmall { needed });
         }
+        let mut encoder = Self::with_frame_info(frame_info, wtr);
+        encoder.max_src_capacity = Some(max_src_capacity);
+        Ok(encoder)
     }
 
     /// Creates a new Encoder with the default settings.
@@ -162,15 +272,262 @@ impl<W: io::Write> FrameEncoder<W> {
         &self.frame_info
     }
 
+    /// Enables or disables adaptive mode.
+    ///
+    /// In adaptive mode the encoder detects sustained incompressible input (several consecutive
+    /// blocks that had to be stored uncompressed) and skips the match search for the following
+    /// blocks to save CPU. A small sample of each skipped block is still compressed, and the match
+    /// search is re-enabled as soon as the sample compresses again.
+    ///
+    /// The output is a valid LZ4 frame in either case, only the encoder effort changes.
+    /// Disabled by default.
+    pub fn set_adaptive(&mut self, adaptive: bool) {
+        self.adaptive = adaptive;
+        self.raw_block_run = 0;
+    }
+
+    /// Sets how many bytes compressing a block has to save, otherwise the block is stored
+    /// uncompressed.
+    ///
+    /// Uncompressed blocks are copied as they are when decoding, which is faster than decoding
+    /// the sequences of a compressed block. A higher value trades compression ratio for decoding
+    /// speed on poorly compressible data. Defaults to 0, i.e. any saving is used.
+    pub fn set_min_compression_gain(&mut self, bytes: usize) {
+        self.min_compression_gain = bytes;
+    }
+
+    /// Enables or disables growing the block size.
+    ///
+    /// With growth enabled, each frame starts with blocks of 64KB, so the first compressed bytes
+    /// are written early and incompressible data is handled in small steps. Whenever several
+    /// consecutive blocks compressed well, the block size grows by a factor of 4, up to the max
+    /// block size of the frame, which improves the compression ratio. The frame header declares
+    /// the max block size, so decoders allocate for the largest blocks.
+    ///
+    /// This has no effect if the max block size is 64KB. Disabled by default.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use lz4_flex::frame::{BlockSize, FrameEncoder, FrameInfo};
+    /// let frame_info = FrameInfo::new().block_size(BlockSize::Max4MB);
+    /// let mut encoder = FrameEncoder::with_frame_info(frame_info, Vec::new());
+    /// encoder.set_block_size_growth(true);
+    /// encoder.write_all(&b"compressible ".repeat(100_000)).unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    /// ```
+    pub fn set_block_size_growth(&mut self, growth: bool) {
+        self.grown_block_size = growth.then_some(GROWTH_START_BLOCK_SIZE);
+        self.compressible_run = 0;
+    }
+
+    /// Sets whether block checksums are only written for blocks of the max block size.
+    ///
+    /// With block checksums enabled, every block is followed by its checksum, including the
+    /// small blocks written by `flush`. If set, partial blocks are written without a checksum,
+    /// which saves 4 bytes and a hash pass per flush when flushing often for low latency.
+    ///
+    /// **The output is not a valid LZ4 frame.** The header still declares block checksums, so
+    /// other decoders (including a `FrameDecoder` with default settings) misread the frame
+    /// after the first partial block. It can only be decoded by a `FrameDecoder` with
+    /// [`FrameDecoder::set_expect_checksum_only_full_blocks`] enabled. Without block checksums in
+    /// the `FrameInfo` this has no effect. Disabled by default.
+    ///
+    /// [`FrameDecoder::set_expect_checksum_only_full_blocks`]:
+    ///     crate::frame::FrameDecoder::set_expect_checksum_only_full_blocks
+    pub fn set_checksum_only_full_blocks(&mut self, only_full_blocks: bool) {
+        self.checksum_only_full_blocks = only_full_blocks;
+    }
+
+    /// Sets the block mode used for [`BlockMode::Auto`] when the frame size can't be determined,
+    /// e.g. when the encoder is flushed before the first block is full.
+    /// `BlockMode::Auto` is treated as `BlockMode::Independent`, which is the default.
+    pub fn set_auto_block_mode_fallback(&mut self, block_mode: BlockMode) {
+        self.auto_block_mode_fallback = match block_mode {
+            BlockMode::Auto => BlockMode::Independent,
+            block_mode => block_mode,
+        };
+    }
+
+    /// Compresses and writes `block` as a single block, without copying it into the internal
+    /// buffer first.
+    ///
+    /// `block` must not be larger than the max block size of the frame, or the reduced block size
+    /// with [`FrameEncoder::with_capacity_limit`]. All blocks except the last one of a frame
+    /// should be exactly that size, like the blocks produced by `write`, otherwise the compression
+    /// ratio suffers. In linked block mode, the lookback window for the
+    /// following block (up to 64KB) is still copied.
+    ///
+    /// If data from previous `write` calls is still buffered, `block` is written through the
+    /// buffer instead.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use lz4_flex::frame::{BlockSize, FrameEncoder, FrameInfo};
+    /// let input = vec![7u8; 200 * 1024];
+    /// let frame_info = FrameInfo::new().block_size(BlockSize::Max64KB);
+    /// let mut encoder = FrameEncoder::with_frame_info(frame_info, Vec::new());
+    /// for block in input.chunks(64 * 1024) {
+    ///     encoder.write_block_borrowed(block).unwrap();
+    /// }
+    /// let compressed = encoder.finish().unwrap();
+    /// ```
+    pub fn write_block_borrowed(&mut self, block: &[u8]) -> io::Result<()> {
+        if block.is_empty() {
+            return Ok(());
+        }
+        if !self.is_frame_open {
+            self.begin_frame(block.len())?;
+        }
+        if block.len() > self.block_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "block is larger than the max block size",
+            ));
+        }
+        if self.stream.pending_len() != 0 {
+            return self.write_all(block);
+        }
+        if self.frame_info.block_mode == BlockMode::Auto {
+            self.resolve_block_mode(self.auto_block_mode_fallback)?;
+        }
+        self.encode_block(Some(block))
+    }
+
+    /// Writes a block which is already compressed, e.g. by [`crate::block::compress`], to the
+    /// frame without compressing it again. `uncompressed_len` is the length of the data
+    /// `compressed` decompresses to.
+    ///
+    /// `compressed` is written verbatim, so it must be a valid LZ4 block that doesn't reference
+    /// data of previous blocks. Data buffered from previous `write` calls is written as a block
+    /// first. In linked block mode, the following blocks don't reference the data before them.
+    ///
+    /// Neither `uncompressed_len` nor `compressed` may exceed the max block size of the frame.
+    /// If content checksums are enabled, `compressed` is decompressed to update the checksum,
+    /// which also validates the block and `uncompressed_len`. Otherwise `uncompressed_len` is
+    /// trusted and only counted towards the content size.
+    ///
+    /// # Example
+    /// ```
+    /// use lz4_flex::frame::{FrameDecoder, FrameEncoder};
+    /// use std::io::Read;
+    /// let input = b"Hello people, what's up? Hello people, what's up?";
+    /// let compressed = lz4_flex::block::compress(input);
+    /// let mut encoder = FrameEncoder::new(Vec::new());
+    /// encoder
+    ///     .write_precompressed_block(&compressed, input.len())
+    ///     .unwrap();
+    /// let frame = encoder.finish().unwrap();
+    ///
+    /// let mut decompressed = Vec::new();
+    /// FrameDecoder::new(&frame[..])
+    ///     .read_to_end(&mut decompressed)
+    ///     .unwrap();
+    /// assert_eq!(decompressed, input);
+    /// ```
+    pub fn write_precompressed_block(
+        &mut self,
+        compressed: &[u8],
+        uncompressed_len: usize,
+    ) -> io::Result<()> {
+        if uncompressed_len == 0 {
+            return Ok(());
+        }
+        if !self.is_frame_open {
+            self.begin_frame(uncompressed_len)?;
+        }
+        let max_block_size = self.frame_info.block_size.get_size();
+        if uncompressed_len > max_block_size || compressed.len() > max_block_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "block is larger than the max block size",
+            ));
+        }
+        self.flush()?;
+        if self.frame_info.block_mode == BlockMode::Auto {
+            self.resolve_block_mode(self.auto_block_mode_fallback)?;
+        }
+
+        // Computed before writing, so that nothing is written if the block is malformed
+        if self.frame_info.content_checksum {
+            self.dst.clear();
+            self.dst.resize(uncompressed_len, 0);
+            let decompressed_len = crate::block::decompress_into(compressed, &mut self.dst)
+                .map_err(Error::DecompressionError)?;
+            if decompressed_len != uncompressed_len {
+                return Err(Error::ContentLengthError {
+                    expected: uncompressed_len as u64,
+                    actual: decompressed_len as u64,
+                }
+                .into());
+            }
+            self.content_hasher.write(&self.dst);
+        }
+
+        let mut block_info_buffer = [0u8; BLOCK_INFO_SIZE];
+        BlockInfo::Compressed(compressed.len() as _).write(&mut block_info_buffer[..])?;
+        self.w.write_all(&block_info_buffer[..])?;
+        self.w.write_all(compressed)?;
+        self.stats.output_bytes += (BLOCK_INFO_SIZE + compressed.len()) as u64;
+        if self.frame_info.block_checksums
+            && (!self.checksum_only_full_blocks || uncompressed_len == max_block_size)
+        {
+            let mut block_hasher = XxHash32::with_seed(0);
+            block_hasher.write(compressed);
+            let block_checksum = block_hasher.finish() as u32;
+            self.w.write_all(&block_checksum.to_le_bytes())?;
+            self.stats.output_bytes += 4;
+        }
+        self.stats.blocks += 1;
+
+        self.content_len += uncompressed_len as u64;
+        self.stats.input_bytes += uncompressed_len as u64;
+
+        // The compressor doesn't know the data of the block, so it can't be used as the window
+        // of the next block.
+        if self.frame_info.block_mode == BlockMode::Linked {
+            self.stream.reset();
+        }
+        Ok(())
+    }
+
+    /// Writes all buffered data as a block and flushes the underlying writer.
+    ///
+    /// Afterwards everything written to the underlying writer so far consists of complete blocks,
+    /// so a reader can decode all data written up to this point without waiting for more input.
+    /// Unlike [`try_finish()`], the frame stays open and further data can be written to it.
+    ///
+    /// Note that flushing frequently produces small blocks, which hurts the compression ratio.
+    ///
+    /// [`try_finish()`]: Self::try_finish
+    pub fn flush_decodable(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.w.flush()
+    }
+
     /// Consumes this encoder, flushing internal buffer and writing stream terminator.
     pub fn finish(mut self) -> Result<W, Error> {
         self.try_finish()?;
         Ok(self.w)
     }
 
+    /// Consumes this encoder like [`finish()`], and additionally returns statistics about the
+    /// written data, e.g. to report the compression ratio.
+    ///
+    /// [`finish()`]: Self::finish
+    pub fn finish_with_stats(mut self) -> Result<(W, EncodeStats), Error> {
+        self.try_finish()?;
+        Ok((self.w, self.stats))
+    }
+
     /// Attempt to finish this output stream, flushing internal buffer and writing stream
     /// terminator.
     pub fn try_finish(&mut self) -> Result<(), Error> {
+        if self.is_frame_open && self.frame_info.block_mode == BlockMode::Auto {
+            // The buffered data is all there is, it fits in a single block.
+            self.resolve_block_mode(BlockMode::Independent)?;
+        }
         match self.flush() {
             Ok(()) => {
                 // Empty input special case
@@ -208,6 +565,9 @@ impl<W: io::Write> FrameEncoder<W> {
     /// Closes the frame by writing the end marker.
     fn end_frame(&mut self) -> Result<(), Error> {
         debug_assert!(self.is_frame_open);
+        if self.frame_info.block_mode == BlockMode::Auto {
+            self.resolve_block_mode(BlockMode::Independent)?;
+        }
         self.is_frame_open = false;
         if let Some(expected) = self.frame_info.content_size {
             if expected != self.content_len {
@@ -221,9 +581,22 @@ impl<W: io::Write> FrameEncoder<W> {
         let mut block_info_buffer = [0u8; BLOCK_INFO_SIZE];
         BlockInfo::EndMark.write(&mut block_info_buffer[..])?;
         self.w.write_all(&block_info_buffer[..])?;
+        self.stats.output_bytes += BLOCK_INFO_SIZE as u64;
         if self.frame_info.content_checksum {
             let content_checksum = self.content_hasher.finish() as u32;
             self.w.write_all(&content_checksum.to_le_bytes())?;
+            self.stats.output_bytes += 4;
+        }
+
+        if let Some(header_patch) = self.header_patch {
+            let frame_info = FrameInfo {
+                content_size: Some(self.content_len),
+                ..self.frame_info.clone()
+            };
+            let mut frame_info_buffer = [0u8; MAX_FRAME_INFO_SIZE];
+            let size = frame_info.write(&mut frame_info_buffer)?;
+            let frame_len = self.stats.output_bytes - self.frame_start;
+            header_patch(&mut self.w, frame_len, &frame_info_buffer[..size])?;
         }
 
         Ok(())
@@ -231,78 +604,127 @@ impl<W: io::Write> FrameEncoder<W> {
 
     /// Begin the frame by writing the frame header.
     /// It'll also setup the encoder for compressing blocks for the the new frame.
+    ///
+    /// With `BlockMode::Auto`, writing the header is deferred until the block mode is known, see
+    /// `resolve_block_mode`.
     fn begin_frame(&mut self, buf_len: usize) -> io::Result<()> {
         self.is_frame_open = true;
         if self.frame_info.block_size == BlockSize::Auto {
             self.frame_info.block_size = BlockSize::from_buf_length(buf_len);
         }
+        if self.frame_info.block_mode == BlockMode::Auto {
+            let max_block_size = self.frame_info.block_size.get_size();
+            match self.frame_info.content_size {
+                Some(content_size) if content_size <= max_block_size as u64 => {
+                    self.frame_info.block_mode = BlockMode::Independent;
+                }
+                Some(_) => self.frame_info.block_mode = BlockMode::Linked,
+                None if buf_len > max_block_size => {
+                    self.frame_info.block_mode = BlockMode::Linked;
+                }
+                None => {}
+            }
+        }
         self.init();
-        let mut frame_info_buffer = [0u8; MAX_FRAME_INFO_SIZE];
-        let size = self.frame_info.write(&mut frame_info_buffer)?;
-        self.w.write_all(&frame_info_buffer[..size])?;
+        if self.frame_info.block_mode != BlockMode::Auto {
+            self.write_frame_header()?;
+        }
 
         if self.content_len != 0 {
             // This is the second or later frame for this Encoder,
             // reset compressor state for the new frame.
             self.content_len = 0;
-            self.src_stream_offset = 0;
-            self.src.clear();
-            self.src_start = 0;
-            self.src_end = 0;
-            self.ext_dict_len = 0;
+            self.stream.reset();
             self.content_hasher = XxHash32::with_seed(0);
-            self.compression_table.clear();
+            self.raw_block_run = 0;
+            if self.grown_block_size.is_some() {
+                self.grown_block_size = Some(GROWTH_START_BLOCK_SIZE);
+                self.compressible_run = 0;
+            }
         }
         Ok(())
     }
 
-    /// Consumes the src contents between src_start and src_end,
-    /// which shouldn't exceed the max block size.
-    fn write_block(&mut self) -> io::Result<()> {
-        debug_assert!(self.is_frame_open);
-        let max_block_size = self.frame_info.block_size.get_size();
-        debug_assert!(self.src_end - self.src_start <= max_block_size);
-
-        // Reposition the compression table if we're anywhere near an overflowing hazard
-        if self.src_stream_offset + max_block_size + WINDOW_SIZE >= u32::MAX as usize / 2 {
-            self.compression_table
-                .reposition((self.src_stream_offset - self.ext_dict_len) as _);
-            self.src_stream_offset = self.ext_dict_len;
-        }
-
-        // input to the compressor, which may include a prefix when blocks are linked
-        let input = &self.src[..self.src_end];
-        // the contents of the block are between src_start and src_end
-        let src = &input[self.src_start..];
-
-        let dst_required_size = crate::block::compress::get_maximum_output_size(src.len());
-
-        let compress_result = if self.ext_dict_len != 0 {
-            debug_assert_eq!(self.frame_info.block_mode, BlockMode::Linked);
-            compress_internal::<_, true, _>(
-                input,
-                self.src_start,
-                &mut vec_sink_for_compression(&mut self.dst, 0, 0, dst_required_size),
-                &mut self.compression_table,
-                &self.src[self.ext_dict_offset..self.ext_dict_offset + self.ext_dict_len],
-                self.src_stream_offset,
-            )
+    /// Writes the frame header.
+    fn write_frame_header(&mut self) -> io::Result<()> {
+        self.frame_start = self.stats.output_bytes;
+        let mut frame_info_buffer = [0u8; MAX_FRAME_INFO_SIZE];
+        let size = if self.header_patch.is_some() {
+            // Reserves the content size field, the header is rewritten in `end_frame`.
+            FrameInfo {
+                content_size: Some(0),
+                ..self.frame_info.clone()
+            }
+            .write(&mut frame_info_buffer)?
         } else {
-            compress_internal::<_, false, _>(
-                input,
-                self.src_start,
-                &mut vec_sink_for_compression(&mut self.dst, 0, 0, dst_required_size),
-                &mut self.compression_table,
-                b"",
-                self.src_stream_offset,
-            )
+            self.frame_info.write(&mut frame_info_buffer)?
+        };
+        self.w.write_all(&frame_info_buffer[..size])?;
+        self.stats.output_bytes += size as u64;
+        Ok(())
+    }
+
+    /// Sets the block mode of a frame with `BlockMode::Auto`, which is deferred until the first
+    /// block is written, and writes the frame header.
+    fn resolve_block_mode(&mut self, block_mode: BlockMode) -> io::Result<()> {
+        debug_assert_eq!(self.frame_info.block_mode, BlockMode::Auto);
+        debug_assert_ne!(block_mode, BlockMode::Auto);
+        self.frame_info.block_mode = block_mode;
+        // Linked mode needs a larger src buffer
+        self.init();
+        self.write_frame_header()
+    }
+
+    /// Compresses the buffered data, or `block` if given, and writes it as a block, together with
+    /// the block checksum (if applicable).
+    fn encode_block(&mut self, block: Option<&[u8]>) -> io::Result<()> {
+        // In adaptive mode, skip the match search on a run of incompressible blocks, unless a
+        // sample of the block compresses.
+        let skip_compression =
+            self.adaptive && self.raw_block_run >= ADAPTIVE_RAW_RUN_THRESHOLD && {
+                let src = block.unwrap_or_else(|| self.stream.pending());
+                let sample = &src[..src.len().min(ADAPTIVE_PROBE_SIZE)];
+                let sample_comp_len = compress_into_sink_with_dict::<false>(
+                    sample,
+                    &mut vec_sink_for_compression(
+                        &mut self.dst,
+                        0,
+                        0,
+                        crate::block::compress::get_maximum_output_size(sample.len()),
+                    ),
+                    b"",
+                )
+                .map_err(Error::CompressionError)?;
+                sample_comp_len >= sample.len()
+            };
+
+        let compress_result = match block {
+            Some(block) => self
+                .stream
+                .compress_borrowed(block, &mut self.dst, skip_compression),
+            None => self
+                .stream
+                .compress_pending(&mut self.dst, skip_compression),
         };
+        let src = block.unwrap_or_else(|| self.stream.pending());
 
         let (block_info, block_data) = match compress_result.map_err(Error::CompressionError)? {
-            comp_len if comp_len < src.len() => {
+            comp_len if comp_len.saturating_add(self.min_compression_gain) < src.len() => {
+                self.raw_block_run = 0;
+                // A block compresses well if it shrinks by at least a quarter.
+                if comp_len <= src.len() / 4 * 3 {
+                    self.compressible_run = self.compressible_run.saturating_add(1);
+                } else {
+                    self.compressible_run = 0;
+                }
                 (BlockInfo::Compressed(comp_len as _), &self.dst[..comp_len])
             }
-            _ => (BlockInfo::Uncompressed(src.len() as _), src),
+            _ => {
+                self.raw_block_run = self.raw_block_run.saturating_add(1);
+                self.compressible_run = 0;
+                self.stats.raw_blocks += 1;
+                (BlockInfo::Uncompressed(src.len() as _), src)
+            }
         };
 
         // Write the (un)compressed block to the writer and the block checksum (if applicable).
@@ -310,67 +732,45 @@ impl<W: io::Write> FrameEncoder<W> {
         block_info.write(&mut block_info_buffer[..])?;
         self.w.write_all(&block_info_buffer[..])?;
         self.w.write_all(block_data)?;
-        if self.frame_info.block_checksums {
+        self.stats.output_bytes += (BLOCK_INFO_SIZE + block_data.len()) as u64;
+        if self.frame_info.block_checksums
+            && (!self.checksum_only_full_blocks
+                || src.len() == self.frame_info.block_size.get_size())
+        {
             let mut block_hasher = XxHash32::with_seed(0);
             block_hasher.write(block_data);
             let block_checksum = block_hasher.finish() as u32;
             self.w.write_all(&block_checksum.to_le_bytes())?;
+            self.stats.output_bytes += 4;
         }
+        self.stats.blocks += 1;
 
         // Content checksum, if applicable
         if self.frame_info.content_checksum {
             self.content_hasher.write(src);
         }
-
-        // Buffer and offsets maintenance
         self.content_len += src.len() as u64;
-        self.src_start += src.len();
-        debug_assert_eq!(self.src_start, self.src_end);
-        if self.frame_info.block_mode == BlockMode::Linked {
-            // In linked mode we consume the input (bumping src_start) but leave the
-            // beginning of src to be used as a prefix in subsequent blocks.
-            // That is at least until we have at least `max_block_size + WINDOW_SIZE`
-            // bytes in src, then we setup an ext_dict with the last WINDOW_SIZE bytes
-            // and the input goes to the beginning of src again.
-            debug_assert_eq!(self.src.capacity(), max_block_size * 2 + WINDOW_SIZE);
-            if self.src_start >= max_block_size + WINDOW_SIZE {
-                // The ext_dict will become the last WINDOW_SIZE bytes
-                self.ext_dict_offset = self.src_end - WINDOW_SIZE;
-                self.ext_dict_len = WINDOW_SIZE;
-                // Input goes in the beginning of the buffer again.
-                self.src_stream_offset += self.src_end;
-                self.src_start = 0;
-                self.src_end = 0;
-            } else if self.src_start + self.ext_dict_len > WINDOW_SIZE {
-                // There's more than WINDOW_SIZE bytes of lookback adding the prefix and ext_dict.
-                // Since we have a limited buffer we must shrink ext_dict in favor of the prefix,
-                // so that we can fit up to max_block_size bytes between dst_start and ext_dict
-                // start.
-                let delta = self
-                    .ext_dict_len
-                    .min(self.src_start + self.ext_dict_len - WINDOW_SIZE);
-                self.ext_dict_offset += delta;
-                self.ext_dict_len -= delta;
-                debug_assert!(self.src_start + self.ext_dict_len >= WINDOW_SIZE)
+        self.stats.input_bytes += src.len() as u64;
+
+        match block {
+            Some(block) => self.stream.consume_borrowed(block),
+            None => self.stream.consume_pending(),
+        }
+
+        if let Some(grown) = self.grown_block_size {
+            if self.compressible_run >= GROWTH_COMPRESSIBLE_RUN_THRESHOLD {
+                self.grown_block_size = Some(grown.saturating_mul(4).min(self.block_size()));
+                self.compressible_run = 0;
             }
-            debug_assert!(
-                self.ext_dict_len == 0 || self.src_start + max_block_size <= self.ext_dict_offset
-            );
-        } else {
-            // In independent block mode we consume the entire src buffer
-            // which is sized equal to the frame max_block_size.
-            debug_assert_eq!(self.ext_dict_len, 0);
-            debug_assert_eq!(self.src.capacity(), max_block_size);
-            self.src_start = 0;
-            self.src_end = 0;
-            // Advance stream offset so we don't have to reset the match dict
-            // for the next block.
-            self.src_stream_offset += src.len();
-        }
-        debug_assert!(self.src_start <= self.src_end);
-        debug_assert!(self.src_start + max_block_size <= self.src.capacity());
+        }
         Ok(())
     }
+
+    /// Writes the buffered data as a block.
+    fn write_block(&mut self) -> io::Result<()> {
+        debug_assert!(self.is_frame_open);
+        self.encode_block(None)
+    }
 }
 
 impl<W: io::Write> io::Write for FrameEncoder<W> {
@@ -380,42 +780,303 @@ impl<W: io::Write> io::Write for FrameEncoder<W> {
         }
         let buf_len = buf.len();
         while !buf.is_empty() {
-            let src_filled = self.src_end - self.src_start;
-            let max_fill_len = self.frame_info.block_size.get_size() - src_filled;
-            if max_fill_len == 0 {
+            let block_size = self.working_block_size();
+            if self.stream.pending_len() >= block_size {
+                if self.frame_info.block_mode == BlockMode::Auto {
+                    // There's more than one block of data
+                    self.resolve_block_mode(BlockMode::Linked)?;
+                }
                 // make space by writing next block
                 self.write_block()?;
-                debug_assert_eq!(self.src_end, self.src_start);
+                debug_assert_eq!(self.stream.pending_len(), 0);
                 continue;
             }
 
-            let fill_len = max_fill_len.min(buf.len());
-            vec_copy_overwriting(&mut self.src, self.src_end, &buf[..fill_len]);
+            let fill_len = buf.len().min(block_size - self.stream.pending_len());
+            let fill_len = self.stream.fill(&buf[..fill_len]);
             buf = &buf[fill_len..];
-            self.src_end += fill_len;
         }
         Ok(buf_len)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        if self.src_start != self.src_end {
+        if self.stream.pending_len() != 0 {
+            if self.frame_info.block_mode == BlockMode::Auto {
+                self.resolve_block_mode(self.auto_block_mode_fallback)?;
+            }
             self.write_block()?;
         }
         Ok(())
     }
 }
 
+/// Compresses `input` as a frame with the default settings and appends it to `output`.
+///
+/// To use other settings, wrap `output` with `BufMut::writer` and use a [`FrameEncoder`].
+#[cfg(feature = "bytes")]
+pub fn compress_into_bytes_mut(input: &[u8], output: &mut bytes::BytesMut) -> Result<(), Error> {
+    use bytes::BufMut;
+    let mut encoder = FrameEncoder::new(output.writer());
+    encoder.write_all(input)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Concatenates frames with independent blocks into a single frame, without recompressing.
+///
+/// Each element of `frames` must hold exactly one complete frame. The blocks are copied as they
+/// are, so all frames need to use [`BlockMode::Independent`] and the same settings apart from the
+/// content size, and so does the combined frame. The combined frame declares the sum of the
+/// content sizes if every frame declares one. If content checksums are enabled, the blocks are
+/// decompressed to verify the checksum of each frame and to compute the checksum of the combined
+/// content.
+///
+/// Returns [`Error::IncompatibleFrames`] if the frames can't be concatenated, and an error of
+/// kind `InvalidInput` if `frames` is empty, since there are no settings to take over then.
+pub fn concat_frames(frames: &[&[u8]], mut out: impl io::Write) -> Result<(), Error> {
+    if frames.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no frames to concatenate").into());
+    }
+    let mut headers = Vec::with_capacity(frames.len());
+    for frame in frames {
+        headers.push(read_concat_frame_header(frame)?);
+    }
+    let mut frame_info = headers[0].0.clone();
+    for (other, _) in &headers {
+        if other.block_size != frame_info.block_size
+            || other.block_checksums != frame_info.block_checksums
+            || other.content_checksum != frame_info.content_checksum
+        {
+            return Err(Error::IncompatibleFrames);
+        }
+    }
+    frame_info.content_size = Some(0);
+    for (other, _) in &headers {
+        frame_info.content_size = match (frame_info.content_size, other.content_size) {
+            (Some(size), Some(other_size)) => {
+                Some(size.checked_add(other_size).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "combined content size overflows",
+                    )
+                })?)
+            }
+            _ => None,
+        };
+    }
+
+    let mut frame_info_buffer = [0u8; MAX_FRAME_INFO_SIZE];
+    let size = frame_info.write(&mut frame_info_buffer)?;
+    out.write_all(&frame_info_buffer[..size])?;
+
+    let max_block_size = frame_info.block_size.get_size();
+    let block_checksum_len = if frame_info.block_checksums { 4 } else { 0 };
+    let mut content_hasher = XxHash32::with_seed(0);
+    let mut decompressed = Vec::new();
+    if frame_info.content_checksum {
+        decompressed.resize(max_block_size, 0);
+    }
+    for (frame, (_, header_len)) in frames.iter().zip(headers) {
+        let mut pos = header_len;
+        let mut frame_hasher = XxHash32::with_seed(0);
+        loop {
+            let block_info_bytes = take(frame, &mut pos, BLOCK_INFO_SIZE)?;
+            let block_info = BlockInfo::read(block_info_bytes)?;
+            let (len, compressed) = match block_info {
+                BlockInfo::Compressed(len) => (len as usize, true),
+                BlockInfo::Uncompressed(len) => (len as usize, false),
+                BlockInfo::EndMark => break,
+            };
+            if len > max_block_size {
+                return Err(Error::BlockTooBig);
+            }
+            let data = take(frame, &mut pos, len)?;
+            let block_checksum = take(frame, &mut pos, block_checksum_len)?;
+            if frame_info.content_checksum {
+                let content = if compressed {
+                    let decompressed_len = crate::block::decompress_into(data, &mut decompressed)
+                        .map_err(Error::DecompressionError)?;
+                    &decompressed[..decompressed_len]
+                } else {
+                    data
+                };
+                frame_hasher.write(content);
+                content_hasher.write(content);
+            }
+            out.write_all(block_info_bytes)?;
+            out.write_all(data)?;
+            out.write_all(block_checksum)?;
+        }
+        if frame_info.content_checksum {
+            let expected = u32::from_le_bytes(take(frame, &mut pos, 4)?.try_into().unwrap());
+            if expected != frame_hasher.finish() as u32 {
+                return Err(Error::ContentChecksumError);
+            }
+        }
+        if pos != frame.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trailing data after the end of the frame",
+            )
+            .into());
+        }
+    }
+
+    let mut block_info_buffer = [0u8; BLOCK_INFO_SIZE];
+    BlockInfo::EndMark.write(&mut block_info_buffer[..])?;
+    out.write_all(&block_info_buffer[..])?;
+    if frame_info.content_checksum {
+        let content_checksum = content_hasher.finish() as u32;
+        out.write_all(&content_checksum.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Wraps a compressed block into a frame consisting of that single block, without recompressing.
+///
+/// `block` is a block in the raw LZ4 block format (e.g. the output of
+/// [`block::compress`](crate::block::compress)) which decompresses to `uncompressed_size`
+/// bytes. With [`BlockSize::Auto`], the smallest block size fitting `uncompressed_size` is
+/// used, and [`BlockMode::Auto`] resolves to [`BlockMode::Independent`]. If content checksums are
+/// enabled, the block is decompressed to compute the checksum. A block which is not smaller than
+/// `uncompressed_size`, e.g. of incompressible data, is decompressed and stored uncompressed.
+///
+/// Returns [`Error::BlockTooBig`] if `uncompressed_size` exceeds the block size, and
+/// [`Error::ContentLengthError`] if the frame info declares a different content size. If the block
+/// is decompressed, or `uncompressed_size` is zero, an error is also returned if the block doesn't
+/// decompress to `uncompressed_size` bytes.
+pub fn wrap_block_as_frame(
+    block: &[u8],
+    uncompressed_size: usize,
+    frame_info: &FrameInfo,
+    mut out: impl io::Write,
+) -> Result<(), Error> {
+    if frame_info.legacy_frame {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "wrapping a block into a legacy frame is not supported",
+        )
+        .into());
+    }
+    let mut frame_info = frame_info.clone();
+    if frame_info.block_size == BlockSize::Auto {
+        frame_info.block_size = BlockSize::from_buf_length(uncompressed_size);
+    }
+    // A single block, which is what the encoder picks for `Auto` as well
+    if frame_info.block_mode == BlockMode::Auto {
+        frame_info.block_mode = BlockMode::Independent;
+    }
+    if uncompressed_size > frame_info.block_size.get_size() {
+        return Err(Error::BlockTooBig);
+    }
+    if let Some(expected) = frame_info.content_size {
+        if expected != uncompressed_size as u64 {
+            return Err(Error::ContentLengthError {
+                expected,
+                actual: uncompressed_size as u64,
+            });
+        }
+    }
+
+    // Like the encoder, a block which doesn't shrink is stored uncompressed. This also keeps
+    // incompressible data within the block size.
+    let store_uncompressed = uncompressed_size > 0 && block.len() >= uncompressed_size;
+    // Computed upfront, so that nothing is written if the block is malformed
+    let decompressed =
+        if frame_info.content_checksum || store_uncompressed || uncompressed_size == 0 {
+            let mut decompressed = vec![0u8; uncompressed_size];
+            let decompressed_len = if block.is_empty() {
+                0
+            } else {
+                crate::block::decompress_into(block, &mut decompressed)
+                    .map_err(Error::DecompressionError)?
+            };
+            if decompressed_len != uncompressed_size {
+                return Err(Error::ContentLengthError {
+                    expected: uncompressed_size as u64,
+                    actual: decompressed_len as u64,
+                });
+            }
+            decompressed
+        } else {
+            Vec::new()
+        };
+    let content_checksum = frame_info.content_checksum.then(|| {
+        let mut content_hasher = XxHash32::with_seed(0);
+        content_hasher.write(&decompressed);
+        content_hasher.finish() as u32
+    });
+
+    let mut frame_info_buffer = [0u8; MAX_FRAME_INFO_SIZE];
+    let size = frame_info.write(&mut frame_info_buffer)?;
+    out.write_all(&frame_info_buffer[..size])?;
+
+    let mut block_info_buffer = [0u8; BLOCK_INFO_SIZE];
+    // Like the encoder, an empty frame has no blocks
+    if uncompressed_size > 0 {
+        let (block_info, block_data) = if store_uncompressed {
+            (
+                BlockInfo::Uncompressed(uncompressed_size as u32),
+                &decompressed[..],
+            )
+        } else {
+            (BlockInfo::Compressed(block.len() as u32), block)
+        };
+        block_info.write(&mut block_info_buffer[..])?;
+        out.write_all(&block_info_buffer[..])?;
+        out.write_all(block_data)?;
+        if frame_info.block_checksums {
+            let mut block_hasher = XxHash32::with_seed(0);
+            block_hasher.write(block_data);
+            let block_checksum = block_hasher.finish() as u32;
+            out.write_all(&block_checksum.to_le_bytes())?;
+        }
+    }
+
+    BlockInfo::EndMark.write(&mut block_info_buffer[..])?;
+    out.write_all(&block_info_buffer[..])?;
+    if let Some(content_checksum) = content_checksum {
+        out.write_all(&content_checksum.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Reads the header of a frame passed to [`concat_frames`], returning it with its length.
+fn read_concat_frame_header(frame: &[u8]) -> Result<(FrameInfo, usize), Error> {
+    take(frame, &mut 0, MAGIC_NUMBER_SIZE)?;
+    let required = FrameInfo::read_size(&frame[..frame.len().min(MIN_FRAME_INFO_SIZE)])?;
+    let frame_info = FrameInfo::read(take(frame, &mut 0, required)?)?;
+    if frame_info.legacy_frame || frame_info.block_mode != BlockMode::Independent {
+        return Err(Error::IncompatibleFrames);
+    }
+    if frame_info.dict_id.is_some() {
+        return Err(Error::DictionaryNotSupported);
+    }
+    Ok((frame_info, required))
+}
+
+/// Returns the next `len` bytes of `input` at `pos` and advances `pos`.
+pub(crate) fn take<'a>(input: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+    let data = input
+        .get(*pos..*pos + len)
+        .ok_or(io::ErrorKind::UnexpectedEof)?;
+    *pos += len;
+    Ok(data)
+}
+
 /// A wrapper around an [`FrameEncoder<W>`] that finishes the stream on drop.
 ///
 /// This can be created by the [`auto_finish()`] method on the [`FrameEncoder<W>`].
 ///
 /// # Note
-/// Errors on drop get silently ignored. If you want to handle errors then use [`finish()`] or
-/// [`try_finish()`] instead.
+/// Errors on drop get silently ignored, since `Drop` can't return them. A failed write of the end
+/// of the frame then goes unnoticed and leaves a truncated frame behind. If you want to handle
+/// errors then use [`finish()`] or [`try_finish()`] instead.
 ///
 /// [`finish()`]: FrameEncoder::finish
 /// [`try_finish()`]: FrameEncoder::try_finish
 /// [`auto_finish()`]: FrameEncoder::auto_finish
+#[must_use = "the frame is finished when the encoder is dropped"]
 pub struct AutoFinishEncoder<W: Write> {
     // We wrap this in an option to take it during drop.
     encoder: Option<FrameEncoder<W>>,
@@ -447,27 +1108,68 @@ impl<W: fmt::Debug + io::Write> fmt::Debug for FrameEncoder<W> {
             .field("is_frame_open", &self.is_frame_open)
             .field("content_hasher", &self.content_hasher)
             .field("content_len", &self.content_len)
-            .field("compression_table", &"{ ... }")
             .field("data_to_frame_written", &self.data_to_frame_written)
             .field("dst", &"[...]")
-            .field("src", &"[...]")
-            .field("src_start", &self.src_start)
-            .field("src_end", &self.src_end)
-            .field("ext_dict_offset", &self.ext_dict_offset)
-            .field("ext_dict_len", &self.ext_dict_len)
-            .field("src_stream_offset", &self.src_stream_offset)
+            .field("stream", &self.stream)
+            .field("adaptive", &self.adaptive)
+            .field("raw_block_run", &self.raw_block_run)
+            .field("auto_block_mode_fallback", &self.auto_block_mode_fallback)
+            .field("min_compression_gain", &self.min_compression_gain)
+            .field("header_patch", &self.header_patch.is_some())
+            .field("grown_block_size", &self.grown_block_size)
+            .field("stats", &self.stats)
+            .field("frame_start", &self.frame_start)
+            .field("max_src_capacity", &self.max_src_capacity)
+            .field("compressible_run", &self.compressible_run)
+            .field("checksum_only_full_blocks", &self.checksum_only_full_blocks)
             .finish()
     }
 }
 
-/// Copy `src` into `target` starting from the `start` index, overwriting existing data if any.
-#[inline]
-fn vec_copy_overwriting(target: &mut Vec<u8>, target_start: usize, src: &[u8]) {
-    debug_assert!(target_start + src.len() <= target.capacity());
+impl<W: io::Write + io::Seek> FrameEncoder<W> {
+    /// Creates a new Encoder with the specified FrameInfo, which declares the content size of
+    /// each frame without knowing it upfront.
+    ///
+    /// The frame header is written with a placeholder content size. When the frame is finished,
+    /// the encoder seeks back to the start of the frame, rewrites the header with the actual
+    /// content size and header checksum, and seeks back to the end of the frame. The
+    /// `content_size` of `frame_info` is ignored.
+    ///
+    /// The writer must not be repositioned while a frame is written.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::{Cursor, Write};
+    /// use lz4_flex::frame::{FrameEncoder, FrameInfo};
+    ///
+    /// let mut encoder = FrameEncoder::with_seekable(FrameInfo::new(), Cursor::new(Vec::new()));
+    /// encoder.write_all(b"content size not known upfront").unwrap();
+    /// let compressed = encoder.finish().unwrap().into_inner();
+    /// ```
+    pub fn with_seekable(mut frame_info: FrameInfo, wtr: W) -> Self {
+        frame_info.content_size = None;
+        let mut encoder = Self::with_frame_info(frame_info, wtr);
+        encoder.header_patch = Some(patch_frame_header::<W>);
+        encoder
+    }
+}
 
-    // By combining overwriting (copy_from_slice) and extending (extend_from_slice)
-    // we can fill the ring buffer without initializing it (eg. filling with 0).
-    let overwrite_len = (target.len() - target_start).min(src.len());
-    target[target_start..target_start + overwrite_len].copy_from_slice(&src[..overwrite_len]);
-    target.extend_from_slice(&src[overwrite_len..]);
+/// Overwrites the frame header, which starts `frame_len` bytes before the current position of
+/// `w`, with `header` and restores the position.
+fn patch_frame_header<W: io::Write + io::Seek>(
+    w: &mut W,
+    frame_len: u64,
+    header: &[u8],
+) -> io::Result<()> {
+    let end = w.stream_position()?;
+    let start = end.checked_sub(frame_len).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "writer was repositioned while writing the frame",
+        )
+    })?;
+    w.seek(io::SeekFrom::Start(start))?;
+    w.write_all(header)?;
+    w.seek(io::SeekFrom::Start(end))?;
+    Ok(())
 }
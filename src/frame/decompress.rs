@@ -6,9 +6,10 @@ use std::{
 };
 use twox_hash::XxHash32;
 
+use super::compress::take;
 use super::header::{
-    BlockInfo, BlockMode, FrameInfo, LZ4F_LEGACY_MAGIC_NUMBER, MAGIC_NUMBER_SIZE,
-    MAX_FRAME_INFO_SIZE, MIN_FRAME_INFO_SIZE,
+    BlockInfo, BlockMode, FrameInfo, BLOCK_INFO_SIZE, LZ4F_LEGACY_MAGIC_NUMBER, LZ4F_MAGIC_NUMBER,
+    MAGIC_NUMBER_SIZE, MAX_FRAME_INFO_SIZE, MIN_FRAME_INFO_SIZE,
 };
 use super::Error;
 use crate::{
@@ -45,6 +46,20 @@ use crate::{
 ///     }
 /// }
 /// ```
+///
+/// # Detecting corruption early
+/// The content checksum of a frame covers all of its data and is only stored at the end, so the
+/// format has no partial checksum to verify a prefix of the data against. Corruption is detected
+/// while streaming if the frame has block checksums (see `FrameInfo::block_checksums`): each
+/// block is verified when it is read, before its data is returned, and a mismatch fails the read
+/// with `Error::BlockChecksumError`. The running hash of the content is available with
+/// [`FrameDecoder::content_hash`], e.g. to compare it with checksums transmitted out of band. For
+/// periodic checksums over the uncompressed data, see [`crate::block::integrity`].
+///
+/// # Non-blocking readers
+/// A read of the underlying reader returning `Ok(0)` is taken as EOF, and a `WouldBlock` error
+/// inside of a block loses the bytes of the block read so far. For non-blocking readers, e.g.
+/// sockets, use [`FrameDecoder::new_non_blocking`] instead.
 pub struct FrameDecoder<R: io::Read> {
     /// The underlying reader.
     r: R,
@@ -52,10 +67,35 @@ pub struct FrameDecoder<R: io::Read> {
     /// It starts as `None` and is filled with the FrameInfo is read from the input.
     /// It's reset to `None` once the frame EndMarker is read from the input.
     current_frame_info: Option<FrameInfo>,
+    /// The FrameInfo of the last frame whose header has been read. Unlike `current_frame_info`,
+    /// it's kept after the end of the frame.
+    last_frame_info: Option<FrameInfo>,
     /// Xxhash32 used when content checksum is enabled.
     content_hasher: XxHash32,
     /// Total length of decompressed output for the current frame.
     content_len: u64,
+    /// Total number of bytes read from the underlying reader, across all frames.
+    compressed_bytes_consumed: u64,
+    /// Result of the content checksum verification, once the end of a frame with a content
+    /// checksum has been read.
+    content_checksum_verified: Option<bool>,
+    /// If set, the decoder stops after the end of the first frame instead of reading the next one.
+    stop_after_frame: bool,
+    /// Whether the end of a frame has been read.
+    frame_finished: bool,
+    /// Whether block checksums are verified, if present.
+    verify_block_checksums: bool,
+    /// Whether block checksums are only present after blocks of the max block size, see
+    /// `FrameEncoder::set_checksum_only_full_blocks`.
+    checksum_only_full_blocks: bool,
+    /// Whether the content checksum is verified, if present.
+    verify_content_checksum: bool,
+    /// Whether the content checksum of the current frame is computed.
+    hash_content: bool,
+    /// Whether frames with a dictionary id are decoded, instead of being rejected.
+    ignore_dict_id: bool,
+    /// Called with `content_len` after each decompressed block.
+    block_boundary_callback: Option<Box<dyn FnMut(u64) + Send + Sync>>,
     /// The compressed bytes buffer, taken from the underlying reader.
     src: Vec<u8>,
     /// The decompressed bytes buffer. Bytes are decompressed from src to dst
@@ -83,11 +123,166 @@ impl<R: io::Read> FrameDecoder<R> {
             dst_start: 0,
             dst_end: 0,
             current_frame_info: None,
+            last_frame_info: None,
             content_hasher: XxHash32::with_seed(0),
             content_len: 0,
+            compressed_bytes_consumed: 0,
+            content_checksum_verified: None,
+            stop_after_frame: false,
+            frame_finished: false,
+            verify_block_checksums: true,
+            checksum_only_full_blocks: false,
+            verify_content_checksum: true,
+            hash_content: false,
+            ignore_dict_id: false,
+            block_boundary_callback: None,
         }
     }
 
+    /// Sets whether the decoder stops after the end of the first frame.
+    ///
+    /// By default, the decoder continues with the next frame after the end of a frame, which
+    /// fails if the frame is followed by unrelated data. If set, `read` returns 0 (EOF) once the
+    /// end of the frame, including its content checksum, has been read, and no further bytes are
+    /// read from the underlying reader.
+    pub fn set_stop_after_frame(&mut self, stop_after_frame: bool) {
+        self.stop_after_frame = stop_after_frame;
+    }
+
+    /// Sets a callback, which is called after each data block of a frame has been decompressed,
+    /// with the number of bytes decompressed in the current frame so far. So the callback
+    /// receives the end offset of each block in the decompressed data of the frame, e.g. to build
+    /// an index for seeking while decoding.
+    ///
+    /// The callback is called when a block is read from the underlying reader, which may be
+    /// before its data has been returned by `read`.
+    pub fn set_block_boundary_callback(
+        &mut self,
+        callback: impl FnMut(u64) + Send + Sync + 'static,
+    ) {
+        self.block_boundary_callback = Some(Box::new(callback));
+    }
+
+    /// Sets whether block checksums are verified. Enabled by default.
+    ///
+    /// If disabled, the block checksums are still read, but not computed, which saves a pass over
+    /// each block. Only disable this for trusted input.
+    pub fn set_verify_block_checksums(&mut self, verify: bool) {
+        self.verify_block_checksums = verify;
+    }
+
+    /// Sets whether block checksums are expected only after blocks of the max block size.
+    ///
+    /// Decodes the non-standard frames written by a `FrameEncoder` with
+    /// [`FrameEncoder::set_checksum_only_full_blocks`], in which partial blocks have no checksum.
+    /// The checksum of a full compressed block is verified after decompressing it. Standard
+    /// frames with block checksums fail to decode with this setting, so only enable it if the
+    /// data is known to be written that way. Disabled by default.
+    ///
+    /// [`FrameEncoder::set_checksum_only_full_blocks`]:
+    ///     crate::frame::FrameEncoder::set_checksum_only_full_blocks
+    pub fn set_expect_checksum_only_full_blocks(&mut self, only_full_blocks: bool) {
+        self.checksum_only_full_blocks = only_full_blocks;
+    }
+
+    /// Sets whether the content checksum is verified. Enabled by default.
+    ///
+    /// If disabled, the content checksum is still read, but not computed, which saves a pass over
+    /// the decompressed data. `verify_consumed` then returns `None`. Only disable this for
+    /// trusted input. Takes effect at the start of the next frame.
+    pub fn set_verify_content_checksum(&mut self, verify: bool) {
+        self.verify_content_checksum = verify;
+    }
+
+    /// Sets whether frames with a dictionary id are decoded. Disabled by default.
+    ///
+    /// Decompressing with a dictionary is currently unsupported, so frames with a dictionary id
+    /// are rejected with `Error::DictionaryNotSupported` by default. If set, they are decoded
+    /// without a dictionary, which is only correct if the dictionary id is informational and the
+    /// data doesn't reference the dictionary. The id can be read with `frame_info`.
+    pub fn set_ignore_dict_id(&mut self, ignore_dict_id: bool) {
+        self.ignore_dict_id = ignore_dict_id;
+    }
+
+    /// Returns the `FrameInfo` of the last frame whose header has been read, e.g. to read its
+    /// `dict_id`.
+    ///
+    /// Returns `None` until the first frame header has been read. The `FrameInfo` is kept after
+    /// the end of the frame, until the header of the next frame is read.
+    pub fn frame_info(&self) -> Option<&FrameInfo> {
+        self.last_frame_info.as_ref()
+    }
+
+    /// Returns whether the content checksum of the current frame matched the decompressed data.
+    ///
+    /// Returns `None` until the end of the frame, including its content checksum, has been read,
+    /// or if the frame doesn't have a content checksum. Reading the next frame resets this.
+    pub fn verify_consumed(&self) -> Option<bool> {
+        self.content_checksum_verified
+    }
+
+    /// Returns the running xxhash32 of the data decompressed so far in the current frame.
+    ///
+    /// This is only computed if the frame has a content checksum and its verification is enabled,
+    /// see `set_verify_content_checksum`. Note that the decoder
+    /// decompresses whole blocks, so this may cover data not yet read by the caller.
+    pub fn content_hash(&self) -> u32 {
+        self.content_hasher.finish() as u32
+    }
+
+    /// Returns the number of bytes consumed from the underlying reader so far.
+    ///
+    /// This includes frame headers, block headers, block data and checksums. Together with the
+    /// size of the compressed input, this can be used for progress reporting.
+    pub fn compressed_bytes_consumed(&self) -> u64 {
+        self.compressed_bytes_consumed
+    }
+
+    /// Returns the number of decompressed bytes which are buffered and not read yet.
+    ///
+    /// A `read` of up to this many bytes doesn't read from the underlying reader.
+    pub fn buffered_len(&self) -> usize {
+        self.dst_end - self.dst_start
+    }
+
+    /// Returns the decompressed bytes which are buffered and not read yet, without consuming them.
+    pub fn peek(&self) -> &[u8] {
+        &self.dst[self.dst_start..self.dst_end]
+    }
+
+    /// Decompresses the next block and returns its decompressed bytes, without copying them into
+    /// a caller provided buffer like `read`. Returns `None` at the end of a frame, the next call
+    /// continues with the next frame, and at the end of the input.
+    ///
+    /// Each call reads at most one block from the underlying reader, so the data of a block can
+    /// be processed as soon as it's available. If decompressed bytes are buffered from a previous
+    /// `read`, the rest of the buffered block is returned instead.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use lz4_flex::frame::{BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
+    /// let frame_info = FrameInfo::new().block_size(BlockSize::Max64KB);
+    /// let mut encoder = FrameEncoder::with_frame_info(frame_info, Vec::new());
+    /// encoder.write_all(&vec![7u8; 100 * 1024]).unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    ///
+    /// let mut decoder = FrameDecoder::new(&compressed[..]);
+    /// let mut block_lens = Vec::new();
+    /// while let Some(block) = decoder.read_block_now().unwrap() {
+    ///     block_lens.push(block.len());
+    /// }
+    /// assert_eq!(block_lens, [64 * 1024, 36 * 1024]);
+    /// ```
+    pub fn read_block_now(&mut self) -> io::Result<Option<&[u8]>> {
+        if self.dst_start == self.dst_end && self.read_more()? == 0 {
+            return Ok(None);
+        }
+        let start = self.dst_start;
+        self.dst_start = self.dst_end;
+        Ok(Some(&self.dst[start..self.dst_end]))
+    }
+
     /// Gets a reference to the underlying reader in this decoder.
     pub fn get_ref(&self) -> &R {
         &self.r
@@ -106,6 +301,183 @@ impl<R: io::Read> FrameDecoder<R> {
         self.r
     }
 
+    /// Consumes the FrameDecoder and returns the underlying reader, if the end of a frame has
+    /// been reached and all of its decompressed data has been read. Otherwise the decoder is
+    /// returned unchanged as the error.
+    ///
+    /// The decoder only reads the bytes of a frame from the underlying reader, without reading
+    /// ahead. So the returned reader is positioned at the first byte after the frame, i.e. after
+    /// the end mark and the content checksum, e.g. to read application data which follows the
+    /// frame on the same stream. `read` returns 0 at the end of each frame, so reading until EOF
+    /// reaches it. Legacy frames have no end mark and aren't supported.
+    // Like `Arc::try_unwrap`, the decoder is handed back on failure.
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner_at_frame_end(self) -> Result<R, Self> {
+        if self.frame_finished && self.current_frame_info.is_none() && self.buffered_len() == 0 {
+            Ok(self.r)
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns whether the end of a frame has been read and the next frame header hasn't.
+    pub(crate) fn at_frame_end(&self) -> bool {
+        self.frame_finished && self.current_frame_info.is_none()
+    }
+
+    /// Skips to the next frame, e.g. after a decoding error.
+    ///
+    /// Scans the underlying reader for the next LZ4 frame magic number and continues decoding the
+    /// frame found there, discarding the rest of the current frame. Returns `false` if the end of
+    /// the reader was reached without finding a frame. Candidates with an invalid frame header are
+    /// skipped.
+    ///
+    /// The reader is scanned byte by byte, so wrapping an unbuffered reader in a
+    /// `std::io::BufReader` is recommended.
+    pub fn recover_next_frame(&mut self) -> Result<bool, Error> {
+        self.current_frame_info = None;
+        self.frame_finished = false;
+        self.dst_start = 0;
+        self.dst_end = 0;
+        // Bytes which have been read as the header of a rejected candidate and are scanned again,
+        // starting after its first byte.
+        let mut rescan = Vec::new();
+        let mut rescan_pos = 0;
+        // The last 4 bytes scanned, in little endian. As the lowest byte of the magic number isn't
+        // 0, it can't match before 4 bytes have been scanned.
+        let mut window = 0u32;
+        loop {
+            let byte = match self.next_recovery_byte(&rescan, &mut rescan_pos)? {
+                Some(byte) => byte,
+                None => return Ok(false),
+            };
+            window = (window >> 8) | ((byte as u32) << 24);
+            if window != LZ4F_MAGIC_NUMBER {
+                continue;
+            }
+
+            let mut buffer = [0u8; MAX_FRAME_INFO_SIZE];
+            buffer[..MAGIC_NUMBER_SIZE].copy_from_slice(&window.to_le_bytes());
+            let mut len = MAGIC_NUMBER_SIZE;
+            let required = loop {
+                let required = if len < MIN_FRAME_INFO_SIZE {
+                    MIN_FRAME_INFO_SIZE
+                } else {
+                    match FrameInfo::read_size(&buffer[..MIN_FRAME_INFO_SIZE]) {
+                        Ok(required) => required,
+                        Err(_) => break None,
+                    }
+                };
+                if len >= required {
+                    break Some(required);
+                }
+                let byte = match self.next_recovery_byte(&rescan, &mut rescan_pos)? {
+                    Some(byte) => byte,
+                    None => return Ok(false),
+                };
+                buffer[len] = byte;
+                len += 1;
+            };
+            // A header which ends within the rescanned bytes is skipped, as the bytes following it
+            // can't be handed to the reads of the blocks. This needs a rejected candidate with a
+            // valid FLG and BD byte, which contains the whole next frame header.
+            if rescan_pos == rescan.len() {
+                if let Some(required) = required {
+                    if self.start_frame(&buffer[..required]).is_ok() {
+                        return Ok(true);
+                    }
+                }
+            }
+            // Not a valid frame header, scan its bytes again
+            let mut unscanned = buffer[1..len].to_vec();
+            unscanned.extend_from_slice(&rescan[rescan_pos..]);
+            rescan = unscanned;
+            rescan_pos = 0;
+            window = 0;
+        }
+    }
+
+    /// Returns the next byte for `recover_next_frame`, taken from `rescan` before the reader.
+    /// Returns `None` at the end of the reader.
+    fn next_recovery_byte(
+        &mut self,
+        rescan: &[u8],
+        rescan_pos: &mut usize,
+    ) -> Result<Option<u8>, Error> {
+        if let Some(&byte) = rescan.get(*rescan_pos) {
+            *rescan_pos += 1;
+            return Ok(Some(byte));
+        }
+        let mut byte = [0u8; 1];
+        loop {
+            match self.r.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.compressed_bytes_consumed += 1;
+        Ok(Some(byte[0]))
+    }
+
+    /// Returns the number of bytes the next `read_more` consumes from the underlying reader,
+    /// given that `input` are the next bytes of the reader. Returns `None` if that can't be
+    /// determined from `input` yet.
+    ///
+    /// If `input` is malformed, the returned length is enough for `read_more` to detect it.
+    pub(crate) fn next_read_len(&self, input: &[u8]) -> Option<usize> {
+        let parsed_frame_info;
+        let (frame_info_len, frame_info) = match &self.current_frame_info {
+            Some(frame_info) => (0, frame_info),
+            None if self.stop_after_frame && self.frame_finished => return Some(0),
+            None => {
+                if input.len() < MAGIC_NUMBER_SIZE {
+                    return None;
+                }
+                let required =
+                    match FrameInfo::read_size(&input[..input.len().min(MIN_FRAME_INFO_SIZE)]) {
+                        Ok(required) => required,
+                        Err(_) => return Some(MIN_FRAME_INFO_SIZE),
+                    };
+                if input.len() < required {
+                    return None;
+                }
+                parsed_frame_info = match FrameInfo::read(&input[..required]) {
+                    Ok(frame_info) => frame_info,
+                    Err(_) => return Some(required),
+                };
+                (required, &parsed_frame_info)
+            }
+        };
+        // `read_more` reads the frame info and the first block at once
+        let input = &input[frame_info_len..];
+        if input.len() < BLOCK_INFO_SIZE {
+            return None;
+        }
+        let checksum_len = |enabled: bool| if enabled { size_of::<u32>() } else { 0 };
+        let block_len = match BlockInfo::read(&input[..BLOCK_INFO_SIZE]) {
+            Ok(block_info @ (BlockInfo::Compressed(len) | BlockInfo::Uncompressed(len))) => {
+                if len as usize > frame_info.block_size.get_size() {
+                    // BlockTooBig is detected after reading the block info
+                    0
+                } else {
+                    // With `checksum_only_full_blocks`, whether a compressed block has a checksum
+                    // is only known after decompressing it. Requiring the 4 bytes anyway is
+                    // fine, as any block is followed by at least the next block info.
+                    let partial_uncompressed = matches!(block_info, BlockInfo::Uncompressed(_))
+                        && len as usize != frame_info.block_size.get_size();
+                    let has_checksum = frame_info.block_checksums
+                        && !(self.checksum_only_full_blocks && partial_uncompressed);
+                    len as usize + checksum_len(has_checksum)
+                }
+            }
+            Ok(BlockInfo::EndMark) => checksum_len(frame_info.content_checksum),
+            Err(_) => 0,
+        };
+        Some(frame_info_len + BLOCK_INFO_SIZE + block_len)
+    }
+
     fn read_frame_info(&mut self) -> Result<usize, io::Error> {
         let mut buffer = [0u8; MAX_FRAME_INFO_SIZE];
 
@@ -114,7 +486,16 @@ impl<R: io::Read> FrameDecoder<R> {
             MAGIC_NUMBER_SIZE => (),
             read => self.r.read_exact(&mut buffer[read..MAGIC_NUMBER_SIZE])?,
         }
+        self.compressed_bytes_consumed += MAGIC_NUMBER_SIZE as u64;
+        self.read_frame_info_after_magic(buffer)
+    }
 
+    /// Reads the rest of the frame info, `buffer` starts with the magic number which has already
+    /// been read.
+    fn read_frame_info_after_magic(
+        &mut self,
+        mut buffer: [u8; MAX_FRAME_INFO_SIZE],
+    ) -> Result<usize, io::Error> {
         if u32::from_le_bytes(buffer[0..MAGIC_NUMBER_SIZE].try_into().unwrap())
             != LZ4F_LEGACY_MAGIC_NUMBER
         {
@@ -128,15 +509,22 @@ impl<R: io::Read> FrameDecoder<R> {
                     .r
                     .read_exact(&mut buffer[MAGIC_NUMBER_SIZE + read..MIN_FRAME_INFO_SIZE])?,
             }
+            self.compressed_bytes_consumed += (MIN_FRAME_INFO_SIZE - MAGIC_NUMBER_SIZE) as u64;
         }
         let required = FrameInfo::read_size(&buffer[..MIN_FRAME_INFO_SIZE])?;
         if required != MIN_FRAME_INFO_SIZE && required != MAGIC_NUMBER_SIZE {
             self.r
                 .read_exact(&mut buffer[MIN_FRAME_INFO_SIZE..required])?;
+            self.compressed_bytes_consumed += (required - MIN_FRAME_INFO_SIZE) as u64;
         }
+        self.start_frame(&buffer[..required])
+    }
 
-        let frame_info = FrameInfo::read(&buffer[..required])?;
-        if frame_info.dict_id.is_some() {
+    /// Parses the frame info in `frame_info_bytes`, including the magic number, and prepares
+    /// decoding the frame. Returns the length of the frame info.
+    fn start_frame(&mut self, frame_info_bytes: &[u8]) -> Result<usize, io::Error> {
+        let frame_info = FrameInfo::read(frame_info_bytes)?;
+        if frame_info.dict_id.is_some() && !self.ignore_dict_id {
             // Unsupported right now so it must be None
             return Err(Error::DictionaryNotSupported.into());
         }
@@ -158,19 +546,24 @@ impl<R: io::Read> FrameDecoder<R> {
         self.dst.clear();
         self.src.reserve_exact(max_block_size);
         self.dst.reserve_exact(dst_size);
+        self.hash_content = frame_info.content_checksum && self.verify_content_checksum;
+        self.last_frame_info = Some(frame_info.clone());
         self.current_frame_info = Some(frame_info);
+        self.frame_finished = false;
         self.content_hasher = XxHash32::with_seed(0);
         self.content_len = 0;
+        self.content_checksum_verified = None;
         self.ext_dict_len = 0;
         self.dst_start = 0;
         self.dst_end = 0;
-        Ok(required)
+        Ok(frame_info_bytes.len())
     }
 
     #[inline]
-    fn read_checksum(r: &mut R) -> Result<u32, io::Error> {
+    fn read_checksum(r: &mut R, bytes_consumed: &mut u64) -> Result<u32, io::Error> {
         let mut checksum_buffer = [0u8; size_of::<u32>()];
         r.read_exact(&mut checksum_buffer[..])?;
+        *bytes_consumed += checksum_buffer.len() as u64;
         let checksum = u32::from_le_bytes(checksum_buffer);
         Ok(checksum)
     }
@@ -231,12 +624,13 @@ impl<R: io::Read> FrameDecoder<R> {
         let block_info = {
             let mut buffer = [0u8; 4];
             if let Err(err) = self.r.read_exact(&mut buffer) {
-                if err.kind() == ErrorKind::UnexpectedEof {
+                if err.kind() == ErrorKind::UnexpectedEof && frame_info.content_size.is_none() {
                     return Ok(0);
                 } else {
                     return Err(err);
                 }
             }
+            self.compressed_bytes_consumed += buffer.len() as u64;
             BlockInfo::read(&buffer)?
         };
         match block_info {
@@ -252,12 +646,18 @@ impl<R: io::Read> FrameDecoder<R> {
                     self.dst_start,
                     self.dst_start + len,
                 ))?;
-                if frame_info.block_checksums {
-                    let expected_checksum = Self::read_checksum(&mut self.r)?;
-                    Self::check_block_checksum(
-                        &self.dst[self.dst_start..self.dst_start + len],
-                        expected_checksum,
-                    )?;
+                self.compressed_bytes_consumed += len as u64;
+                if frame_info.block_checksums
+                    && (!self.checksum_only_full_blocks || len == max_block_size)
+                {
+                    let expected_checksum =
+                        Self::read_checksum(&mut self.r, &mut self.compressed_bytes_consumed)?;
+                    if self.verify_block_checksums {
+                        Self::check_block_checksum(
+                            &self.dst[self.dst_start..self.dst_start + len],
+                            expected_checksum,
+                        )?;
+                    }
                 }
 
                 self.dst_end += len;
@@ -272,9 +672,13 @@ impl<R: io::Read> FrameDecoder<R> {
                 // https://github.com/rust-lang/rust/issues/42788 stabilizes
                 self.r
                     .read_exact(vec_resize_and_get_mut(&mut self.src, 0, len))?;
-                if frame_info.block_checksums {
-                    let expected_checksum = Self::read_checksum(&mut self.r)?;
-                    Self::check_block_checksum(&self.src[..len], expected_checksum)?;
+                self.compressed_bytes_consumed += len as u64;
+                if frame_info.block_checksums && !self.checksum_only_full_blocks {
+                    let expected_checksum =
+                        Self::read_checksum(&mut self.r, &mut self.compressed_bytes_consumed)?;
+                    if self.verify_block_checksums {
+                        Self::check_block_checksum(&self.src[..len], expected_checksum)?;
+                    }
                 }
 
                 let with_dict_mode =
@@ -306,6 +710,17 @@ impl<R: io::Read> FrameDecoder<R> {
                 }
                 .map_err(Error::DecompressionError)?;
 
+                if frame_info.block_checksums
+                    && self.checksum_only_full_blocks
+                    && decomp_size == max_block_size
+                {
+                    let expected_checksum =
+                        Self::read_checksum(&mut self.r, &mut self.compressed_bytes_consumed)?;
+                    if self.verify_block_checksums {
+                        Self::check_block_checksum(&self.src[..len], expected_checksum)?;
+                    }
+                }
+
                 self.dst_end += decomp_size;
                 self.content_len += decomp_size as u64;
             }
@@ -321,19 +736,28 @@ impl<R: io::Read> FrameDecoder<R> {
                     }
                 }
                 if frame_info.content_checksum {
-                    let expected_checksum = Self::read_checksum(&mut self.r)?;
-                    let calc_checksum = self.content_hasher.finish() as u32;
-                    if calc_checksum != expected_checksum {
-                        return Err(Error::ContentChecksumError.into());
+                    let expected_checksum =
+                        Self::read_checksum(&mut self.r, &mut self.compressed_bytes_consumed)?;
+                    if self.hash_content {
+                        let calc_checksum = self.content_hasher.finish() as u32;
+                        self.content_checksum_verified = Some(calc_checksum == expected_checksum);
+                        if calc_checksum != expected_checksum {
+                            return Err(Error::ContentChecksumError.into());
+                        }
                     }
                 }
                 self.current_frame_info = None;
+                self.frame_finished = true;
                 return Ok(0);
             }
         }
 
+        if let Some(callback) = &mut self.block_boundary_callback {
+            callback(self.content_len);
+        }
+
         // Content checksum, if applicable
-        if frame_info.content_checksum {
+        if self.hash_content {
             self.content_hasher
                 .write(&self.dst[self.dst_start..self.dst_end]);
         }
@@ -342,10 +766,32 @@ impl<R: io::Read> FrameDecoder<R> {
     }
 
     fn read_more(&mut self) -> io::Result<usize> {
-        if self.current_frame_info.is_none() && self.read_frame_info()? == 0 {
-            return Ok(0);
+        if self.current_frame_info.is_none() {
+            if self.stop_after_frame && self.frame_finished {
+                return Ok(0);
+            }
+            if self.read_frame_info()? == 0 {
+                return Ok(0);
+            }
+        }
+        self.read_block().map_err(|err| self.truncation_error(err))
+    }
+
+    /// Returns `Error::ContentLengthError` with the length decompressed so far instead of an
+    /// unexpected EOF in a frame with a content size, so that a truncated frame can be told apart
+    /// from a corrupt one.
+    fn truncation_error(&self, err: io::Error) -> io::Error {
+        match &self.current_frame_info {
+            Some(FrameInfo {
+                content_size: Some(expected),
+                ..
+            }) if err.kind() == ErrorKind::UnexpectedEof => Error::ContentLengthError {
+                expected: *expected,
+                actual: self.content_len,
+            }
+            .into(),
+            _ => err,
         }
-        self.read_block()
     }
 }
 
@@ -427,6 +873,8 @@ impl<R: fmt::Debug + io::Read> fmt::Debug for FrameDecoder<R> {
             .field("r", &self.r)
             .field("content_hasher", &self.content_hasher)
             .field("content_len", &self.content_len)
+            .field("compressed_bytes_consumed", &self.compressed_bytes_consumed)
+            .field("content_checksum_verified", &self.content_checksum_verified)
             .field("src", &"[...]")
             .field("dst", &"[...]")
             .field("dst_start", &self.dst_start)
@@ -438,6 +886,139 @@ impl<R: fmt::Debug + io::Read> fmt::Debug for FrameDecoder<R> {
     }
 }
 
+/// Decompresses the frame in `input` and appends the decompressed data to `output`.
+///
+/// The data is copied from the decoder's buffer to `output` directly, without an intermediate
+/// `Vec`.
+#[cfg(feature = "bytes")]
+pub fn decompress_into_bytes_mut(input: &[u8], output: &mut bytes::BytesMut) -> Result<(), Error> {
+    let mut decoder = FrameDecoder::new(input);
+    loop {
+        let data = decoder.fill_buf()?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        output.extend_from_slice(data);
+        let len = data.len();
+        decoder.consume(len);
+    }
+}
+
+/// Decompresses all frames in `input`, which is already in memory, into a new `Vec`.
+///
+/// Unlike reading from a [`FrameDecoder`] over the slice, each block is decompressed directly
+/// from `input` into the returned `Vec`, without copying it into the decoder's buffers first.
+/// Linked blocks reference the previous blocks in the output. Consecutive frames are
+/// decompressed into the same `Vec`, like the `lz4` command line tool does, while
+/// `FrameDecoder::read_to_end` stops at the end of each frame. Legacy frames, and anything after
+/// them, are decoded with a `FrameDecoder`.
+///
+/// Checksums are verified. Frames with a dictionary id are rejected.
+///
+/// # Example
+/// ```
+/// use lz4_flex::frame::{decompress_frame_slice, FrameEncoder};
+/// use std::io::Write;
+/// let mut encoder = FrameEncoder::new(Vec::new());
+/// encoder.write_all(b"decompressed without copying blocks").unwrap();
+/// let compressed = encoder.finish().unwrap();
+/// let decompressed = decompress_frame_slice(&compressed).unwrap();
+/// assert_eq!(decompressed, b"decompressed without copying blocks");
+/// ```
+pub fn decompress_frame_slice(input: &[u8]) -> Result<Vec<u8>, Error> {
+    // `output[..written]` is the decompressed data, the rest is initialized space for the next
+    // block, which grows geometrically.
+    let mut output = Vec::new();
+    let mut written = 0;
+    let mut pos = 0;
+    while pos < input.len() {
+        let frame_start = pos;
+        take(input, &mut pos.clone(), MAGIC_NUMBER_SIZE)?;
+        let header = &input[pos..input.len().min(pos + MIN_FRAME_INFO_SIZE)];
+        let required = FrameInfo::read_size(header)?;
+        let frame_info = FrameInfo::read(take(input, &mut pos, required)?)?;
+        if frame_info.legacy_frame {
+            output.truncate(written);
+            let mut decoder = FrameDecoder::new(&input[frame_start..]);
+            while io::Read::read_to_end(&mut decoder, &mut output)? != 0 {}
+            return Ok(output);
+        }
+        if frame_info.dict_id.is_some() {
+            return Err(Error::DictionaryNotSupported);
+        }
+        let max_block_size = frame_info.block_size.get_size();
+
+        let frame_output_start = written;
+        loop {
+            let block_info = BlockInfo::read(take(input, &mut pos, BLOCK_INFO_SIZE)?)?;
+            let (len, compressed) = match block_info {
+                BlockInfo::Compressed(len) => (len as usize, true),
+                BlockInfo::Uncompressed(len) => (len as usize, false),
+                BlockInfo::EndMark => break,
+            };
+            if len > max_block_size {
+                return Err(Error::BlockTooBig);
+            }
+            let data = take(input, &mut pos, len)?;
+            if frame_info.block_checksums {
+                let expected = u32::from_le_bytes(take(input, &mut pos, 4)?.try_into().unwrap());
+                let mut block_hasher = XxHash32::with_seed(0);
+                block_hasher.write(data);
+                if block_hasher.finish() as u32 != expected {
+                    return Err(Error::BlockChecksumError);
+                }
+            }
+
+            let end = written + max_block_size;
+            if output.len() < end {
+                output.resize(end.max(output.len() * 2), 0);
+            }
+            written += if !compressed {
+                output[written..written + len].copy_from_slice(data);
+                len
+            } else if frame_info.block_mode == BlockMode::Linked {
+                // The previous output of the frame is the prefix
+                crate::block::decompress::decompress_internal::<false, _>(
+                    data,
+                    &mut SliceSink::new(
+                        &mut output[frame_output_start..end],
+                        written - frame_output_start,
+                    ),
+                    b"",
+                )
+                .map_err(Error::DecompressionError)?
+            } else {
+                crate::block::decompress::decompress_internal::<false, _>(
+                    data,
+                    &mut SliceSink::new(&mut output[written..end], 0),
+                    b"",
+                )
+                .map_err(Error::DecompressionError)?
+            };
+        }
+
+        let content = &output[frame_output_start..written];
+        if let Some(expected) = frame_info.content_size {
+            if content.len() as u64 != expected {
+                return Err(Error::ContentLengthError {
+                    expected,
+                    actual: content.len() as u64,
+                });
+            }
+        }
+        if frame_info.content_checksum {
+            let expected = u32::from_le_bytes(take(input, &mut pos, 4)?.try_into().unwrap());
+            let mut content_hasher = XxHash32::with_seed(0);
+            content_hasher.write(content);
+            if content_hasher.finish() as u32 != expected {
+                return Err(Error::ContentChecksumError);
+            }
+        }
+    }
+    output.truncate(written);
+    Ok(output)
+}
+
 /// Similar to `v.get_mut(start..end) but will adjust the len if needed.
 #[inline]
 fn vec_resize_and_get_mut(v: &mut Vec<u8>, start: usize, end: usize) -> &mut [u8] {
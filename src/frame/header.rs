@@ -10,6 +10,7 @@ use std::{
 
 const FLG_RESERVED_MASK: u8 = 0b00000010;
 const FLG_VERSION_MASK: u8 = 0b11000000;
+const FLG_VERSION_MASK_RSHIFT: u8 = 6;
 const FLG_SUPPORTED_VERSION_BITS: u8 = 0b01000000;
 
 const FLG_INDEPENDENT_BLOCKS: u8 = 0b00100000;
@@ -24,7 +25,7 @@ const BD_BLOCK_SIZE_MASK_RSHIFT: u8 = 4;
 
 const BLOCK_UNCOMPRESSED_SIZE_BIT: u32 = 0x80000000;
 
-const LZ4F_MAGIC_NUMBER: u32 = 0x184D2204;
+pub(crate) const LZ4F_MAGIC_NUMBER: u32 = 0x184D2204;
 pub(crate) const LZ4F_LEGACY_MAGIC_NUMBER: u32 = 0x184C2102;
 const LZ4F_SKIPPABLE_MAGIC_RANGE: std::ops::RangeInclusive<u32> = 0x184D2A50..=0x184D2A5F;
 
@@ -33,7 +34,8 @@ pub(crate) const MIN_FRAME_INFO_SIZE: usize = 7;
 pub(crate) const MAX_FRAME_INFO_SIZE: usize = 19;
 pub(crate) const BLOCK_INFO_SIZE: usize = 4;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Different predefines blocksizes to choose when compressing data.
 #[derive(Default)]
 pub enum BlockSize {
@@ -77,9 +79,11 @@ impl BlockSize {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The two `BlockMode` operations that can be set on (`FrameInfo`)[FrameInfo]
 #[derive(Default)]
+#[non_exhaustive]
 pub enum BlockMode {
     /// Every block is compressed independently. The default.
     #[default]
@@ -88,6 +92,13 @@ pub enum BlockMode {
     ///
     /// Effective when the stream contains small blocks.
     Linked,
+    /// Only valid for compression. The encoder picks `Independent` if the frame fits in a single
+    /// block and `Linked` otherwise.
+    ///
+    /// The frame size is taken from `content_size` if set, otherwise the decision is deferred
+    /// until the first block is written. If it can't be determined then (e.g. because the encoder
+    /// is flushed), the fallback set with `FrameEncoder::set_auto_block_mode_fallback` is used.
+    Auto,
 }
 
 // From: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
@@ -125,16 +136,22 @@ pub enum BlockMode {
 // |:----------:| ------ |:----------------:|
 // |  4 bytes   |        |   0 - 4 bytes    |
 //
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The metadata for de/compressing with lz4 frame format.
 pub struct FrameInfo {
     /// If set, includes the total uncompressed size of data in the frame.
+    ///
+    /// `Some(0)` is valid for an empty frame: finishing an encoder without writing any data still
+    /// writes the frame header declaring a size of 0.
     pub content_size: Option<u64>,
     /// The identifier for the dictionary that must be used to correctly decode data.
     /// The compressor and the decompressor must use exactly the same dictionary.
     ///
-    /// Note that this is currently unsupported and for this reason it's not pub.
-    pub(crate) dict_id: Option<u32>,
+    /// Compressing with a dictionary is currently unsupported, so the encoder only writes the id
+    /// into the frame header, e.g. to tag the frame for bookkeeping. `FrameDecoder` rejects frames
+    /// with a dictionary id, unless `FrameDecoder::set_ignore_dict_id` is set.
+    pub dict_id: Option<u32>,
     /// The maximum uncompressed size of each data block.
     pub block_size: BlockSize,
     /// The block mode.
@@ -160,6 +177,12 @@ impl FrameInfo {
         self
     }
 
+    /// The identifier for the dictionary that must be used to correctly decode data.
+    pub fn dict_id(mut self, dict_id: Option<u32>) -> Self {
+        self.dict_id = dict_id;
+        self
+    }
+
     /// The maximum uncompressed size of each data block.
     pub fn block_size(mut self, block_size: BlockSize) -> Self {
         self.block_size = block_size;
@@ -230,6 +253,13 @@ impl FrameInfo {
     }
 
     pub(crate) fn write(&self, output: &mut [u8]) -> Result<usize, Error> {
+        // `Auto` has to be resolved before writing the header and 8MB blocks only exist in
+        // legacy frames, which have no frame descriptor.
+        if matches!(self.block_size, BlockSize::Auto | BlockSize::Max8MB) {
+            return Err(Error::UnsupportedBlocksize(self.block_size as u8));
+        }
+        // Likewise `BlockMode::Auto`, which has no representation in the header.
+        debug_assert_ne!(self.block_mode, BlockMode::Auto);
         let write_size = self.write_size();
         if output.len() < write_size {
             return Err(Error::IoError(io::ErrorKind::UnexpectedEof.into()));
@@ -307,8 +337,11 @@ impl FrameInfo {
         };
 
         if flg_byte & FLG_VERSION_MASK != FLG_SUPPORTED_VERSION_BITS {
-            // version is always 01
-            return Err(Error::UnsupportedVersion(flg_byte & FLG_VERSION_MASK));
+            // Version is always 01, every released producer of the frame format writes it. Older
+            // streams without a version field use the legacy magic number handled above.
+            return Err(Error::UnsupportedVersion(
+                (flg_byte & FLG_VERSION_MASK) >> FLG_VERSION_MASK_RSHIFT,
+            ));
         }
 
         if flg_byte & FLG_RESERVED_MASK != 0 || bd_byte & BD_RESERVED_MASK != 0 {
@@ -336,7 +369,7 @@ impl FrameInfo {
         let mut content_size = None;
         if flg_byte & FLG_CONTENT_SIZE != 0 {
             let mut buffer = [0u8; 8];
-            input.read_exact(&mut buffer).unwrap();
+            input.read_exact(&mut buffer)?;
             content_size = Some(u64::from_le_bytes(buffer));
         }
 
@@ -409,3 +442,78 @@ impl BlockInfo {
         Ok(4)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_info_write_read_roundtrip() {
+        let block_sizes = [
+            BlockSize::Max64KB,
+            BlockSize::Max256KB,
+            BlockSize::Max1MB,
+            BlockSize::Max4MB,
+        ];
+        for block_size in block_sizes {
+            for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+                for block_checksums in [false, true] {
+                    for content_checksum in [false, true] {
+                        for content_size in [None, Some(0), Some(1 << 40), Some(u64::MAX)] {
+                            for dict_id in [None, Some(0), Some(0xDEAD_BEEF)] {
+                                let frame_info = FrameInfo {
+                                    content_size,
+                                    dict_id,
+                                    block_size,
+                                    block_mode,
+                                    block_checksums,
+                                    content_checksum,
+                                    legacy_frame: false,
+                                };
+                                let mut buffer = [0u8; MAX_FRAME_INFO_SIZE];
+                                let size = frame_info.write(&mut buffer).unwrap();
+                                assert_eq!(size, frame_info.write_size());
+                                assert_eq!(FrameInfo::read_size(&buffer[..size]).unwrap(), size);
+                                assert_eq!(FrameInfo::read(&buffer[..size]).unwrap(), frame_info);
+                                // Every truncation is an error instead of a panic.
+                                for len in 0..size {
+                                    assert!(FrameInfo::read(&buffer[..len]).is_err());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frame_info_read_unsupported_version() {
+        let mut buffer = [0u8; MAX_FRAME_INFO_SIZE];
+        let frame_info = FrameInfo {
+            block_size: BlockSize::Max64KB,
+            ..FrameInfo::default()
+        };
+        let size = frame_info.write(&mut buffer).unwrap();
+        for version in [0u8, 2, 3] {
+            let mut header = buffer;
+            header[4] = (header[4] & !FLG_VERSION_MASK) | (version << FLG_VERSION_MASK_RSHIFT);
+            assert!(matches!(
+                FrameInfo::read(&header[..size]),
+                Err(Error::UnsupportedVersion(v)) if v == version
+            ));
+        }
+    }
+
+    #[test]
+    fn frame_info_write_unsupported_block_size() {
+        for block_size in [BlockSize::Auto, BlockSize::Max8MB] {
+            let frame_info = FrameInfo::new().block_size(block_size);
+            let mut buffer = [0u8; MAX_FRAME_INFO_SIZE];
+            assert!(matches!(
+                frame_info.write(&mut buffer),
+                Err(Error::UnsupportedBlocksize(_))
+            ));
+        }
+    }
+}
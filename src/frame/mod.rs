@@ -19,15 +19,28 @@
 
 use std::{fmt, io};
 
+#[cfg(feature = "async")]
+mod async_io;
 #[cfg_attr(feature = "safe-encode", forbid(unsafe_code))]
 pub(crate) mod compress;
 #[cfg_attr(feature = "safe-decode", forbid(unsafe_code))]
 pub(crate) mod decompress;
 pub(crate) mod header;
+mod non_blocking;
 
-pub use compress::{AutoFinishEncoder, FrameEncoder};
-pub use decompress::FrameDecoder;
+pub use compress::{
+    concat_frames, wrap_block_as_frame, AutoFinishEncoder, EncodeStats, FrameEncoder,
+};
+pub use decompress::{decompress_frame_slice, FrameDecoder};
 pub use header::{BlockMode, BlockSize, FrameInfo};
+pub use non_blocking::NonBlockingFrameDecoder;
+
+#[cfg(feature = "async")]
+pub use async_io::{AsyncFrameDecoder, AsyncFrameEncoder};
+#[cfg(feature = "bytes")]
+pub use compress::compress_into_bytes_mut;
+#[cfg(feature = "bytes")]
+pub use decompress::decompress_into_bytes_mut;
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -41,7 +54,8 @@ pub enum Error {
     IoError(io::Error),
     /// Unsupported block size.
     UnsupportedBlocksize(u8),
-    /// Unsupported frame version.
+    /// Unsupported frame version, as stored in the two version bits of the frame descriptor.
+    /// Only version 1 is defined by the LZ4 frame format.
     UnsupportedVersion(u8),
     /// Wrong magic number for the LZ4 frame format.
     WrongMagicNumber,
@@ -62,13 +76,22 @@ pub enum Error {
     SkippableFrame(u32),
     /// External dictionaries are not supported.
     DictionaryNotSupported,
-    /// Content length differs.
+    /// Content length differs, or a frame with a content size is truncated, i.e. the input ends
+    /// before its end mark.
     ContentLengthError {
         /// Expected content length.
         expected: u64,
         /// Actual content length.
         actual: u64,
     },
+    /// The frames can't be concatenated without recompression, see [`concat_frames`].
+    IncompatibleFrames,
+    /// The capacity limit is too small for the smallest block size, see
+    /// [`FrameEncoder::with_capacity_limit`].
+    CapacityLimitTooSmall {
+        /// Minimum capacity limit for the block mode.
+        needed: usize,
+    },
 }
 
 impl From<Error> for io::Error {
@@ -78,7 +101,10 @@ impl From<Error> for io::Error {
             Error::CompressionError(_)
             | Error::DecompressionError(_)
             | Error::SkippableFrame(_)
-            | Error::DictionaryNotSupported => io::Error::new(io::ErrorKind::Other, e),
+            | Error::DictionaryNotSupported => io::Error::other(e),
+            Error::IncompatibleFrames | Error::CapacityLimitTooSmall { .. } => {
+                io::Error::new(io::ErrorKind::InvalidInput, e)
+            }
             Error::WrongMagicNumber
             | Error::UnsupportedBlocksize(..)
             | Error::UnsupportedVersion(..)
@@ -96,8 +122,8 @@ impl From<Error> for io::Error {
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
         match e.get_ref().map(|e| e.downcast_ref::<Error>()) {
-            Some(_) => *e.into_inner().unwrap().downcast::<Error>().unwrap(),
-            None => Error::IoError(e),
+            Some(Some(_)) => *e.into_inner().unwrap().downcast::<Error>().unwrap(),
+            _ => Error::IoError(e),
         }
     }
 }
@@ -0,0 +1,120 @@
+//! Frame decoder for non-blocking readers, e.g. sockets in non-blocking mode.
+
+use std::io::{self, ErrorKind, Read};
+
+use super::FrameDecoder;
+
+/// Size of the chunks read from the underlying reader.
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// A reader for decompressing the LZ4 frame format from a non-blocking reader, created with
+/// [`FrameDecoder::new_non_blocking`].
+///
+/// [`FrameDecoder`] reads headers and blocks with `read_exact`, so a `WouldBlock` error in the
+/// middle of a block loses the bytes read so far, and a read returning `Ok(0)` is taken as EOF.
+/// This decoder instead buffers compressed data until a complete block is available, then
+/// decompresses it:
+/// - `WouldBlock` errors of the underlying reader are returned and `read` can be called again
+///   once the reader is ready, without losing data. `Interrupted` reads are retried.
+/// - A read returning `Ok(0)` is only taken as EOF at the end of a frame, where it can't be told
+///   apart from a transient empty read. Before the first frame and inside of a frame it's taken
+///   as no data being available yet, and `read` returns a `WouldBlock` error.
+///
+/// As a consequence, a stream which ends inside of a frame keeps returning `WouldBlock`. Use
+/// [`at_frame_end`](Self::at_frame_end) to tell a truncated stream apart, e.g. once the peer
+/// closed the connection.
+#[derive(Debug)]
+pub struct NonBlockingFrameDecoder<R> {
+    /// The underlying reader.
+    r: R,
+    /// Decoder over the compressed bytes read from `r` but not consumed yet.
+    decoder: FrameDecoder<io::Cursor<Vec<u8>>>,
+}
+
+impl<R: Read> FrameDecoder<R> {
+    /// Creates a new decoder for a non-blocking reader, which may return `WouldBlock` errors or
+    /// `Ok(0)` while no data is available. See [`NonBlockingFrameDecoder`].
+    pub fn new_non_blocking(rdr: R) -> NonBlockingFrameDecoder<R> {
+        NonBlockingFrameDecoder {
+            r: rdr,
+            decoder: FrameDecoder::new(io::Cursor::new(Vec::new())),
+        }
+    }
+}
+
+impl<R: Read> NonBlockingFrameDecoder<R> {
+    /// Returns whether the end of a frame has been reached and all of its decompressed data has
+    /// been read, with no compressed data of the next frame buffered.
+    pub fn at_frame_end(&self) -> bool {
+        self.decoder.at_frame_end() && self.decoder.buffered_len() == 0 && self.input().is_empty()
+    }
+
+    /// Gets a reference to the underlying reader in this decoder.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Gets a mutable reference to the underlying reader in this decoder.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.r
+    }
+
+    /// Consumes the decoder, returning the underlying reader.
+    ///
+    /// Compressed data which was read from the reader but not decompressed yet is lost.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// The compressed bytes read from the underlying reader but not consumed yet.
+    fn input(&self) -> &[u8] {
+        let cursor = self.decoder.get_ref();
+        &cursor.get_ref()[cursor.position() as usize..]
+    }
+
+    /// Returns whether the next `read` of `decoder` can complete without further input.
+    fn can_decode(&self) -> bool {
+        if self.decoder.buffered_len() != 0 {
+            return true;
+        }
+        let input = self.input();
+        matches!(self.decoder.next_read_len(input), Some(len) if len <= input.len())
+    }
+
+    /// Reads the next chunk of compressed data from the underlying reader. Returns the number of
+    /// bytes read.
+    fn fill(&mut self) -> io::Result<usize> {
+        let cursor = self.decoder.get_mut();
+        let consumed = cursor.position() as usize;
+        let buffer = cursor.get_mut();
+        buffer.drain(..consumed);
+        let len = buffer.len();
+        buffer.resize(len + READ_CHUNK_SIZE, 0);
+        let result = loop {
+            match self.r.read(&mut buffer[len..]) {
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        buffer.truncate(len + *result.as_ref().unwrap_or(&0));
+        cursor.set_position(0);
+        result
+    }
+}
+
+impl<R: Read> Read for NonBlockingFrameDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.can_decode() {
+            if self.fill()? == 0 {
+                if self.at_frame_end() {
+                    return Ok(0);
+                }
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+        self.decoder.read(buf)
+    }
+}
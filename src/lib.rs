@@ -61,11 +61,18 @@
 //! - `safe-encode` uses only safe rust for encode. _enabled by default_
 //! - `safe-decode` uses only safe rust for encode. _enabled by default_
 //! - `frame` support for LZ4 frame format. _implies `std`, enabled by default_
-//! - `std` enables dependency on the standard library. _enabled by default_
+//! - `std` enables dependency on the standard library. _implies `alloc`, enabled by default_
+//! - `alloc` enables the functions which allocate, e.g. return a `Vec`. _enabled by default_
+//! - `capi` exposes the block format as C functions, see [`capi`](capi/index.html). _implies `alloc`_
+//! - `profiling` adds `block::compress_with_analysis`, measuring where the time goes during
+//!   compression. _implies `std`_
 //!
-//! For maximum performance use `no-default-features`.
+//! For maximum performance use `no-default-features` with the `alloc` feature.
 //!
-//! For no_std support only the [`block format`](block/index.html) is supported.
+//! For no_std support only the [`block format`](block/index.html) is supported. Without `alloc`,
+//! only compression and decompression into caller-provided buffers is available, e.g. with
+//! [`compress_into`](block::compress_into) and [`decompress_into`](block::decompress_into), so no
+//! global allocator is required.
 //!
 //!
 #![deny(warnings)]
@@ -74,6 +81,7 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![cfg_attr(feature = "nightly", feature(optimize_attribute))]
 
+#[cfg(any(feature = "alloc", test))]
 #[cfg_attr(test, macro_use)]
 extern crate alloc;
 
@@ -82,6 +90,9 @@ extern crate alloc;
 extern crate more_asserts;
 
 pub mod block;
+#[cfg(feature = "capi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "capi")))]
+pub mod capi;
 #[cfg(feature = "frame")]
 #[cfg_attr(docsrs, doc(cfg(feature = "frame")))]
 pub mod frame;
@@ -93,16 +104,23 @@ mod fastcpy;
 #[allow(dead_code)]
 mod fastcpy_unsafe;
 
+#[cfg(feature = "alloc")]
 #[deprecated(
     since = "0.11.0",
     note = "This re-export is deprecated as it can be confused with the frame API and is not suitable for very large data, use block:: instead"
 )]
-pub use block::{compress, compress_into, compress_prepend_size};
+pub use block::{compress, compress_prepend_size};
 #[deprecated(
     since = "0.11.0",
     note = "This re-export is deprecated as it can be confused with the frame API and is not suitable for very large data, use block:: instead"
 )]
-pub use block::{decompress, decompress_into, decompress_size_prepended};
+pub use block::{compress_into, decompress_into};
+#[cfg(feature = "alloc")]
+#[deprecated(
+    since = "0.11.0",
+    note = "This re-export is deprecated as it can be confused with the frame API and is not suitable for very large data, use block:: instead"
+)]
+pub use block::{decompress, decompress_size_prepended};
 
 #[cfg_attr(
     all(feature = "safe-encode", feature = "safe-decode"),
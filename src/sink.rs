@@ -1,3 +1,4 @@
+#[cfg(feature = "alloc")]
 #[allow(unused_imports)]
 use alloc::vec::Vec;
 
@@ -9,13 +10,13 @@ use crate::fastcpy::slice_copy;
 /// when the `safe-decode` feature is enabled, or `VecSink` otherwise.
 /// The argument `pos` defines the initial output position in the Sink.
 #[inline]
-#[cfg(feature = "frame")]
+#[cfg(feature = "alloc")]
 pub fn vec_sink_for_compression(
     vec: &mut Vec<u8>,
     offset: usize,
     pos: usize,
     required_capacity: usize,
-) -> SliceSink {
+) -> SliceSink<'_> {
     {
         vec.resize(offset + required_capacity, 0);
         SliceSink::new(&mut vec[offset..], pos)
@@ -34,13 +35,15 @@ pub fn vec_sink_for_decompression(
     offset: usize,
     pos: usize,
     required_capacity: usize,
-) -> SliceSink {
+) -> SliceSink<'_> {
     {
         vec.resize(offset + required_capacity, 0);
         SliceSink::new(&mut vec[offset..], pos)
     }
 }
 
+// Without `alloc` there is no compressor, which uses some of the methods.
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
 pub trait Sink {
     /// Returns a raw ptr to the first unfilled byte of the Sink. Analogous to `[pos..].as_ptr()`.
     #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
@@ -64,7 +67,6 @@ pub trait Sink {
     #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
     unsafe fn set_pos(&mut self, new_pos: usize);
 
-    #[cfg(feature = "safe-decode")]
     fn extend_with_fill(&mut self, byte: u8, len: usize);
 
     /// Extends the Sink with `data`.
@@ -75,10 +77,8 @@ pub trait Sink {
     /// Copies `len` bytes starting from `start` to the end of the Sink.
     /// # Panics
     /// Panics if `start` >= `pos`.
-    #[cfg(feature = "safe-decode")]
     fn extend_from_within(&mut self, start: usize, wild_len: usize, copy_len: usize);
 
-    #[cfg(feature = "safe-decode")]
     fn extend_from_within_overlapping(&mut self, start: usize, num_bytes: usize);
 }
 
@@ -156,7 +156,6 @@ impl Sink for SliceSink<'_> {
     }
 
     #[inline]
-    #[cfg(feature = "safe-decode")]
     fn extend_with_fill(&mut self, byte: u8, len: usize) {
         self.output[self.pos..self.pos + len].fill(byte);
         self.pos += len;
@@ -179,14 +178,12 @@ impl Sink for SliceSink<'_> {
     /// # Panics
     /// Panics if `start` >= `pos`.
     #[inline]
-    #[cfg(feature = "safe-decode")]
     fn extend_from_within(&mut self, start: usize, wild_len: usize, copy_len: usize) {
         self.output.copy_within(start..start + wild_len, self.pos);
         self.pos += copy_len;
     }
 
     #[inline]
-    #[cfg(feature = "safe-decode")]
     #[cfg_attr(feature = "nightly", optimize(size))] // to avoid loop unrolling
     fn extend_from_within_overlapping(&mut self, start: usize, num_bytes: usize) {
         let offset = self.pos - start;
@@ -218,6 +215,7 @@ impl PtrSink {
     /// `pos` defines the initial output position in the Sink.
     /// # Panics
     /// Panics if `pos` is out of bounds.
+    #[cfg(feature = "alloc")]
     #[inline]
     pub fn from_vec(output: &mut Vec<u8>, pos: usize) -> Self {
         // SAFETY: Bytes behind pointer may be uninitialized.
@@ -227,6 +225,20 @@ impl PtrSink {
             cap: output.capacity(),
         }
     }
+
+    /// Creates a `Sink` backed by the spare capacity of `output`, i.e. the bytes after
+    /// `output.len()`, which may be uninitialized.
+    #[cfg(all(feature = "alloc", not(feature = "safe-decode")))]
+    #[inline]
+    pub fn from_vec_spare_capacity(output: &mut Vec<u8>) -> Self {
+        let len = output.len();
+        Self {
+            // SAFETY: `len` is at most the capacity, so the pointer stays within the allocation.
+            output: unsafe { output.as_mut_ptr().add(len) },
+            pos: 0,
+            cap: output.capacity() - len,
+        }
+    }
 }
 
 #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
@@ -277,7 +289,6 @@ impl Sink for PtrSink {
     }
 
     #[inline]
-    #[cfg(feature = "safe-decode")]
     fn extend_with_fill(&mut self, _byte: u8, _len: usize) {
         unreachable!();
     }
@@ -301,18 +312,388 @@ impl Sink for PtrSink {
     /// # Panics
     /// Panics if `start` >= `pos`.
     #[inline]
-    #[cfg(feature = "safe-decode")]
     fn extend_from_within(&mut self, _start: usize, _wild_len: usize, _copy_len: usize) {
         unreachable!();
     }
 
     #[inline]
-    #[cfg(feature = "safe-decode")]
     fn extend_from_within_overlapping(&mut self, _start: usize, _num_bytes: usize) {
         unreachable!();
     }
 }
 
+/// CountingSink discards all data written to it and only tracks the position, i.e. the number of
+/// bytes written. It's used to compute the compressed size without an output buffer.
+///
+/// Only the methods used by the safe compressor are supported.
+#[cfg(all(feature = "safe-encode", feature = "alloc"))]
+pub struct CountingSink {
+    /// Number of bytes written
+    pos: usize,
+}
+
+#[cfg(all(feature = "safe-encode", feature = "alloc"))]
+impl CountingSink {
+    /// Creates an empty `CountingSink`.
+    #[inline]
+    pub fn new() -> Self {
+        CountingSink { pos: 0 }
+    }
+}
+
+#[cfg(all(feature = "safe-encode", feature = "alloc"))]
+impl Sink for CountingSink {
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn pos_mut_ptr(&mut self) -> *mut u8 {
+        unreachable!();
+    }
+
+    fn byte_at(&mut self, _pos: usize) -> u8 {
+        unreachable!();
+    }
+
+    #[inline]
+    fn push(&mut self, _byte: u8) {
+        self.pos += 1;
+    }
+
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn base_mut_ptr(&mut self) -> *mut u8 {
+        unreachable!();
+    }
+
+    #[inline]
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        usize::MAX
+    }
+
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn set_pos(&mut self, _new_pos: usize) {
+        unreachable!();
+    }
+
+    fn extend_with_fill(&mut self, _byte: u8, _len: usize) {
+        unreachable!();
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        self.pos += data.len();
+    }
+
+    #[inline]
+    fn extend_from_slice_wild(&mut self, data: &[u8], copy_len: usize) {
+        assert!(copy_len <= data.len());
+        self.pos += copy_len;
+    }
+
+    fn extend_from_within(&mut self, _start: usize, _wild_len: usize, _copy_len: usize) {
+        unreachable!();
+    }
+
+    fn extend_from_within_overlapping(&mut self, _start: usize, _num_bytes: usize) {
+        unreachable!();
+    }
+}
+
+/// StridedSink is used as target to decompress data into rows of `row_len` bytes, which are
+/// `stride` bytes apart in `output`, e.g. a tile of a larger image.
+///
+/// Positions of the Sink are logical positions in the contiguous decompressed data, they are
+/// translated to the physical position in `output`. The bytes between rows are not touched.
+///
+/// Only the methods used by the safe decompressor are supported.
+pub struct StridedSink<'a> {
+    /// The output buffer, of which only the first `row_len` bytes of each row are written
+    output: &'a mut [u8],
+    /// Number of bytes written
+    pos: usize,
+    row_len: usize,
+    stride: usize,
+    /// Number of logical bytes which fit into `output`
+    cap: usize,
+}
+
+impl<'a> StridedSink<'a> {
+    /// Creates an empty `StridedSink` writing rows of `row_len` bytes every `stride` bytes into
+    /// `output`. The last row doesn't need to be padded to `stride`.
+    /// # Panics
+    /// Panics if `row_len` is 0 or larger than `stride`.
+    #[inline]
+    pub fn new(output: &'a mut [u8], row_len: usize, stride: usize) -> Self {
+        assert!(row_len > 0 && row_len <= stride);
+        let rows = match output.len().checked_sub(row_len) {
+            Some(len) => len / stride + 1,
+            None => 0,
+        };
+        StridedSink {
+            output,
+            pos: 0,
+            row_len,
+            stride,
+            cap: rows * row_len,
+        }
+    }
+
+    /// Translates a logical position to the position in `output`.
+    #[inline]
+    fn physical(&self, pos: usize) -> usize {
+        pos / self.row_len * self.stride + pos % self.row_len
+    }
+
+    /// Returns the number of bytes from the logical position `pos` to the end of its row.
+    #[inline]
+    fn row_remaining(&self, pos: usize) -> usize {
+        self.row_len - pos % self.row_len
+    }
+}
+
+impl Sink for StridedSink<'_> {
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn pos_mut_ptr(&mut self) -> *mut u8 {
+        unreachable!();
+    }
+
+    #[inline]
+    fn byte_at(&mut self, pos: usize) -> u8 {
+        self.output[self.physical(pos)]
+    }
+
+    #[cfg(feature = "safe-encode")]
+    fn push(&mut self, _byte: u8) {
+        unreachable!();
+    }
+
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn base_mut_ptr(&mut self) -> *mut u8 {
+        unreachable!();
+    }
+
+    #[inline]
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn set_pos(&mut self, _new_pos: usize) {
+        unreachable!();
+    }
+
+    fn extend_with_fill(&mut self, byte: u8, mut len: usize) {
+        assert!(self.pos + len <= self.cap);
+        while len > 0 {
+            let chunk_len = len.min(self.row_remaining(self.pos));
+            let start = self.physical(self.pos);
+            self.output[start..start + chunk_len].fill(byte);
+            self.pos += chunk_len;
+            len -= chunk_len;
+        }
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        self.extend_from_slice_wild(data, data.len())
+    }
+
+    /// Only `copy_len` bytes are copied, since the bytes after the current row may not belong to
+    /// the Sink.
+    fn extend_from_slice_wild(&mut self, data: &[u8], copy_len: usize) {
+        assert!(copy_len <= data.len());
+        assert!(self.pos + copy_len <= self.cap);
+        let mut data = &data[..copy_len];
+        while !data.is_empty() {
+            let chunk_len = data.len().min(self.row_remaining(self.pos));
+            let start = self.physical(self.pos);
+            self.output[start..start + chunk_len].copy_from_slice(&data[..chunk_len]);
+            self.pos += chunk_len;
+            data = &data[chunk_len..];
+        }
+    }
+
+    /// Copies `copy_len` bytes starting from `start` to the end of the Sink, see
+    /// `extend_from_slice_wild`.
+    fn extend_from_within(&mut self, mut start: usize, _wild_len: usize, copy_len: usize) {
+        assert!(start + copy_len <= self.pos);
+        assert!(self.pos + copy_len <= self.cap);
+        let end = self.pos + copy_len;
+        while self.pos < end {
+            let chunk_len = (end - self.pos)
+                .min(self.row_remaining(start))
+                .min(self.row_remaining(self.pos));
+            let src = self.physical(start);
+            let dst = self.physical(self.pos);
+            self.output.copy_within(src..src + chunk_len, dst);
+            start += chunk_len;
+            self.pos += chunk_len;
+        }
+    }
+
+    fn extend_from_within_overlapping(&mut self, start: usize, num_bytes: usize) {
+        assert!(self.pos + num_bytes <= self.cap);
+        let offset = self.pos - start;
+        for pos in self.pos..self.pos + num_bytes {
+            let byte = self.output[self.physical(pos - offset)];
+            let dst = self.physical(pos);
+            self.output[dst] = byte;
+        }
+        self.pos += num_bytes;
+    }
+}
+
+/// Number of decompressed bytes after which `ChecksumSink` hashes the new output, small enough
+/// to still be in the CPU cache.
+#[cfg(feature = "frame")]
+const CHECKSUM_CHUNK_SIZE: usize = 4 * 1024;
+
+/// ChecksumSink wraps a `SliceSink` and computes the xxhash32, or the hash of another `Hasher`,
+/// of the data written to it while decompressing, so verifying a checksum doesn't need a second
+/// pass over the output.
+///
+/// The output is hashed in chunks as it's written. Bytes before `pos` are final, wild copies only
+/// write after it.
+#[cfg(feature = "frame")]
+pub struct ChecksumSink<'a, H: core::hash::Hasher = twox_hash::XxHash32> {
+    inner: SliceSink<'a>,
+    hasher: H,
+    /// Number of bytes in start of the output already hashed
+    hashed: usize,
+}
+
+#[cfg(feature = "frame")]
+impl<'a> ChecksumSink<'a> {
+    /// Creates a `ChecksumSink` hashing the data written to `inner` after its current position.
+    #[inline]
+    pub fn new(inner: SliceSink<'a>) -> Self {
+        Self::with_hasher(inner, twox_hash::XxHash32::with_seed(0))
+    }
+
+    /// Returns the xxhash32 of the data written to the Sink.
+    #[inline]
+    pub fn finish(self) -> u32 {
+        use core::hash::Hasher;
+        self.into_hasher().finish() as u32
+    }
+}
+
+#[cfg(feature = "frame")]
+impl<'a, H: core::hash::Hasher> ChecksumSink<'a, H> {
+    /// Creates a `ChecksumSink` writing the data written to `inner` after its current position to
+    /// `hasher`. `hasher` may be a `&mut` reference, to hash the data of several blocks.
+    #[inline]
+    pub fn with_hasher(inner: SliceSink<'a>, hasher: H) -> Self {
+        let hashed = inner.pos();
+        ChecksumSink {
+            inner,
+            hasher,
+            hashed,
+        }
+    }
+
+    /// Returns the hasher, after writing all data written to the Sink to it.
+    #[inline]
+    pub fn into_hasher(mut self) -> H {
+        self.hash_up_to_pos();
+        self.hasher
+    }
+
+    #[inline]
+    fn hash_up_to_pos(&mut self) {
+        let pos = self.inner.pos;
+        self.hasher.write(&self.inner.output[self.hashed..pos]);
+        self.hashed = pos;
+    }
+
+    #[inline]
+    fn written(&mut self) {
+        if self.inner.pos - self.hashed >= CHECKSUM_CHUNK_SIZE {
+            self.hash_up_to_pos();
+        }
+    }
+}
+
+#[cfg(feature = "frame")]
+impl<H: core::hash::Hasher> Sink for ChecksumSink<'_, H> {
+    #[inline]
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn pos_mut_ptr(&mut self) -> *mut u8 {
+        self.inner.pos_mut_ptr()
+    }
+
+    #[inline]
+    fn byte_at(&mut self, pos: usize) -> u8 {
+        self.inner.byte_at(pos)
+    }
+
+    #[inline]
+    #[cfg(feature = "safe-encode")]
+    fn push(&mut self, byte: u8) {
+        self.inner.push(byte);
+        self.written();
+    }
+
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    unsafe fn base_mut_ptr(&mut self) -> *mut u8 {
+        self.inner.base_mut_ptr()
+    }
+
+    #[inline]
+    fn pos(&self) -> usize {
+        self.inner.pos()
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    #[cfg(not(all(feature = "safe-encode", feature = "safe-decode")))]
+    #[inline]
+    unsafe fn set_pos(&mut self, new_pos: usize) {
+        self.inner.set_pos(new_pos);
+        self.written();
+    }
+
+    #[inline]
+    fn extend_with_fill(&mut self, byte: u8, len: usize) {
+        self.inner.extend_with_fill(byte, len);
+        self.written();
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        self.inner.extend_from_slice(data);
+        self.written();
+    }
+
+    #[inline]
+    fn extend_from_slice_wild(&mut self, data: &[u8], copy_len: usize) {
+        self.inner.extend_from_slice_wild(data, copy_len);
+        self.written();
+    }
+
+    #[inline]
+    fn extend_from_within(&mut self, start: usize, wild_len: usize, copy_len: usize) {
+        self.inner.extend_from_within(start, wild_len, copy_len);
+        self.written();
+    }
+
+    #[inline]
+    fn extend_from_within_overlapping(&mut self, start: usize, num_bytes: usize) {
+        self.inner.extend_from_within_overlapping(start, num_bytes);
+        self.written();
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
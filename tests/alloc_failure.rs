@@ -0,0 +1,141 @@
+//! Tests for the fallible allocation variants, using an allocator which fails allocations larger
+//! than a configurable limit and tracks the number of allocated bytes.
+#![cfg(feature = "alloc")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use lz4_flex::block::{CompressError, DecompressError};
+
+struct LimitedAlloc;
+
+static ALLOC_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Number of bytes currently allocated.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+/// Serializes the tests, which depend on the global state of the allocator.
+static LOCK: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for LimitedAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() > ALLOC_LIMIT.load(Ordering::SeqCst) {
+            return std::ptr::null_mut();
+        }
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size > ALLOC_LIMIT.load(Ordering::SeqCst) {
+            return std::ptr::null_mut();
+        }
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+            ALLOCATED.fetch_add(new_size, Ordering::SeqCst);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: LimitedAlloc = LimitedAlloc;
+
+#[test]
+fn allocation_failure_is_reported() {
+    let _lock = LOCK.lock().unwrap();
+    let input = include_bytes!("../benches/compression_34k.txt").repeat(30);
+    let compressed = lz4_flex::block::try_compress(&input).unwrap();
+    assert_eq!(
+        lz4_flex::block::try_decompress(&compressed, input.len()).unwrap(),
+        input
+    );
+
+    ALLOC_LIMIT.store(256 * 1024, Ordering::SeqCst);
+    let compress_result = lz4_flex::block::try_compress(&input);
+    let decompress_result = lz4_flex::block::try_decompress(&compressed, input.len());
+    let small_result = lz4_flex::block::try_decompress(&lz4_flex::compress(b"small"), 5);
+    // the output fits, but the 8KB hashtable doesn't
+    ALLOC_LIMIT.store(5 * 1024, Ordering::SeqCst);
+    let table_result = lz4_flex::block::try_compress(&input[..4000]);
+    ALLOC_LIMIT.store(usize::MAX, Ordering::SeqCst);
+
+    assert!(matches!(
+        compress_result,
+        Err(CompressError::AllocationFailed)
+    ));
+    assert!(matches!(
+        decompress_result,
+        Err(DecompressError::AllocationFailed)
+    ));
+    assert_eq!(small_result.unwrap(), b"small");
+    assert!(matches!(table_result, Err(CompressError::AllocationFailed)));
+
+    // sizes beyond what can be allocated at all
+    assert!(matches!(
+        lz4_flex::block::try_decompress(&compressed, usize::MAX),
+        Err(DecompressError::AllocationFailed)
+    ));
+}
+
+#[test]
+#[cfg(feature = "frame")]
+fn frame_encoder_capacity_limit() {
+    use lz4_flex::frame::{BlockMode, BlockSize, Error, FrameDecoder, FrameEncoder, FrameInfo};
+    use std::io::{Read, Write};
+
+    let _lock = LOCK.lock().unwrap();
+    let input = include_bytes!("../benches/compression_34k.txt").repeat(30);
+    let frame_info = FrameInfo::new()
+        .block_size(BlockSize::Max4MB)
+        .block_mode(BlockMode::Linked);
+    const ENCODERS: usize = 16;
+    const LIMIT: usize = 576 * 1024;
+
+    let outputs: Vec<Vec<u8>> = (0..ENCODERS)
+        .map(|_| Vec::with_capacity(input.len()))
+        .collect();
+
+    // Without the limit, each encoder would buffer over 8MB, which the allocator rejects.
+    ALLOC_LIMIT.store(LIMIT + 64 * 1024, Ordering::SeqCst);
+    let allocated_before = ALLOCATED.load(Ordering::SeqCst);
+    let mut encoders: Vec<_> = outputs
+        .into_iter()
+        .map(|output| FrameEncoder::with_capacity_limit(frame_info.clone(), output, LIMIT).unwrap())
+        .collect();
+    for encoder in &mut encoders {
+        encoder.write_all(&input).unwrap();
+    }
+    let allocated = ALLOCATED.load(Ordering::SeqCst) - allocated_before;
+    ALLOC_LIMIT.store(usize::MAX, Ordering::SeqCst);
+    // The buffer of uncompressed data, the output for the compressed blocks and the hash table.
+    let per_encoder = LIMIT + 384 * 1024;
+    assert!(
+        allocated <= ENCODERS * per_encoder,
+        "{allocated} bytes allocated"
+    );
+
+    for encoder in encoders {
+        let compressed = encoder.finish().unwrap();
+        let mut decoder = FrameDecoder::new(&compressed[..]);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, input);
+        assert_eq!(decoder.frame_info().unwrap().block_size, BlockSize::Max4MB);
+    }
+
+    assert!(matches!(
+        FrameEncoder::with_capacity_limit(frame_info, Vec::new(), 128 * 1024),
+        Err(Error::CapacityLimitTooSmall { needed }) if needed == 192 * 1024
+    ));
+    let independent = FrameInfo::new().block_size(BlockSize::Max4MB);
+    assert!(FrameEncoder::with_capacity_limit(independent, Vec::new(), 128 * 1024).is_ok());
+}
@@ -0,0 +1,68 @@
+//! Tests for the functions which only use caller-provided buffers, so they are available without
+//! the `alloc` feature. CI runs them with `--no-default-features`.
+
+use lz4_flex::block::{
+    compress_into_stack, decompress_into, get_maximum_output_size, roundtrip_in_place,
+    CompressError, DecompressError,
+};
+
+const COMPRESSION1K: &[u8] = include_bytes!("../benches/compression_1k.txt");
+const COMPRESSION66JSON: &[u8] = include_bytes!("../benches/compression_66k_JSON.txt");
+
+#[test]
+fn compress_into_stack_roundtrip() {
+    let (compressed, len) =
+        compress_into_stack::<{ get_maximum_output_size(1024) }>(COMPRESSION1K).unwrap();
+    let mut uncompressed = [0u8; 1024];
+    let uncompressed_len = decompress_into(&compressed[..len], &mut uncompressed).unwrap();
+    assert_eq!(&uncompressed[..uncompressed_len], COMPRESSION1K);
+
+    // Larger inputs use the table with 32 bit positions
+    let (compressed, len) =
+        compress_into_stack::<{ get_maximum_output_size(70_000) }>(COMPRESSION66JSON).unwrap();
+    let mut uncompressed = [0u8; 70_000];
+    let uncompressed_len = decompress_into(&compressed[..len], &mut uncompressed).unwrap();
+    assert_eq!(&uncompressed[..uncompressed_len], COMPRESSION66JSON);
+
+    assert!(matches!(
+        compress_into_stack::<64>(COMPRESSION1K),
+        Err(CompressError::OutputTooSmall { provided: 64, .. })
+    ));
+}
+
+#[test]
+fn roundtrip_in_place_without_alloc() {
+    let mut scratch = [0u8; get_maximum_output_size(1024) + 1024];
+    assert!(roundtrip_in_place(&mut scratch, COMPRESSION1K).unwrap());
+    assert!(roundtrip_in_place(&mut scratch, b"").unwrap());
+    assert!(matches!(
+        roundtrip_in_place(&mut scratch[..1024], COMPRESSION1K),
+        Err(DecompressError::OutputTooSmall { .. })
+    ));
+}
+
+#[test]
+fn decompress_linked_blocks_fixed_buffers() {
+    use lz4_flex::block::{
+        compress_into, compress_into_with_dict, decompress_block_linked, decompress_into_fixed,
+    };
+    let block1: &[u8] = b"The quick brown fox jumps over the lazy dog. ";
+    let block2: &[u8] = b"The quick brown fox jumps over the lazy cat. The lazy dog sleeps.";
+
+    let mut compressed1 = [0u8; get_maximum_output_size(128)];
+    let mut compressed2 = [0u8; get_maximum_output_size(128)];
+    let len1 = compress_into(block1, &mut compressed1).unwrap();
+    let len2 = compress_into_with_dict(block2, &mut compressed2, block1).unwrap();
+
+    // Decode with two stack buffers: the previous block's output serves as window.
+    let mut window = [0u8; 64];
+    let mut output = [0u8; 128];
+    let window_len = decompress_into_fixed(&compressed1[..len1], &mut window).unwrap();
+    assert_eq!(&window[..window_len], block1);
+    let output_len =
+        decompress_block_linked(&compressed2[..len2], &mut output, &window[..window_len]).unwrap();
+    assert_eq!(&output[..output_len], block2);
+
+    // Without the window the second block can't be decoded.
+    assert!(decompress_into_fixed(&compressed2[..len2], &mut output).is_err());
+}
@@ -3,8 +3,6 @@
 #[macro_use]
 extern crate more_asserts;
 
-use std::iter;
-
 use lz4_compress::compress as lz4_rust_compress;
 #[cfg(feature = "frame")]
 use lz4_flex::frame::BlockMode;
@@ -159,8 +157,7 @@ fn compare_compression() {
 fn test_minimum_compression_ratio_block() {
     let compressed = compress_block(COMPRESSION34K);
     let ratio = compressed.len() as f64 / COMPRESSION34K.len() as f64;
-    assert_lt!(ratio, 0.585); // TODO check why compression is not deterministic (fails in ci for
-                              // 0.58)
+    assert_lt!(ratio, 0.58);
     let compressed = compress_block(COMPRESSION65);
     let ratio = compressed.len() as f64 / COMPRESSION65.len() as f64;
     assert_lt!(ratio, 0.574);
@@ -170,6 +167,58 @@ fn test_minimum_compression_ratio_block() {
     assert_lt!(ratio, 0.229);
 }
 
+#[test]
+fn compress_deterministic() {
+    use lz4_flex::block::compress_deterministic;
+
+    /// FNV-1a, to pin the output without depending on the hasher of std.
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    for input in [COMPRESSION1K, COMPRESSION34K, COMPRESSION66JSON] {
+        let compressed = compress_deterministic(input);
+        for _ in 0..20 {
+            assert_eq!(compress_deterministic(input), compressed);
+        }
+        assert_eq!(compressed, compress_block(input));
+    }
+
+    // The output must not change between versions and targets.
+    let pinned = [
+        (COMPRESSION1K, 558, 0xd9f1_95d5_4185_6613),
+        (COMPRESSION34K, 19888, 0xbd88_39dc_41ef_2119),
+        (COMPRESSION66JSON, 15268, 0x440a_c7d9_5501_593d),
+    ];
+    for (input, len, hash) in pinned {
+        let compressed = compress_deterministic(input);
+        assert_eq!((compressed.len(), fnv1a(&compressed)), (len, hash));
+    }
+}
+
+#[test]
+fn content_fingerprint() {
+    use lz4_flex::block::{compress_deterministic, compressed_eq, content_fingerprint};
+    // FNV-1a test vectors
+    assert_eq!(content_fingerprint(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(content_fingerprint(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+    let chunks = [COMPRESSION1K, COMPRESSION34K, COMPRESSION1K];
+    let compressed: Vec<_> = chunks.iter().map(|c| compress_deterministic(c)).collect();
+    assert!(compressed_eq(&compressed[0], &compressed[2]));
+    assert_eq!(
+        content_fingerprint(&compressed[0]),
+        content_fingerprint(&compressed[2])
+    );
+    assert!(!compressed_eq(&compressed[0], &compressed[1]));
+    assert_ne!(
+        content_fingerprint(&compressed[0]),
+        content_fingerprint(&compressed[1])
+    );
+}
+
 #[cfg(feature = "frame")]
 #[test]
 fn test_minimum_compression_ratio_frame() {
@@ -350,6 +399,691 @@ mod checked_decode {
     }
 }
 
+#[test]
+fn decompress_streaming() {
+    use lz4_flex::block::decompress_streaming;
+    let mut zeros_and_text = vec![0u8; 300_000];
+    zeros_and_text.extend_from_slice(COMPRESSION66JSON);
+    let inputs: [&[u8]; 5] = [
+        b"",
+        COMPRESSION1K,
+        COMPRESSION66JSON,
+        &COMPRESSION10MB[..1_000_000],
+        &zeros_and_text,
+    ];
+    for input in inputs {
+        let compressed = compress_block(input);
+        let mut uncompressed = Vec::new();
+        decompress_streaming(&compressed, input.len(), |chunk| {
+            assert!(!chunk.is_empty() && chunk.len() <= 128 * 1024);
+            uncompressed.extend_from_slice(chunk);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(uncompressed, decompress(&compressed, input.len()).unwrap());
+    }
+
+    let compressed = compress_block(COMPRESSION10MB);
+    let err = decompress_streaming(&compressed, COMPRESSION10MB.len(), |_| {
+        Err(std::io::Error::other("sink failed"))
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "sink failed");
+
+    let too_small = decompress_streaming(&compressed, 1000, |_| Ok(())).unwrap_err();
+    assert_eq!(too_small.kind(), std::io::ErrorKind::InvalidData);
+    let truncated = decompress_streaming(&compressed[..1000], usize::MAX, |_| Ok(()));
+    assert_eq!(
+        truncated.unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn verify_roundtrip() {
+    for input in [
+        b"" as &[u8],
+        b"a",
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+    ] {
+        lz4_flex::block::verify_roundtrip(input).unwrap();
+    }
+}
+
+#[test]
+fn roundtrip_in_place() {
+    use lz4_flex::block::{get_maximum_output_size, roundtrip_in_place, DecompressError};
+    let mut scratch = vec![0xAA; 200_000];
+    for input in [
+        b"" as &[u8],
+        b"a",
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+    ] {
+        let needed = get_maximum_output_size(input.len()) + input.len();
+        assert!(roundtrip_in_place(&mut scratch[..needed], input).unwrap());
+        assert!(roundtrip_in_place(&mut scratch, input).unwrap());
+        assert!(matches!(
+            roundtrip_in_place(&mut scratch[..needed - 1], input),
+            Err(DecompressError::OutputTooSmall { expected, .. }) if expected == needed
+        ));
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn max_expansion() {
+    use lz4_flex::block::max_expansion;
+    // xorshift, incompressible
+    let mut state = 0x1234_5678u32;
+    let random: Vec<u8> = (0..1_000_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    for len in (0..300).chain([4096, 65_535, 65_536, 1_000_000]) {
+        let input = &random[..len];
+        let compressed = compress_block(input);
+        assert!(compressed.len() <= len + max_expansion(len), "len {len}");
+    }
+    // incompressible data is stored as a single literal run
+    assert_eq!(
+        compress_block(&random).len(),
+        random.len() + 2 + (random.len() - 15) / 255
+    );
+}
+
+#[test]
+fn compress_tagged() {
+    use lz4_flex::block::{
+        compress_tagged, compress_tagged_with, decompress_tagged, tagged_uncompressed_size,
+        DecompressError, SizeTag,
+    };
+    let compressed = compress_tagged(COMPRESSION34K);
+    assert_eq!(compressed[0], 0);
+    assert_eq!(&compressed[1..], compress_prepend_size(COMPRESSION34K));
+    assert_eq!(decompress_tagged(&compressed).unwrap(), COMPRESSION34K);
+
+    for (tag, header_len) in [(SizeTag::U32, 5), (SizeTag::U64, 9), (SizeTag::Varint, 4)] {
+        for input in [COMPRESSION34K, b"", b"short"] {
+            let compressed = compress_tagged_with(input, tag);
+            assert_eq!(compressed[0], tag as u8);
+            let (read_tag, size, rest) = tagged_uncompressed_size(&compressed).unwrap();
+            assert_eq!((read_tag, size), (tag, input.len()));
+            if input == COMPRESSION34K {
+                assert_eq!(rest.len(), compressed.len() - header_len);
+            }
+            assert_eq!(decompress_tagged(&compressed).unwrap(), input);
+        }
+    }
+
+    // varint sizes around the byte boundaries
+    for (size, encoded) in [
+        (0, &[0x00][..]),
+        (0x7F, &[0x7F]),
+        (0x80, &[0x80, 0x01]),
+        (0x3FFF, &[0xFF, 0x7F]),
+        (0x4000, &[0x80, 0x80, 0x01]),
+        (u32::MAX as usize, &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+    ] {
+        let mut input = vec![SizeTag::Varint as u8];
+        input.extend_from_slice(encoded);
+        input.push(0xAB);
+        let (tag, read_size, rest) = tagged_uncompressed_size(&input).unwrap();
+        assert_eq!((tag, read_size, rest), (SizeTag::Varint, size, &[0xAB][..]));
+    }
+    let mut overflow = vec![SizeTag::Varint as u8];
+    overflow.extend_from_slice(&[0xFF; 10]);
+    overflow.push(0x01);
+    assert!(matches!(
+        tagged_uncompressed_size(&overflow),
+        Err(DecompressError::InvalidSize)
+    ));
+
+    // Sizes larger than the block can decompress to aren't allocated
+    let mut huge_u64 = vec![SizeTag::U64 as u8];
+    huge_u64.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+    huge_u64.extend_from_slice(&[0x10, b'a']);
+    let mut huge_varint = vec![SizeTag::Varint as u8];
+    huge_varint.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x10, b'a']);
+    for huge in [huge_u64, huge_varint] {
+        assert!(matches!(
+            decompress_tagged(&huge),
+            Err(DecompressError::InvalidSize)
+        ));
+    }
+    let zeros = vec![0u8; 1024 * 1024];
+    assert_eq!(
+        decompress_tagged(&compress_tagged_with(&zeros, SizeTag::Varint)).unwrap(),
+        zeros
+    );
+
+    let mut compressed = compress_tagged(COMPRESSION1K);
+    compressed[0] = 3;
+    assert!(matches!(
+        decompress_tagged(&compressed),
+        Err(DecompressError::UnknownTag { tag: 3 })
+    ));
+    assert!(matches!(
+        decompress_tagged(&[]),
+        Err(DecompressError::ExpectedAnotherByte)
+    ));
+    assert!(matches!(
+        decompress_tagged(&[SizeTag::Varint as u8, 0x80]),
+        Err(DecompressError::ExpectedAnotherByte)
+    ));
+}
+
+#[test]
+fn size_prepended_mismatch() {
+    use lz4_flex::block::DecompressError;
+    let mut compressed = compress_prepend_size(COMPRESSION1K);
+    let size = COMPRESSION1K.len() as u32;
+
+    compressed[..4].copy_from_slice(&(size + 10).to_le_bytes());
+    assert!(matches!(
+        decompress_size_prepended(&compressed),
+        Err(DecompressError::UncompressedSizeDiffers { expected, actual })
+            if expected == size as usize + 10 && actual == size as usize
+    ));
+    assert!(matches!(
+        decompress_size_prepended_with_dict(&compressed, b""),
+        Err(DecompressError::UncompressedSizeDiffers { .. })
+    ));
+
+    #[cfg(any(feature = "safe-decode", feature = "checked-decode"))]
+    {
+        compressed[..4].copy_from_slice(&(size - 10).to_le_bytes());
+        assert!(matches!(
+            decompress_size_prepended(&compressed),
+            Err(DecompressError::OutputTooSmall { .. })
+        ));
+    }
+}
+
+#[test]
+fn decompress_grow() {
+    use lz4_flex::block::{decompress_grow, DecompressError};
+    for input in [
+        b"" as &[u8],
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+        &[0u8; 100_000],
+    ] {
+        let compressed = compress_block(input);
+        for size_hint in [0, 1, input.len() / 3, input.len(), input.len() * 2] {
+            let uncompressed = decompress_grow(&compressed, size_hint).unwrap();
+            assert_eq!(uncompressed, input);
+        }
+    }
+    // other errors are passed through
+    let compressed = compress_block(COMPRESSION1K);
+    assert!(matches!(
+        decompress_grow(&compressed[..compressed.len() - 1], 10),
+        Err(DecompressError::LiteralOutOfBounds)
+    ));
+}
+
+#[cfg(feature = "frame")]
+#[test]
+fn decompress_into_with_checksum() {
+    use lz4_flex::block::decompress_into_with_checksum;
+    use std::hash::Hasher;
+    for input in [
+        b"" as &[u8],
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+        &[7u8; 100_000],
+    ] {
+        let compressed = compress_block(input);
+        let mut output = vec![0u8; input.len() + 100];
+        let (len, checksum) = decompress_into_with_checksum(&compressed, &mut output).unwrap();
+        assert_eq!(&output[..len], input);
+        let mut hasher = twox_hash::XxHash32::with_seed(0);
+        hasher.write(input);
+        assert_eq!(checksum, hasher.finish() as u32);
+    }
+}
+
+#[test]
+fn is_likely_compressed() {
+    use lz4_flex::block::is_likely_compressed;
+    let mut state = 0x1234_5678u32;
+    let random: Vec<u8> = (0..10_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let inputs: [&[u8]; 8] = [
+        b"",
+        b"hello",
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+        &COMPRESSION10MB[..200_000],
+        &[0u8; 10_000],
+        &random,
+    ];
+    for input in inputs {
+        assert!(is_likely_compressed(&compress_block(input)));
+        if !input.is_empty() {
+            assert!(!is_likely_compressed(input));
+        }
+    }
+    // raw data at different positions
+    let misdetected = (0..1000)
+        .filter(|&i| is_likely_compressed(&COMPRESSION10MB[i * 1000..i * 1000 + 500]))
+        .count()
+        + random
+            .chunks(100)
+            .filter(|chunk| is_likely_compressed(chunk))
+            .count();
+    assert_eq!(misdetected, 0);
+}
+
+#[test]
+fn decompress_with_stats() {
+    use lz4_flex::block::{decompress_with_stats, DecodeStats};
+    let inputs: [&[u8]; 5] = [
+        b"hello",
+        COMPRESSION1K,
+        COMPRESSION66JSON,
+        &COMPRESSION10MB[..200_000],
+        &[0u8; 10_000],
+    ];
+    for input in inputs {
+        let compressed = compress_block(input);
+        let (decompressed, stats) = decompress_with_stats(&compressed, input.len()).unwrap();
+        assert_eq!(decompressed, input);
+        assert_eq!(stats.literal_bytes + stats.match_bytes, input.len());
+        assert_eq!(stats.literal_lengths.iter().sum::<usize>(), stats.sequences);
+        assert_eq!(stats.matches() + 1, stats.sequences);
+        assert_eq!(
+            stats.short_offsets.iter().sum::<usize>() + stats.other_offsets,
+            stats.matches()
+        );
+    }
+
+    // a single literal followed by a long match with offset 1 and the last literals
+    let (_, stats) = decompress_with_stats(&compress_block(&[0u8; 10_000]), 10_000).unwrap();
+    let mut expected = DecodeStats {
+        sequences: 2,
+        literal_bytes: 1 + 6,
+        match_bytes: 10_000 - 7,
+        ..Default::default()
+    };
+    expected.literal_lengths[1] = 1;
+    expected.literal_lengths[3] = 1;
+    expected.match_lengths[7] = 1;
+    expected.short_offsets[0] = 1;
+    assert_eq!(stats, expected);
+}
+
+#[test]
+fn disassemble() {
+    use lz4_flex::block::{disassemble, DecompressError, Token};
+    let mut block = vec![0xFF, 5];
+    block.extend_from_slice(&[b'x'; 20]);
+    // Offset 1, match length 4 + 15 + 255 + 3.
+    block.extend_from_slice(&[1, 0, 0xFF, 3]);
+    block.push(0x50);
+    block.extend_from_slice(b"tail!");
+    assert_eq!(
+        disassemble(&block).unwrap(),
+        [
+            Token::Literals(20),
+            Token::Match {
+                offset: 1,
+                length: 277
+            },
+            Token::Literals(5),
+        ]
+    );
+    assert_eq!(decompress(&block, 302).unwrap().len(), 302);
+
+    assert_eq!(disassemble(&[0]).unwrap(), []);
+    assert!(matches!(
+        disassemble(&block[..24]),
+        Err(DecompressError::ExpectedAnotherByte)
+    ));
+    assert!(matches!(
+        disassemble(&block[..10]),
+        Err(DecompressError::LiteralOutOfBounds)
+    ));
+    block[22] = 0;
+    assert!(matches!(
+        disassemble(&block),
+        Err(DecompressError::OffsetOutOfBounds)
+    ));
+
+    let tokens = disassemble(&compress_block(COMPRESSION34K)).unwrap();
+    let len: usize = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literals(len) => *len,
+            Token::Match { length, .. } => *length,
+        })
+        .sum();
+    assert_eq!(len, COMPRESSION34K.len());
+}
+
+#[test]
+fn block_header() {
+    use lz4_flex::block::{BlockHeader, DecompressError};
+    // 300 literals: 15 in the token, 255 + 30 in two length bytes
+    let mut block = vec![0xF0, 255, 30];
+    block.extend_from_slice(&[b'x'; 300]);
+    let header = BlockHeader::try_from(&block[..]).unwrap();
+    assert_eq!(
+        header,
+        BlockHeader {
+            token: 0xF0,
+            literal_length: 300,
+            literals_start: 3,
+        }
+    );
+    assert_eq!(decompress(&block, 300).unwrap(), [b'x'; 300]);
+    // The literals don't need to be present
+    assert_eq!(BlockHeader::try_from(&block[..3]).unwrap(), header);
+    assert!(matches!(
+        BlockHeader::try_from(&block[..2]),
+        Err(DecompressError::ExpectedAnotherByte)
+    ));
+    assert!(BlockHeader::try_from(&[][..]).is_err());
+
+    // A compressed block starts with literals up to the first repetition
+    let compressed = compress_block(b"0123456789abcdef 0123456789abcdef");
+    let header = BlockHeader::try_from(&compressed[..]).unwrap();
+    assert_eq!(header.literal_length, 17);
+    assert_eq!(
+        &compressed[header.literals_start..][..header.literal_length],
+        b"0123456789abcdef "
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn validate() {
+    use lz4_flex::block::{validate, DecompressError};
+    // Compares `validate` with `decompress`. Invalid offsets may only be detected by `validate`,
+    // in which case the decoder continues, and may fail later or not at all.
+    let check = |block: &[u8], expected_size: usize| match (
+        validate(block, expected_size),
+        decompress(block, expected_size),
+    ) {
+        (Ok(()), Ok(_)) | (Err(DecompressError::OffsetOutOfBounds), _) => {}
+        #[cfg(feature = "safe-decode")]
+        (Err(validated), Err(err)) => {
+            assert_eq!(format!("{validated:?}"), format!("{err:?}"))
+        }
+        #[cfg(not(feature = "safe-decode"))]
+        (Err(_), Err(_)) => {}
+        (validated, decompressed) => panic!("{validated:?} {:?}", decompressed.map(|_| ())),
+    };
+
+    for input in [COMPRESSION1K, COMPRESSION34K, COMPRESSION66JSON, b""] {
+        let compressed = compress_block(input);
+        validate(&compressed, input.len()).unwrap();
+        check(&compressed, input.len());
+        check(&compressed, input.len().saturating_sub(1));
+        check(&compressed, input.len() / 2);
+        let step = compressed.len() / 300 + 1;
+        for len in (0..compressed.len()).step_by(step) {
+            check(&compressed[..len], input.len());
+        }
+        let mut corrupted = compressed.clone();
+        for pos in (0..compressed.len()).step_by(step) {
+            corrupted[pos] ^= 0x5A;
+            check(&corrupted, input.len());
+            corrupted[pos] = compressed[pos];
+        }
+    }
+
+    // Offset 0, and an offset before the start of the output.
+    let mut block = vec![0x40, b'a', b'b', b'c', b'd', 0, 0, 0x50];
+    block.extend_from_slice(b"tail!");
+    assert!(matches!(
+        validate(&block, 100),
+        Err(DecompressError::OffsetOutOfBounds)
+    ));
+    block[5] = 5;
+    assert!(matches!(
+        validate(&block, 100),
+        Err(DecompressError::OffsetOutOfBounds)
+    ));
+    block[5] = 4;
+    validate(&block, 13).unwrap();
+    assert!(matches!(
+        validate(&block, 12),
+        Err(DecompressError::OutputTooSmall {
+            expected: 13,
+            actual: 12
+        })
+    ));
+}
+
+#[test]
+fn decompress_checked_unchecked() {
+    use lz4_flex::block::decompress_checked;
+    for input in [COMPRESSION1K, COMPRESSION34K, COMPRESSION66JSON, b""] {
+        let compressed = compress_block(input);
+        let checked = decompress_checked(&compressed, input.len()).unwrap();
+        assert_eq!(checked, input);
+        #[cfg(not(feature = "safe-decode"))]
+        assert_eq!(
+            lz4_flex::block::decompress_unchecked(&compressed, input.len()).unwrap(),
+            checked
+        );
+    }
+    assert!(decompress_checked(&compress_block(COMPRESSION1K), 100).is_err());
+}
+
+#[test]
+fn decompress_range() {
+    use lz4_flex::block::{decompress_range, DecompressError};
+    let compressed = compress_block(COMPRESSION1K);
+    let mut buffer = b"header".to_vec();
+    buffer.extend_from_slice(&compressed);
+    buffer.extend_from_slice(b"trailer");
+    let range = 6..6 + compressed.len();
+    assert_eq!(
+        decompress_range(&buffer, range, COMPRESSION1K.len()).unwrap(),
+        COMPRESSION1K
+    );
+
+    let len = buffer.len();
+    assert!(matches!(
+        decompress_range(&buffer, 6..len + 1, COMPRESSION1K.len()),
+        Err(DecompressError::RangeOutOfBounds { start: 6, end, len: buffer_len })
+            if end == len + 1 && buffer_len == len
+    ));
+    assert!(matches!(
+        decompress_range(
+            &buffer,
+            std::ops::Range { start: 10, end: 6 },
+            COMPRESSION1K.len()
+        ),
+        Err(DecompressError::RangeOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn decompress_append() {
+    use lz4_flex::block::decompress_append;
+    let mut out = b"prefix".to_vec();
+    let first = compress_block(COMPRESSION1K);
+    let second = compress_block(COMPRESSION34K);
+    assert_eq!(
+        decompress_append(&first, COMPRESSION1K.len(), &mut out).unwrap(),
+        COMPRESSION1K.len()
+    );
+    // a larger size only reserves more
+    assert_eq!(
+        decompress_append(&second, COMPRESSION34K.len() + 100, &mut out).unwrap(),
+        COMPRESSION34K.len()
+    );
+    assert_eq!(out, [b"prefix", COMPRESSION1K, COMPRESSION34K].concat());
+
+    // a corrupted block leaves the output as it was
+    let len = out.len();
+    assert!(
+        decompress_append(&second[..second.len() - 10], COMPRESSION34K.len(), &mut out).is_err()
+    );
+    assert_eq!(out.len(), len);
+}
+
+#[test]
+fn decompress_exact() {
+    use lz4_flex::block::{decompress_exact, DecompressError};
+    let compressed = compress_block(COMPRESSION1K);
+    let size = COMPRESSION1K.len();
+    assert_eq!(decompress_exact(&compressed, size).unwrap(), COMPRESSION1K);
+    assert!(matches!(
+        decompress_exact(&compressed, size + 10),
+        Err(DecompressError::UncompressedSizeDiffers { expected, actual })
+            if expected == size + 10 && actual == size
+    ));
+    // a truncated block ends early
+    assert!(decompress_exact(&compressed[..compressed.len() - 10], size).is_err());
+
+    #[cfg(any(feature = "safe-decode", feature = "checked-decode"))]
+    assert!(matches!(
+        decompress_exact(&compressed, size - 10),
+        Err(DecompressError::OutputTooSmall { .. })
+    ));
+}
+
+#[test]
+fn decompress_next_from_cursor() {
+    use lz4_flex::block::{compress_length_prefixed, decompress_next_from_cursor};
+    use std::io::{Cursor, Read};
+    let inputs: [&[u8]; 4] = [COMPRESSION1K, b"", COMPRESSION34K, COMPRESSION66JSON];
+    let stream: Vec<u8> = inputs
+        .iter()
+        .flat_map(|input| compress_length_prefixed(input))
+        .chain(*b"trailer")
+        .collect();
+
+    let mut cursor = Cursor::new(&stream[..]);
+    for input in &inputs[..3] {
+        assert_eq!(
+            decompress_next_from_cursor(&mut cursor).unwrap().unwrap(),
+            *input
+        );
+    }
+    // Other data can be read from the cursor in between
+    let pos = cursor.position();
+    let mut size = [0u8; 4];
+    cursor.read_exact(&mut size).unwrap();
+    assert_eq!(u32::from_le_bytes(size) as usize, COMPRESSION66JSON.len());
+    cursor.set_position(pos);
+    assert_eq!(
+        decompress_next_from_cursor(&mut cursor).unwrap().unwrap(),
+        COMPRESSION66JSON
+    );
+    // The trailer isn't a block, the cursor stays at its start
+    let pos = cursor.position();
+    assert!(decompress_next_from_cursor(&mut cursor).is_err());
+    assert_eq!(cursor.position(), pos);
+    let mut trailer = Vec::new();
+    cursor.read_to_end(&mut trailer).unwrap();
+    assert_eq!(trailer, b"trailer");
+    assert_eq!(decompress_next_from_cursor(&mut cursor).unwrap(), None);
+
+    // A truncated block
+    let block = compress_length_prefixed(COMPRESSION1K);
+    let mut cursor = Cursor::new(&block[..block.len() - 1]);
+    assert!(decompress_next_from_cursor(&mut cursor).is_err());
+    assert_eq!(cursor.position(), 0);
+    // A cursor over a Vec, beyond the end
+    let mut cursor = Cursor::new(block);
+    cursor.set_position(1000);
+    assert_eq!(decompress_next_from_cursor(&mut cursor).unwrap(), None);
+
+    // A size larger than the block can decompress to isn't allocated
+    let zeros = vec![0u8; 1024 * 1024];
+    let mut block = compress_length_prefixed(&zeros);
+    let mut cursor = Cursor::new(&block);
+    assert_eq!(
+        decompress_next_from_cursor(&mut cursor).unwrap().unwrap(),
+        zeros
+    );
+    block[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut cursor = Cursor::new(&block);
+    assert!(matches!(
+        decompress_next_from_cursor(&mut cursor),
+        Err(lz4_flex::block::DecompressError::InvalidSize)
+    ));
+}
+
+#[test]
+fn decompress_strict() {
+    use lz4_flex::block::{decompress_strict, DecompressError};
+    for input in [&b""[..], COMPRESSION1K, COMPRESSION34K, COMPRESSION65] {
+        let compressed = compress_block(input);
+        assert_eq!(decompress_strict(&compressed, input.len()).unwrap(), input);
+
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        assert!(matches!(
+            decompress_strict(&trailing, input.len()),
+            Err(DecompressError::TrailingBytes { count: 1 })
+        ));
+        // The next block of a container
+        let mut two_blocks = compressed.clone();
+        two_blocks.extend_from_slice(&compress_block(COMPRESSION1K));
+        assert!(matches!(
+            decompress_strict(&two_blocks, input.len()),
+            Err(DecompressError::TrailingBytes { count }) if count == two_blocks.len() - compressed.len()
+        ));
+    }
+    let compressed = compress_block(COMPRESSION1K);
+    // a truncated block or the wrong size are still errors
+    assert!(decompress_strict(&compressed[..compressed.len() - 10], 1024).is_err());
+    assert!(decompress_strict(&compressed, COMPRESSION1K.len() + 10).is_err());
+    #[cfg(any(feature = "safe-decode", feature = "checked-decode"))]
+    assert!(decompress_strict(&compressed, COMPRESSION1K.len() - 10).is_err());
+}
+
+#[test]
+fn decompress_small() {
+    use lz4_flex::block::decompress_small;
+    let periodic: Vec<u8> = b"abc".iter().cycle().take(300).copied().collect();
+    for data in [COMPRESSION66JSON, COMPRESSION1K, &periodic] {
+        for len in 0..=300.min(data.len()) {
+            let input = &data[data.len() - len..];
+            let compressed = compress_block(input);
+            assert_eq!(decompress_small(&compressed, input.len()).unwrap(), input);
+            // A larger size is fine, truncated blocks behave like with the general decoder
+            assert_eq!(
+                decompress_small(&compressed, input.len() + 10).unwrap(),
+                input
+            );
+            for cut in 0..compressed.len() {
+                let truncated = &compressed[..cut];
+                assert_eq!(
+                    decompress_small(truncated, input.len()).ok(),
+                    lz4_flex::block::decompress(truncated, input.len()).ok()
+                );
+            }
+            if len > 0 {
+                assert!(decompress_small(&compressed, input.len() - 1).is_err());
+            }
+        }
+    }
+}
+
 #[test]
 fn test_end_offset() {
     // the last 5 bytes need to be literals, so the last match block is not allowed to match to the
@@ -527,10 +1261,41 @@ fn bug_fuzz_8() {
 
 #[test]
 fn test_so_many_zeros() {
-    let data: Vec<u8> = iter::repeat(0).take(30_000).collect();
+    let data: Vec<u8> = vec![0; 30_000];
     test_roundtrip(data);
 }
 
+#[test]
+fn long_periodic_runs() {
+    // Bytes of a literal or match length of `len` beyond its 4 bits in the token.
+    let length_bytes = |len: usize| if len < 15 { 0 } else { (len - 15) / 255 + 1 };
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let unit: Vec<u8> = (0..100 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let len = 1024 * 1024;
+    for period in [100 * 1024, 65536, 65535, 60 * 1024, 1000, 7] {
+        let input: Vec<u8> = unit[..period].iter().copied().cycle().take(len).collect();
+        let compressed = compress_block(&input);
+        assert_eq!(decompress(&compressed, len).unwrap(), input);
+        let bound = if period > u16::MAX as usize {
+            // Offsets reach at most 65535 bytes back, so the best is a single literal run
+            1 + length_bytes(len) + len
+        } else {
+            // One literal run of the first period, a single match of maximal length (not
+            // chained matches) and the last literals
+            let match_len = len - period - 5;
+            1 + length_bytes(period) + period + 2 + length_bytes(match_len - 4) + 1 + 5
+        };
+        assert_le!(compressed.len(), bound + 16, "period {period}");
+    }
+}
+
 #[test]
 fn compression_works() {
     let s = r#"An iterator that knows its exact length.
@@ -542,6 +1307,39 @@ fn compression_works() {
     assert!(compress_block(s.as_bytes()).len() < s.len());
 }
 
+#[test]
+fn decompress_into_strided() {
+    use lz4_flex::block::{compress, decompress_into_strided, DecompressError};
+    // rows of different lengths, so that matches and literals cross row boundaries
+    for (input, row_len) in [
+        (COMPRESSION34K, 100),
+        (COMPRESSION66JSON, 7),
+        (&[b'a'; 1000][..], 33),
+        (&[1, 2, 3, 4, 5].repeat(300)[..], 64),
+    ] {
+        let compressed = compress(input);
+        let stride = row_len + 13;
+        let rows = input.len().div_ceil(row_len);
+        let mut output = vec![0xAAu8; rows * stride];
+        let len = decompress_into_strided(&compressed, &mut output, row_len, stride).unwrap();
+        assert_eq!(len, input.len());
+        for (row, expected) in output.chunks(stride).zip(input.chunks(row_len)) {
+            assert_eq!(&row[..expected.len()], expected);
+            // the padding between rows is untouched
+            assert!(row[row_len..].iter().all(|&b| b == 0xAA));
+        }
+
+        // the last row doesn't need padding, but the output has to hold all rows
+        let len = (rows - 1) * stride + row_len;
+        assert!(decompress_into_strided(&compressed, &mut output[..len], row_len, stride).is_ok());
+        let len = (rows - 2) * stride + row_len;
+        assert!(matches!(
+            decompress_into_strided(&compressed, &mut output[..len], row_len, stride),
+            Err(DecompressError::OutputTooSmall { .. })
+        ));
+    }
+}
+
 // #[test]
 // fn multi_compress() {
 //     let s1 = r#"An iterator that knows its exact length.performant implementation than the
@@ -553,78 +1351,825 @@ fn compression_works() {
 //     assert!(compress(s.as_bytes()).len() < s.len());
 // }
 
-#[ignore]
 #[test]
-fn big_compression() {
-    let mut s = Vec::with_capacity(80_000_000);
+fn delta_roundtrip() {
+    use lz4_flex::block::{compress_delta, decompress_delta};
+    let version_a = COMPRESSION34K;
+    let mut version_b = version_a.to_vec();
+    version_b[1000..1010].copy_from_slice(b"0123456789");
+    version_b.drain(20_000..20_500);
+    version_b.splice(30_000..30_000, b"inserted text".iter().cloned());
+    version_b.extend_from_slice(b"appended at the end");
+
+    let patch = compress_delta(version_a, &version_b);
+    assert_lt!(patch.len(), compress_block(&version_b).len() / 10);
+    let reconstructed = decompress_delta(&patch, version_a, version_b.len()).unwrap();
+    assert_eq!(reconstructed, version_b);
+
+    // base larger than the window
+    let version_a = &COMPRESSION10MB[..200_000];
+    let version_b = &COMPRESSION10MB[150_000..210_000];
+    let patch = compress_delta(version_a, version_b);
+    let reconstructed = decompress_delta(&patch, version_a, version_b.len()).unwrap();
+    assert_eq!(reconstructed, version_b);
+
+    let patch = compress_delta(b"", b"no base");
+    assert_eq!(decompress_delta(&patch, b"", 7).unwrap(), b"no base");
+}
 
-    for n in 0..80_000_000 {
-        s.push((n as u8).wrapping_mul(0xA).wrapping_add(33) ^ 0xA2);
+#[test]
+fn compress_reuse() {
+    let mut out = Vec::new();
+    lz4_flex::block::compress_reuse(COMPRESSION66JSON, &mut out);
+    let capacity = out.capacity();
+    assert_eq!(
+        decompress(&out, COMPRESSION66JSON.len()).unwrap(),
+        COMPRESSION66JSON
+    );
+    let ptr = out.as_ptr();
+    for input in [COMPRESSION1K, COMPRESSION34K, b"", COMPRESSION66JSON] {
+        lz4_flex::block::compress_reuse(input, &mut out);
+        assert_eq!(out, compress_block(input));
+        assert_eq!(decompress(&out, input.len()).unwrap(), input);
+        // the allocation is reused
+        assert_eq!(out.capacity(), capacity);
+        assert_eq!(out.as_ptr(), ptr);
     }
-
-    test_roundtrip(s);
 }
 
 #[test]
-#[cfg_attr(miri, ignore)]
-fn test_text_10mb() {
-    test_roundtrip(COMPRESSION10MB);
+fn compress_continue() {
+    use lz4_flex::block::{compress_continue, decompress_continue};
+    for chunk_size in [100, 5000, 70_000] {
+        let mut decompressed = Vec::new();
+        let mut compressed_len = 0;
+        for (i, chunk) in COMPRESSION10MB[..150_000].chunks(chunk_size).enumerate() {
+            let prev_window = &COMPRESSION10MB[..i * chunk_size];
+            let block = compress_continue(prev_window, chunk);
+            compressed_len += block.len();
+            let uncompressed = decompress_continue(&block, &decompressed, chunk.len()).unwrap();
+            assert_eq!(uncompressed, chunk);
+            decompressed.extend_from_slice(&uncompressed);
+        }
+        assert_eq!(decompressed, &COMPRESSION10MB[..150_000]);
+        if chunk_size >= 5000 {
+            // the window is used for back references
+            let independent_len: usize = COMPRESSION10MB[..150_000]
+                .chunks(chunk_size)
+                .map(|chunk| compress_block(chunk).len())
+                .sum();
+            assert!(compressed_len < independent_len);
+        }
+    }
+    assert_eq!(
+        decompress_continue(&compress_continue(b"abcdefgh", b""), b"abcdefgh", 0).unwrap(),
+        b""
+    );
 }
+
 #[test]
-fn test_json_66k() {
-    test_roundtrip(COMPRESSION66JSON);
+fn compress_dependent_block() {
+    use lz4_flex::block::{compress_continue, compress_dependent_block, decompress_continue};
+    let prev = COMPRESSION34K;
+    // Blocks of 12 bytes can start with a match into `prev`, which `compress_continue` doesn't
+    // search for.
+    let input = &COMPRESSION34K[1000..1012];
+    let block = compress_dependent_block(prev, input);
+    assert_lt!(block.len(), 12);
+    assert_eq!(block[0] >> 4, 0, "starts with a match");
+    assert_gt!(compress_continue(prev, input).len(), 12);
+    assert_eq!(decompress_continue(&block, prev, 12).unwrap(), input);
+
+    // Otherwise the output is the same as `compress_continue`.
+    for len in [0, 5, 11, 13, 100, 5000] {
+        let input = &COMPRESSION66JSON[..len];
+        let block = compress_dependent_block(prev, input);
+        assert_eq!(block, compress_continue(prev, input));
+        assert_eq!(decompress_continue(&block, prev, len).unwrap(), input);
+    }
+    // Without a prefix there is nothing to match.
+    let block = compress_dependent_block(b"", &input[..12]);
+    assert_eq!(decompress_continue(&block, b"", 12).unwrap(), &input[..12]);
 }
+
 #[test]
-fn test_text_65k() {
-    test_roundtrip(COMPRESSION65);
+fn compress_within() {
+    use lz4_flex::block::{compress_within, CompressError};
+    let inputs: [&[u8]; 5] = [
+        b"",
+        b"hello",
+        COMPRESSION1K,
+        COMPRESSION66JSON,
+        &COMPRESSION10MB[..200_000],
+    ];
+    for input in inputs {
+        let compressed = compress_block(input);
+        assert_eq!(
+            compress_within(input, compressed.len()).unwrap(),
+            compressed
+        );
+        assert_eq!(compress_within(input, usize::MAX).unwrap(), compressed);
+        assert!(matches!(
+            compress_within(input, compressed.len() - 1),
+            Err(CompressError::OutputBudgetExceeded { max_output }) if max_output == compressed.len() - 1
+        ));
+    }
+
+    // incompressible input exceeds a tight budget
+    let mut state = 0x1234_5678u32;
+    let random: Vec<u8> = (0..100_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    assert!(matches!(
+        compress_within(&random, 1000),
+        Err(CompressError::OutputBudgetExceeded { .. })
+    ));
 }
+
 #[test]
-fn test_text_34k() {
-    test_roundtrip(COMPRESSION34K);
+fn compress_dest_size() {
+    use lz4_flex::block::{compress_dest_size, disassemble, Token};
+    let mut page = [0u8; 1024];
+    let (consumed, written) = compress_dest_size(COMPRESSION34K, &mut page);
+    assert!(written <= 1024 && written > 1000);
+    assert!(consumed > 1024 && consumed < COMPRESSION34K.len());
+    assert_eq!(
+        decompress(&page[..written], consumed).unwrap(),
+        &COMPRESSION34K[..consumed]
+    );
+    assert_eq!(
+        lz4_cpp_block_decompress(&page[..written], consumed).unwrap(),
+        &COMPRESSION34K[..consumed]
+    );
+
+    let random: Vec<u8> = (0..5000u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    for input in [COMPRESSION34K, &random, &[0u8; 5000], b"short"] {
+        for page_size in (0..300).chain([1000, 4999, 5020, 40_000]) {
+            let mut page = vec![0u8; page_size];
+            let (consumed, written) = compress_dest_size(input, &mut page);
+            assert!(written <= page_size);
+            if written == 0 {
+                assert_eq!(consumed, 0);
+                continue;
+            }
+            let block = &page[..written];
+            assert_eq!(
+                decompress(block, consumed).unwrap(),
+                &input[..consumed],
+                "page size {page_size}"
+            );
+            if page_size >= 40_000 {
+                assert_eq!(consumed, input.len());
+            }
+            // End of block rules: the last match starts at least 12 bytes before the end and is
+            // followed by at least 5 literals.
+            let tokens = disassemble(block).unwrap();
+            if let [.., Token::Match { length, .. }, last] = tokens[..] {
+                assert!(matches!(last, Token::Literals(len) if len >= 5 && length + len >= 12));
+            }
+        }
+    }
 }
 
 #[test]
-fn test_text_1k() {
-    test_roundtrip(COMPRESSION1K);
+fn compress_into_split() {
+    use lz4_flex::block::{compress_into_split, get_maximum_output_size, CompressError};
+    let mut buffer = vec![0u8; get_maximum_output_size(COMPRESSION1K.len() + COMPRESSION34K.len())];
+    let (first, rest) = compress_into_split(COMPRESSION1K, &mut buffer).unwrap();
+    let first_len = first.len();
+    assert_eq!(first, &compress_block(COMPRESSION1K)[..]);
+    let rest_len = rest.len();
+    let (second, rest) = compress_into_split(COMPRESSION34K, rest).unwrap();
+    let second_len = second.len();
+    assert_eq!(rest.len(), rest_len - second_len);
+
+    let (first, second) = buffer.split_at(first_len);
+    assert_eq!(
+        decompress(first, COMPRESSION1K.len()).unwrap(),
+        COMPRESSION1K
+    );
+    assert_eq!(
+        decompress(&second[..second_len], COMPRESSION34K.len()).unwrap(),
+        COMPRESSION34K
+    );
+
+    assert!(matches!(
+        compress_into_split(COMPRESSION34K, &mut [0u8; 100]),
+        Err(CompressError::OutputTooSmall { .. })
+    ));
 }
 
-use proptest::{prelude::*, test_runner::FileFailurePersistence};
+#[test]
+fn compress_with_dict_smart() {
+    use lz4_flex::block::{compress_with_dict, compress_with_dict_smart, decompress_with_dict};
+    // 256KB dictionary, of which only the text at the front is related to the input.
+    let mut dict = COMPRESSION10MB[..64 * 1024].to_vec();
+    while dict.len() < 256 * 1024 {
+        dict.extend_from_slice(COMPRESSION66JSON);
+    }
+    dict.truncate(256 * 1024);
+    let input = &COMPRESSION10MB[100 * 1024..120 * 1024];
 
-proptest! {
-    #![proptest_config(ProptestConfig {
-        failure_persistence: Some(Box::new(FileFailurePersistence::WithSource("regressions"))),
-        ..Default::default()
-    })]
+    let (compressed, range) = compress_with_dict_smart(input, &dict);
+    assert_eq!(range, 0..64 * 1024);
+    let decompressed = decompress_with_dict(&compressed, input.len(), &dict[range]).unwrap();
+    assert_eq!(decompressed, input);
+    let tail_len = compress_with_dict(input, &dict).len();
+    assert_lt!(compressed.len(), tail_len);
+
+    // Small dictionaries are used as they are.
+    let dict = &COMPRESSION10MB[..10_000];
+    let (compressed, range) = compress_with_dict_smart(input, dict);
+    assert_eq!(range, 0..dict.len());
+    assert_eq!(compressed, compress_with_dict(input, dict));
+}
 
-    #[test]
-    #[cfg_attr(miri, ignore)]
-    fn proptest_roundtrip(v in vec_of_vec()) {
-        let data: Vec<u8>  = v.iter().flat_map(|v|v.iter()).cloned().collect::<Vec<_>>();
-        test_roundtrip(data);  // sum of the sum of all vectors.
+#[test]
+fn stream_compressor() {
+    use lz4_flex::block::{decompress_with_dict, StreamCompressor};
+    let input = &COMPRESSION10MB[..1024 * 1024];
+    let block_size = 16 * 1024;
+    let mut compressor = StreamCompressor::new(block_size);
+    let mut blocks = Vec::new();
+    for chunk in input[..300 * 1024].chunks(7777) {
+        blocks.extend(compressor.push(chunk));
+    }
+    // A flushed block in the middle of the stream is still referenced by the following blocks.
+    blocks.extend(compressor.flush());
+    blocks.extend(compressor.push(&input[300 * 1024..]));
+    blocks.extend(compressor.finish());
+    assert_eq!(blocks.len(), 300 / 16 + 1 + 724 / 16 + 1);
+
+    let mut decompressed = Vec::new();
+    for block in &blocks {
+        let dict = &decompressed[decompressed.len().saturating_sub(64 * 1024)..];
+        let data = decompress_with_dict(block, block_size, dict).unwrap();
+        assert!(data.len() <= block_size);
+        decompressed.extend_from_slice(&data);
     }
+    assert_eq!(decompressed, input);
 
+    // Linked blocks compress better than independent ones.
+    let compressed_len: usize = blocks.iter().map(|block| block.len()).sum();
+    let independent_len: usize = input
+        .chunks(block_size)
+        .map(|block| compress_block(block).len())
+        .sum();
+    assert!(compressed_len < independent_len);
 }
 
-fn vec_of_vec() -> impl Strategy<Value = Vec<Vec<u8>>> {
-    const N: u8 = 200;
+#[test]
+fn compress_vectored() {
+    use lz4_flex::block::compress_vectored;
+    let bufs: Vec<&[u8]> = COMPRESSION34K.chunks(777).collect();
+    let compressed = compress_vectored(&bufs);
+    // matches cross the slice boundaries
+    assert_eq!(compressed, compress_block(COMPRESSION34K));
+    assert_eq!(
+        decompress(&compressed, COMPRESSION34K.len()).unwrap(),
+        COMPRESSION34K
+    );
 
-    let length = 0..N;
-    length.prop_flat_map(vec_from_length)
+    let bufs = [b"" as &[u8], b"hello ", b"", b"hello hello ", b"world"];
+    let compressed = compress_vectored(&bufs);
+    assert_eq!(
+        decompress(&compressed, 23).unwrap(),
+        b"hello hello hello world"
+    );
+    assert_eq!(
+        compress_vectored(&[COMPRESSION1K]),
+        compress_block(COMPRESSION1K)
+    );
+    assert_eq!(decompress(&compress_vectored(&[]), 0).unwrap(), b"");
 }
 
-fn vec_from_length(length: u8) -> impl Strategy<Value = Vec<Vec<u8>>> {
-    const K: usize = u8::MAX as usize;
-    let mut result = vec![];
-    for index in 1..length {
-        let inner = proptest::collection::vec(0..index, 0..K);
-        result.push(inner);
+#[test]
+fn compress_with_table() {
+    use lz4_flex::block::{compress_with_table, HashTable};
+    /// Keeps only the last position, regardless of the input.
+    struct ConstantHashTable(usize);
+    impl HashTable for ConstantHashTable {
+        fn get_at(&self, _hash: usize) -> usize {
+            self.0
+        }
+        fn put_at(&mut self, _hash: usize, val: usize) {
+            self.0 = val;
+        }
+        fn clear(&mut self) {
+            self.0 = 0;
+        }
+        fn get_hash_at(_input: &[u8], _pos: usize) -> usize {
+            0
+        }
     }
-    result
+
+    let mut table = ConstantHashTable(12345);
+    for input in [
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+        b"",
+        b"aaaaaaaaaaaaaaaaaaaa",
+    ] {
+        let compressed = compress_with_table(input, &mut table);
+        assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+    }
+    // runs of the same byte are still found
+    let input = [7u8; 1000];
+    assert_lt!(compress_with_table(&input, &mut table).len(), 20);
 }
 
-#[cfg(feature = "frame")]
-mod frame {
-    use lz4_flex::frame::BlockSize;
+#[test]
+fn compress_job() {
+    use lz4_flex::block::{compress, CompressJob};
+    use std::task::Poll;
+
+    let incompressible: Vec<u8> = (0..100_000u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    for input in [
+        COMPRESSION1K,
+        COMPRESSION34K,
+        COMPRESSION66JSON,
+        COMPRESSION10MB,
+        &incompressible,
+        b"",
+        b"0123456789abc",
+    ] {
+        let expected = compress(input);
+        for budget in [0, 100, 4096, usize::MAX] {
+            if input.len() == COMPRESSION10MB.len() && budget < 4096 {
+                continue;
+            }
+            let mut job = CompressJob::new(input);
+            let mut steps = 1;
+            let compressed = loop {
+                match job.step(budget) {
+                    Poll::Ready(compressed) => break compressed,
+                    Poll::Pending => steps += 1,
+                }
+            };
+            assert_eq!(compressed, expected);
+            if budget == 4096 {
+                assert_ge!(steps, input.len() / (2 * 4096));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn compress_with_analysis() {
+    use lz4_flex::block::{compress, compress_with_analysis};
+
+    for input in [COMPRESSION66JSON, COMPRESSION10MB, b"".as_slice()] {
+        let (compressed, profile) = compress_with_analysis(input);
+        assert_eq!(compressed, compress(input));
+        let phases = profile.match_finding + profile.literal_copy + profile.output;
+        assert_le!(phases, profile.total);
+        assert_eq!(
+            profile.sequences,
+            lz4_flex::block::decompress_with_stats(&compressed, input.len())
+                .unwrap()
+                .1
+                .sequences
+        );
+        if input.len() > 1000 {
+            // only the setup isn't covered by the phases
+            assert_ge!(phases * 2, profile.total);
+            assert!(!profile.match_finding.is_zero());
+            assert!(!profile.literal_copy.is_zero());
+            assert!(!profile.output.is_zero());
+        }
+    }
+}
+
+#[test]
+fn compress_with_coverage() {
+    use lz4_flex::block::{compress, compress_with_coverage, decompress_with_stats};
+
+    // A unique part, followed by a repeated part
+    let mut state = 0x2545_f491_u32;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    };
+    let unique: Vec<u8> = (0..2000).map(|_| random()).collect();
+    let chunk: Vec<u8> = (0..1000).map(|_| random()).collect();
+    let partially_repetitive = [&unique[..], &chunk, &chunk, &chunk, &chunk].concat();
+
+    for input in [
+        COMPRESSION66JSON,
+        COMPRESSION10MB,
+        &partially_repetitive,
+        b"".as_slice(),
+    ] {
+        let (compressed, literals) = compress_with_coverage(input);
+        assert_eq!(compressed, compress(input));
+        let mut end = 0;
+        for range in &literals {
+            assert_lt!(range.start, range.end);
+            assert_le!(end, range.start);
+            end = range.end;
+        }
+        assert_le!(end, input.len());
+        let literal_bytes: usize = literals.iter().map(|range| range.len()).sum();
+        let stats = decompress_with_stats(&compressed, input.len()).unwrap().1;
+        assert_eq!(literal_bytes, stats.literal_bytes);
+    }
+
+    let (_, literals) = compress_with_coverage(&partially_repetitive);
+    // The repetitions are matches, except for the end of the block, which is written as literals
+    let repeated = 3000..partially_repetitive.len() - 12;
+    assert!(literals
+        .iter()
+        .all(|range| range.end <= repeated.start || range.start >= repeated.end));
+    assert_eq!(literals.last().unwrap().end, partially_repetitive.len());
+}
+
+#[cfg(feature = "frame")]
+#[test]
+fn integrity_stream() {
+    use lz4_flex::block::integrity::{IntegrityError, IntegrityReader, IntegrityWriter};
+    use std::collections::hash_map::DefaultHasher;
+    use std::io::{ErrorKind, Read, Write};
+
+    const BLOCK_SIZE: usize = 4 * 1024;
+    const INTERVAL: usize = 16 * 1024;
+    let input = COMPRESSION66JSON;
+    let mut writer = IntegrityWriter::with_options(Vec::new(), BLOCK_SIZE, INTERVAL);
+    writer.write_all(input).unwrap();
+    let stream = writer.finish().unwrap();
+    let mut uncompressed = Vec::new();
+    IntegrityReader::new(&stream[..])
+        .read_to_end(&mut uncompressed)
+        .unwrap();
+    assert_eq!(uncompressed, input);
+
+    // Corrupt the last byte of block 5, which is always a literal, so the block still
+    // decompresses. With 4 blocks per checkpoint, it's covered by checkpoint 1.
+    let u32_at = |pos: usize| u32::from_le_bytes(stream[pos..pos + 4].try_into().unwrap());
+    let mut pos = 0;
+    let mut block = 0;
+    let corrupt_pos = loop {
+        if u32_at(pos) == u32::MAX {
+            pos += 4 + 8;
+            continue;
+        }
+        pos += 8 + u32_at(pos + 4) as usize;
+        if block == 5 {
+            break pos - 1;
+        }
+        block += 1;
+    };
+    let mut corrupted = stream.clone();
+    corrupted[corrupt_pos] ^= 1;
+    let mut uncompressed = Vec::new();
+    let err = IntegrityReader::new(&corrupted[..])
+        .read_to_end(&mut uncompressed)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    // only the verified data of checkpoint 0 is returned
+    assert_eq!(uncompressed, &input[..INTERVAL]);
+    let err = err
+        .into_inner()
+        .unwrap()
+        .downcast::<IntegrityError>()
+        .unwrap();
+    assert!(matches!(
+        *err,
+        IntegrityError::ChecksumMismatch {
+            checkpoint: 1,
+            start: 16384,
+            end: 32768
+        }
+    ));
+
+    // a stream without the end record
+    let err = IntegrityReader::new(&stream[..stream.len() - 4])
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+    // a custom checksum, flushed checkpoints
+    let mut writer =
+        IntegrityWriter::<_, DefaultHasher>::with_hasher(Vec::new(), BLOCK_SIZE, INTERVAL);
+    for chunk in COMPRESSION34K.chunks(1000) {
+        writer.write_all(chunk).unwrap();
+        writer.flush().unwrap();
+    }
+    let stream = writer.finish().unwrap();
+    let mut uncompressed = Vec::new();
+    IntegrityReader::<_, DefaultHasher>::with_hasher(&stream[..])
+        .read_to_end(&mut uncompressed)
+        .unwrap();
+    assert_eq!(uncompressed, COMPRESSION34K);
+    let err = IntegrityReader::new(&stream[..])
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert!(err.to_string().contains("checkpoint 0"));
+}
+
+#[test]
+fn compress_archive() {
+    use lz4_flex::block::{compress_archive, decompress_archive, DecompressError};
+    let records: Vec<String> = (0..200)
+        .map(|i| format!(r#"{{"id": {i}, "name": "user{i}", "active": true, "tags": ["a", "b"]}}"#))
+        .collect();
+    let items: Vec<&[u8]> = records.iter().map(|record| record.as_bytes()).collect();
+    let compressed = compress_archive(&items);
+    assert_eq!(decompress_archive(&compressed).unwrap(), items);
+    // similar items compress much better together
+    let separate_len: usize = items.iter().map(|item| compress_block(item).len()).sum();
+    assert_lt!(compressed.len() * 4, separate_len);
+
+    // item boundaries, including empty items
+    let items = [b"" as &[u8], b"a", b"", COMPRESSION1K, b""];
+    assert_eq!(
+        decompress_archive(&compress_archive(&items)).unwrap(),
+        items
+    );
+    assert!(decompress_archive(&compress_archive(&[]))
+        .unwrap()
+        .is_empty());
+
+    // the length of the second item exceeds the archive
+    let archive = [1, 0, 0, 0, b'a', 2, 0, 0, 0, b'b'];
+    assert!(matches!(
+        decompress_archive(&lz4_flex::block::compress_prepend_size(&archive)),
+        Err(DecompressError::InvalidArchive { item: 1 })
+    ));
+}
+
+#[test]
+fn compress_many_into() {
+    let mut inputs: Vec<&[u8]> = COMPRESSION66JSON.chunks(1000).collect();
+    inputs.extend([
+        b"" as &[u8],
+        b"a",
+        COMPRESSION34K,
+        COMPRESSION1K,
+        b"abcabcabcabcabcabc",
+    ]);
+    let max_output_size: usize = inputs
+        .iter()
+        .map(|input| lz4_flex::block::get_maximum_output_size(input.len()))
+        .sum();
+    let mut output = vec![0u8; max_output_size];
+    let ranges = lz4_flex::block::compress_many_into(&inputs, &mut output).unwrap();
+    assert_eq!(ranges.len(), inputs.len());
+    let mut expected_offset = 0;
+    for (input, &(offset, len)) in inputs.iter().zip(&ranges) {
+        assert_eq!(offset, expected_offset);
+        expected_offset += len;
+        let compressed = &output[offset..offset + len];
+        assert_eq!(decompress(compressed, input.len()).unwrap(), *input);
+    }
+
+    let mut output = vec![0u8; 10_000];
+    assert!(lz4_flex::block::compress_many_into(&inputs, &mut output).is_err());
+}
+
+#[test]
+#[cfg(feature = "bytes")]
+fn bytes_roundtrip() {
+    let input = bytes::Bytes::from_static(COMPRESSION34K);
+    let compressed = lz4_flex::block::compress_bytes(&input);
+    let uncompressed = lz4_flex::block::decompress_bytes(&compressed, input.len()).unwrap();
+    assert_eq!(uncompressed, input);
+
+    #[cfg(feature = "frame")]
+    {
+        let mut compressed = bytes::BytesMut::from(&b"prefix"[..]);
+        lz4_flex::frame::compress_into_bytes_mut(COMPRESSION66JSON, &mut compressed).unwrap();
+        assert_eq!(&compressed[..6], b"prefix");
+        let mut uncompressed = bytes::BytesMut::new();
+        lz4_flex::frame::decompress_into_bytes_mut(&compressed[6..], &mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION66JSON);
+    }
+}
+
+#[test]
+#[cfg(feature = "capi")]
+fn capi_roundtrip() {
+    use lz4_flex::capi::*;
+    use std::ptr::{null, null_mut};
+
+    for input in [COMPRESSION1K, COMPRESSION34K, b""] {
+        let mut compressed = vec![0u8; lz4flex_compress_bound(input.len())];
+        let compressed_len = unsafe {
+            lz4flex_compress(
+                input.as_ptr(),
+                input.len(),
+                compressed.as_mut_ptr(),
+                compressed.len(),
+            )
+        };
+        assert!(compressed_len > 0);
+        let compressed = &compressed[..compressed_len as usize];
+        assert_eq!(compressed, lz4_flex::block::compress(input));
+
+        let mut uncompressed = vec![0u8; input.len()];
+        let uncompressed_len = unsafe {
+            lz4flex_decompress(
+                compressed.as_ptr(),
+                compressed.len(),
+                uncompressed.as_mut_ptr(),
+                uncompressed.len(),
+            )
+        };
+        assert_eq!(uncompressed_len, input.len() as isize);
+        assert_eq!(uncompressed, input);
+    }
+
+    let compressed = lz4_flex::block::compress(COMPRESSION1K);
+    let mut output = vec![0u8; COMPRESSION1K.len() - 1];
+    unsafe {
+        assert_eq!(
+            lz4flex_compress(
+                COMPRESSION1K.as_ptr(),
+                COMPRESSION1K.len(),
+                output.as_mut_ptr(),
+                100
+            ),
+            LZ4FLEX_ERROR_OUTPUT_TOO_SMALL
+        );
+        assert_eq!(
+            lz4flex_decompress(
+                compressed.as_ptr(),
+                compressed.len(),
+                output.as_mut_ptr(),
+                output.len()
+            ),
+            LZ4FLEX_ERROR_OUTPUT_TOO_SMALL
+        );
+        // offset 0 is invalid
+        assert_eq!(
+            lz4flex_decompress(
+                [0x11, 1, 0, 0].as_ptr(),
+                4,
+                output.as_mut_ptr(),
+                output.len()
+            ),
+            LZ4FLEX_ERROR_INVALID_INPUT
+        );
+        assert_eq!(
+            lz4flex_compress(null(), 10, output.as_mut_ptr(), output.len()),
+            LZ4FLEX_ERROR_NULL_POINTER
+        );
+        assert_eq!(
+            lz4flex_decompress(compressed.as_ptr(), compressed.len(), null_mut(), 10),
+            LZ4FLEX_ERROR_NULL_POINTER
+        );
+
+        // in-place operation isn't supported, adjacent ranges are fine
+        let mut buffer = vec![0u8; 4096];
+        buffer[..compressed.len()].copy_from_slice(&compressed);
+        let ptr = buffer.as_mut_ptr();
+        assert!(lz4flex_compress(ptr, 1000, ptr.add(1000), 3000) > 0);
+        assert_eq!(
+            lz4flex_compress(ptr.add(100), 1000, ptr, 3000),
+            LZ4FLEX_ERROR_OVERLAP
+        );
+        assert_eq!(
+            lz4flex_decompress(ptr, compressed.len(), ptr.add(compressed.len() - 1), 2000),
+            LZ4FLEX_ERROR_OVERLAP
+        );
+    }
+    assert_eq!(lz4flex_compress_bound(usize::MAX), 0);
+}
+
+#[ignore]
+#[test]
+fn big_compression() {
+    let mut s = Vec::with_capacity(80_000_000);
+
+    for n in 0..80_000_000 {
+        s.push((n as u8).wrapping_mul(0xA).wrapping_add(33) ^ 0xA2);
+    }
+
+    test_roundtrip(s);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn test_text_10mb() {
+    test_roundtrip(COMPRESSION10MB);
+}
+#[test]
+fn test_json_66k() {
+    test_roundtrip(COMPRESSION66JSON);
+}
+#[test]
+fn test_text_65k() {
+    test_roundtrip(COMPRESSION65);
+}
+#[test]
+fn test_text_34k() {
+    test_roundtrip(COMPRESSION34K);
+}
+
+#[test]
+fn test_text_1k() {
+    test_roundtrip(COMPRESSION1K);
+}
+
+use proptest::{prelude::*, test_runner::FileFailurePersistence};
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        failure_persistence: Some(Box::new(FileFailurePersistence::WithSource("regressions"))),
+        ..Default::default()
+    })]
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn proptest_roundtrip(v in vec_of_vec()) {
+        let data: Vec<u8>  = v.iter().flat_map(|v|v.iter()).cloned().collect::<Vec<_>>();
+        test_roundtrip(data);  // sum of the sum of all vectors.
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn proptest_max_expansion(input in proptest::collection::vec(any::<u8>(), 0..2000)) {
+        let max_len = input.len() + lz4_flex::block::max_expansion(input.len());
+        prop_assert!(compress_block(&input).len() <= max_len);
+        prop_assert!(compress_prepend_size(&input).len() <= max_len + 4);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn proptest_roundtrip_literal_runs(runs in literal_runs(), slack in 0..40usize) {
+        // Random literal runs around the 32 bytes copied at once, separated by repetitions, and
+        // decompressed close to the end of the output.
+        let mut data = Vec::new();
+        for (literals, repeat_len) in runs {
+            data.extend_from_slice(&literals);
+            let start = data.len().saturating_sub(repeat_len);
+            data.extend_from_within(start..);
+        }
+        let compressed = compress_block(&data);
+        let mut output = vec![0u8; data.len() + slack];
+        let len = lz4_flex::block::decompress_into(&compressed, &mut output).unwrap();
+        prop_assert_eq!(&output[..len], &data[..]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn proptest_common_prefix_len(
+        a in proptest::collection::vec(any::<u8>(), 0..100),
+        tail in proptest::collection::vec(any::<u8>(), 0..20),
+        prefix_len in 0..100usize,
+        offset in 0..8usize,
+    ) {
+        // `b` shares up to `prefix_len` bytes with `a`, followed by bytes which may match by
+        // chance. The offset tests unaligned slices.
+        let mut b = vec![0u8; offset];
+        b.extend_from_slice(&a[..prefix_len.min(a.len())]);
+        b.extend_from_slice(&tail);
+        let b = &b[offset..];
+        let naive = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+        prop_assert_eq!(lz4_flex::block::common_prefix_len(&a, b), naive);
+        prop_assert_eq!(lz4_flex::block::common_prefix_len(b, &a), naive);
+    }
+}
+
+fn literal_runs() -> impl Strategy<Value = Vec<(Vec<u8>, usize)>> {
+    proptest::collection::vec(
+        (proptest::collection::vec(any::<u8>(), 0..48), 0..24usize),
+        0..20,
+    )
+}
+
+fn vec_of_vec() -> impl Strategy<Value = Vec<Vec<u8>>> {
+    const N: u8 = 200;
+
+    let length = 0..N;
+    length.prop_flat_map(vec_from_length)
+}
+
+fn vec_from_length(length: u8) -> impl Strategy<Value = Vec<Vec<u8>>> {
+    const K: usize = u8::MAX as usize;
+    let mut result = vec![];
+    for index in 1..length {
+        let inner = proptest::collection::vec(0..index, 0..K);
+        result.push(inner);
+    }
+    result
+}
+
+#[cfg(feature = "frame")]
+mod frame {
+    use lz4_flex::frame::BlockSize;
 
     use super::*;
     use std::io::{Read, Write};
@@ -646,6 +2191,261 @@ mod frame {
         assert_eq!(&*uncompressed, COMPRESSION34K);
     }
 
+    #[test]
+    fn compressed_bytes_consumed() {
+        let mut frame_info = lz4_flex::frame::FrameInfo::new();
+        frame_info.block_checksums = true;
+        frame_info.content_checksum = true;
+        frame_info.content_size = Some(COMPRESSION66JSON.len() as u64);
+        frame_info.block_mode = BlockMode::Linked;
+        frame_info.block_size = BlockSize::Max64KB;
+        let mut compressed = lz4_flex_frame_compress_with(frame_info, COMPRESSION66JSON).unwrap();
+        let first_frame_len = compressed.len() as u64;
+        let frame_info = lz4_flex::frame::FrameInfo::new()
+            .block_mode(BlockMode::Linked)
+            .block_size(BlockSize::Max64KB);
+        compressed.extend(lz4_flex_frame_compress_with(frame_info, COMPRESSION1K).unwrap());
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&*compressed);
+        assert_eq!(dec.compressed_bytes_consumed(), 0);
+        let mut buf = [0u8; 1024];
+        dec.read_exact(&mut buf).unwrap();
+        let consumed = dec.compressed_bytes_consumed();
+        assert!(consumed > 0 && consumed < first_frame_len);
+
+        let mut uncompressed = buf.to_vec();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION66JSON);
+        assert_eq!(dec.compressed_bytes_consumed(), first_frame_len);
+
+        uncompressed.clear();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION1K);
+        assert_eq!(dec.compressed_bytes_consumed(), compressed.len() as u64);
+    }
+
+    #[test]
+    fn write_block_borrowed() {
+        let input = &COMPRESSION10MB[..1_000_000];
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            for block_size in [BlockSize::Max64KB, BlockSize::Max256KB] {
+                let frame_info = lz4_flex::frame::FrameInfo::new()
+                    .block_mode(block_mode)
+                    .block_size(block_size)
+                    .block_checksums(true)
+                    .content_checksum(true);
+                let max_block_size = match block_size {
+                    BlockSize::Max64KB => 64 * 1024,
+                    _ => 256 * 1024,
+                };
+
+                let mut enc =
+                    lz4_flex::frame::FrameEncoder::with_frame_info(frame_info.clone(), Vec::new());
+                for block in input.chunks(max_block_size) {
+                    enc.write_block_borrowed(block).unwrap();
+                }
+                let compressed = enc.finish().unwrap();
+                assert_eq!(lz4_flex_frame_decompress(&compressed).unwrap(), input);
+                let compressed_with_write =
+                    lz4_flex_frame_compress_with(frame_info.clone(), input).unwrap();
+                assert_lt!(compressed.len(), compressed_with_write.len() * 101 / 100);
+
+                // mixed with buffered writes and short blocks
+                let mut enc =
+                    lz4_flex::frame::FrameEncoder::with_frame_info(frame_info.clone(), Vec::new());
+                let (head, tail) = input.split_at(100);
+                enc.write_all(head).unwrap();
+                enc.flush().unwrap();
+                for (i, block) in tail.chunks(max_block_size).enumerate() {
+                    if i % 3 == 1 {
+                        enc.write_all(&block[..10]).unwrap();
+                        enc.write_block_borrowed(&block[10..]).unwrap();
+                    } else {
+                        let (a, b) = block.split_at(block.len() / 3);
+                        enc.write_block_borrowed(a).unwrap();
+                        enc.write_block_borrowed(b).unwrap();
+                    }
+                }
+                let compressed = enc.finish().unwrap();
+                assert_eq!(lz4_flex_frame_decompress(&compressed).unwrap(), input);
+
+                let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, vec![]);
+                let too_big = vec![0; max_block_size + 1];
+                assert_eq!(
+                    enc.write_block_borrowed(&too_big).unwrap_err().kind(),
+                    std::io::ErrorKind::InvalidInput
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flush_decodable() {
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            let frame_info = lz4_flex::frame::FrameInfo::new()
+                .block_mode(block_mode)
+                .content_checksum(true);
+            let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+            let mut written = 0;
+            for chunk in COMPRESSION66JSON.chunks(10_000) {
+                enc.write_all(chunk).unwrap();
+                written += chunk.len();
+                enc.flush_decodable().unwrap();
+
+                // The frame is still open, but all data written so far can be decoded.
+                let mut dec = lz4_flex::frame::FrameDecoder::new(&enc.get_ref()[..]);
+                let mut uncompressed = Vec::new();
+                dec.read_to_end(&mut uncompressed).unwrap();
+                assert_eq!(uncompressed, &COMPRESSION66JSON[..written]);
+            }
+            let compressed = enc.finish().unwrap();
+            assert_eq!(
+                lz4_flex_frame_decompress(&compressed).unwrap(),
+                COMPRESSION66JSON
+            );
+        }
+    }
+
+    #[test]
+    fn verify_consumed() {
+        use std::hash::Hasher;
+        let frame_info = lz4_flex::frame::FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .content_checksum(true);
+        let mut compressed =
+            lz4_flex_frame_compress_with(frame_info.clone(), COMPRESSION66JSON).unwrap();
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&*compressed);
+        let mut buf = vec![0u8; 1000];
+        dec.read_exact(&mut buf).unwrap();
+        assert_eq!(dec.verify_consumed(), None);
+        // the first block has been decoded
+        let mut hasher = twox_hash::XxHash32::with_seed(0);
+        hasher.write(&COMPRESSION66JSON[..64 * 1024]);
+        assert_eq!(dec.content_hash(), hasher.finish() as u32);
+
+        dec.read_to_end(&mut buf).unwrap();
+        assert_eq!(dec.verify_consumed(), Some(true));
+        hasher.write(&COMPRESSION66JSON[64 * 1024..]);
+        assert_eq!(dec.content_hash(), hasher.finish() as u32);
+
+        let compressed_len = compressed.len();
+        compressed[compressed_len - 1] ^= 0xFF;
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&*compressed);
+        assert!(dec.read_to_end(&mut Vec::new()).is_err());
+        assert_eq!(dec.verify_consumed(), Some(false));
+
+        // no content checksum
+        let compressed = lz4_flex_frame_compress_with(Default::default(), COMPRESSION1K).unwrap();
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&*compressed);
+        dec.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(dec.verify_consumed(), None);
+    }
+
+    #[test]
+    fn block_mode_auto() {
+        fn compress_auto(
+            content_size: Option<u64>,
+            fallback: BlockMode,
+            write: impl Fn(&mut lz4_flex::frame::FrameEncoder<Vec<u8>>),
+        ) -> Vec<u8> {
+            let frame_info = lz4_flex::frame::FrameInfo::new()
+                .block_mode(BlockMode::Auto)
+                .block_size(BlockSize::Max64KB)
+                .content_size(content_size);
+            let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+            enc.set_auto_block_mode_fallback(fallback);
+            write(&mut enc);
+            enc.finish().unwrap()
+        }
+        fn is_independent(compressed: &[u8]) -> bool {
+            // FLG byte, B.Indep flag
+            compressed[4] & 0b0010_0000 != 0
+        }
+        let check = |compressed: &[u8], input: &[u8], independent: bool| {
+            assert_eq!(is_independent(compressed), independent);
+            assert_eq!(lz4_flex_frame_decompress(compressed).unwrap(), input);
+        };
+
+        // tiny input
+        let compressed = compress_auto(None, BlockMode::Linked, |enc| {
+            enc.write_all(COMPRESSION1K).unwrap()
+        });
+        check(&compressed, COMPRESSION1K, true);
+        let compressed = compress_auto(None, BlockMode::Linked, |_| {});
+        check(&compressed, b"", true);
+        // large input in a single write
+        let compressed = compress_auto(None, BlockMode::Independent, |enc| {
+            enc.write_all(COMPRESSION66JSON).unwrap()
+        });
+        check(&compressed, COMPRESSION66JSON, false);
+        // large input in small writes
+        let compressed = compress_auto(None, BlockMode::Independent, |enc| {
+            for chunk in COMPRESSION66JSON.chunks(1000) {
+                enc.write_all(chunk).unwrap();
+            }
+        });
+        check(&compressed, COMPRESSION66JSON, false);
+        // content size
+        let len = COMPRESSION66JSON.len() as u64;
+        let compressed = compress_auto(Some(len), BlockMode::Independent, |enc| {
+            enc.write_all(&COMPRESSION66JSON[..1000]).unwrap();
+            enc.flush().unwrap();
+            enc.write_all(&COMPRESSION66JSON[1000..]).unwrap();
+        });
+        check(&compressed, COMPRESSION66JSON, false);
+        let compressed = compress_auto(Some(1000), BlockMode::Linked, |enc| {
+            enc.write_all(&COMPRESSION66JSON[..1000]).unwrap();
+        });
+        check(&compressed, &COMPRESSION66JSON[..1000], true);
+        // flushed before the first block is full, uses the fallback
+        for fallback in [BlockMode::Independent, BlockMode::Linked] {
+            let compressed = compress_auto(None, fallback, |enc| {
+                enc.write_all(&COMPRESSION66JSON[..1000]).unwrap();
+                enc.flush().unwrap();
+                enc.write_all(&COMPRESSION66JSON[1000..]).unwrap();
+            });
+            check(
+                &compressed,
+                COMPRESSION66JSON,
+                fallback == BlockMode::Independent,
+            );
+        }
+    }
+
+    #[test]
+    fn block_checksums_detect_corruption_early() {
+        use lz4_flex::frame::{Error, FrameDecoder, FrameInfo};
+        let input = &COMPRESSION10MB[..1024 * 1024];
+        let frame_info = FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_checksums(true)
+            .content_checksum(true);
+        let mut compressed = lz4_flex_frame_compress_with(frame_info, input).unwrap();
+        // Corrupt the data of a block in the middle of the frame
+        let middle = compressed.len() / 2;
+        compressed[middle] ^= 0xFF;
+
+        let mut decoder = FrameDecoder::new(&compressed[..]);
+        let mut uncompressed = Vec::new();
+        let mut buf = [0u8; 4096];
+        let err = loop {
+            match decoder.read(&mut buf) {
+                Ok(0) => panic!("corruption not detected"),
+                Ok(n) => uncompressed.extend_from_slice(&buf[..n]),
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(Error::BlockChecksumError)
+        ));
+        // Detected long before the end, only intact data was returned
+        assert_lt!(uncompressed.len(), input.len() * 3 / 4);
+        assert_gt!(uncompressed.len(), 0);
+        assert_eq!(uncompressed, input[..uncompressed.len()]);
+    }
+
     #[test]
     fn checksums() {
         for &input in &[COMPRESSION34K, COMPRESSION66JSON] {
@@ -683,6 +2483,36 @@ mod frame {
         }
     }
 
+    #[test]
+    fn skip_checksum_verification() {
+        let decompress = |compressed: &[u8], verify_block: bool, verify_content: bool| {
+            let mut dec = lz4_flex::frame::FrameDecoder::new(compressed);
+            dec.set_verify_block_checksums(verify_block);
+            dec.set_verify_content_checksum(verify_content);
+            let mut uncompressed = Vec::new();
+            dec.read_to_end(&mut uncompressed)
+                .map(|_| (uncompressed, dec.verify_consumed()))
+        };
+        let frame_info = lz4_flex::frame::FrameInfo::new()
+            .block_checksums(true)
+            .content_checksum(true);
+        let mut compressed = lz4_flex_frame_compress_with(frame_info, COMPRESSION66JSON).unwrap();
+        let compressed_len = compressed.len();
+        // corrupt the last block checksum, the checksums are still consumed
+        compressed[compressed_len - 9] ^= 0xFF;
+        assert!(decompress(&compressed, true, true).is_err());
+        let (uncompressed, verified) = decompress(&compressed, false, true).unwrap();
+        assert_eq!(uncompressed, COMPRESSION66JSON);
+        assert_eq!(verified, Some(true));
+
+        // corrupt the content checksum
+        compressed[compressed_len - 1] ^= 0xFF;
+        assert!(decompress(&compressed, false, true).is_err());
+        let (uncompressed, verified) = decompress(&compressed, false, false).unwrap();
+        assert_eq!(uncompressed, COMPRESSION66JSON);
+        assert_eq!(verified, None);
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn block_size() {
@@ -736,6 +2566,90 @@ mod frame {
         }
     }
 
+    #[test]
+    fn content_size_truncated() {
+        let frame_info = lz4_flex::frame::FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_checksums(true)
+            .content_checksum(true)
+            .content_size(Some(COMPRESSION66JSON.len() as u64));
+        let compressed = lz4_flex_frame_compress_with(frame_info, COMPRESSION66JSON).unwrap();
+        // magic number + frame descriptor with content size
+        let header_len = 4 + 3 + 8;
+        let first_block_len = 4
+            + u32::from_le_bytes(compressed[header_len..header_len + 4].try_into().unwrap())
+                as usize
+            + 4;
+
+        for len in [
+            header_len,
+            header_len + 2,
+            header_len + 100,
+            header_len + first_block_len,
+            compressed.len() - 6,
+            compressed.len() - 4,
+            compressed.len() - 1,
+        ] {
+            match lz4_flex_frame_decompress(&compressed[..len]) {
+                Err(lz4_flex::frame::Error::ContentLengthError { expected, actual }) => {
+                    assert_eq!(expected, COMPRESSION66JSON.len() as u64);
+                    // only complete blocks are decompressed
+                    if len < header_len + first_block_len {
+                        assert_eq!(actual, 0);
+                    } else if len < compressed.len() - 8 {
+                        assert_eq!(actual, 64 * 1024);
+                    } else {
+                        assert_eq!(actual, expected);
+                    }
+                }
+                r => panic!("{len} {r:?}"),
+            }
+        }
+
+        // without a content size, a frame truncated at a block boundary ends silently
+        let frame_info = lz4_flex::frame::FrameInfo::new().block_size(BlockSize::Max64KB);
+        let compressed = lz4_flex_frame_compress_with(frame_info, COMPRESSION66JSON).unwrap();
+        let header_len = 4 + 3;
+        let first_block_len =
+            4 + u32::from_le_bytes(compressed[header_len..header_len + 4].try_into().unwrap())
+                as usize;
+        let uncompressed =
+            lz4_flex_frame_decompress(&compressed[..header_len + first_block_len]).unwrap();
+        assert_eq!(uncompressed, &COMPRESSION66JSON[..64 * 1024]);
+    }
+
+    #[test]
+    fn content_size_zero() {
+        let frame_info = lz4_flex::frame::FrameInfo::new().content_size(Some(0));
+        let enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info.clone(), Vec::new());
+        let compressed = enc.finish().unwrap();
+        // magic number + frame descriptor with content size + end mark
+        assert_eq!(compressed.len(), 4 + 3 + 8 + 4);
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&*compressed);
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert!(uncompressed.is_empty());
+        assert_eq!(dec.compressed_bytes_consumed(), compressed.len() as u64);
+
+        let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+        enc.write_all(b"").unwrap();
+        enc.flush().unwrap();
+        assert_eq!(enc.finish().unwrap(), compressed);
+
+        // data written with a declared content size of zero
+        let frame_info = lz4_flex::frame::FrameInfo::new().content_size(Some(0));
+        let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+        enc.write_all(b"123").unwrap();
+        match enc.finish() {
+            Err(lz4_flex::frame::Error::ContentLengthError { expected, actual }) => {
+                assert_eq!(expected, 0);
+                assert_eq!(actual, 3);
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn legacy_frame() {
@@ -743,6 +2657,944 @@ mod frame {
         let uncompressed = lz4_flex_frame_decompress(DECOMPRESSION10MB_LEGACY).unwrap();
         assert_eq!(uncompressed, COMPRESSION10MB);
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn decompress_frame_slice() {
+        use lz4_flex::frame::{decompress_frame_slice, Error, FrameInfo};
+        let input = mixed_random_text(300 * 1024);
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            for block_size in [BlockSize::Max64KB, BlockSize::Max256KB, BlockSize::Max4MB] {
+                let frame_info = FrameInfo::new()
+                    .block_mode(block_mode)
+                    .block_size(block_size)
+                    .block_checksums(true)
+                    .content_checksum(true)
+                    .content_size(Some(input.len() as u64));
+                let compressed = lz4_flex_frame_compress_with(frame_info, &input).unwrap();
+                assert_eq!(decompress_frame_slice(&compressed).unwrap(), input);
+
+                // All consecutive frames are decompressed
+                let plain =
+                    lz4_flex_frame_compress_with(FrameInfo::new().block_mode(block_mode), b"plain")
+                        .unwrap();
+                let frames = [&compressed[..], &plain, &compressed].concat();
+                let expected = [&input[..], b"plain", &input].concat();
+                assert_eq!(decompress_frame_slice(&frames).unwrap(), expected);
+
+                // Truncated frames and corrupted checksums
+                assert!(decompress_frame_slice(&compressed[..compressed.len() - 1]).is_err());
+                let mut corrupted = compressed.clone();
+                let last = corrupted.len() - 1;
+                corrupted[last] ^= 1;
+                assert!(matches!(
+                    decompress_frame_slice(&corrupted),
+                    Err(Error::ContentChecksumError)
+                ));
+                corrupted[last] ^= 1;
+                corrupted[30] ^= 1;
+                assert!(decompress_frame_slice(&corrupted).is_err());
+            }
+        }
+        let compressed = lz4_cpp_frame_compress(&input, false).unwrap();
+        assert_eq!(decompress_frame_slice(&compressed).unwrap(), input);
+        assert!(decompress_frame_slice(&[]).unwrap().is_empty());
+        assert!(decompress_frame_slice(&[0x04, 0x22]).is_err());
+
+        const DECOMPRESSION10MB_LEGACY: &[u8] = include_bytes!("../benches/dickens.lz4");
+        assert_eq!(
+            decompress_frame_slice(DECOMPRESSION10MB_LEGACY).unwrap(),
+            COMPRESSION10MB
+        );
+    }
+
+    /// Half pseudo random (incompressible) bytes followed by text.
+    fn mixed_random_text(len: usize) -> Vec<u8> {
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut data: Vec<u8> = (0..len / 2)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        data.extend_from_slice(&COMPRESSION10MB[..len / 2]);
+        data
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn adaptive() {
+        let input = mixed_random_text(1024 * 1024);
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            let frame_info = lz4_flex::frame::FrameInfo::new()
+                .block_size(BlockSize::Max64KB)
+                .block_mode(block_mode)
+                .content_checksum(true);
+            let compressed = lz4_flex_frame_compress_with(frame_info.clone(), &input).unwrap();
+
+            let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+            enc.set_adaptive(true);
+            enc.write_all(&input).unwrap();
+            let compressed_adaptive = enc.finish().unwrap();
+
+            let uncompressed = lz4_flex_frame_decompress(&compressed_adaptive).unwrap();
+            assert_eq!(uncompressed, input);
+            // The compressible half is still compressed.
+            assert_le!(compressed_adaptive.len(), compressed.len() + 64);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn block_size_growth() {
+        let input = &COMPRESSION10MB[..4 * 1024 * 1024];
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            let frame_info = lz4_flex::frame::FrameInfo::new()
+                .block_size(BlockSize::Max4MB)
+                .block_mode(block_mode);
+            let compressed = lz4_flex_frame_compress_with(frame_info.clone(), input).unwrap();
+
+            let mut enc = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+            enc.set_block_size_growth(true);
+            // The first block is written after 64KB.
+            enc.write_all(&input[..64 * 1024 + 1]).unwrap();
+            assert_gt!(enc.get_ref().len(), 32 * 1024);
+            enc.write_all(&input[64 * 1024 + 1..]).unwrap();
+            let (compressed_growth, stats) = enc.finish_with_stats().unwrap();
+
+            let uncompressed = lz4_flex_frame_decompress(&compressed_growth).unwrap();
+            assert_eq!(uncompressed, input);
+            // 4 blocks of 64KB, 4 of 256KB, the rest in 1MB blocks
+            assert_eq!(stats.blocks, 4 + 4 + 3);
+            assert_le!(
+                compressed_growth.len() as f64,
+                compressed.len() as f64 * 1.01
+            );
+        }
+    }
+
+    #[test]
+    fn wrap_block_as_frame() {
+        use lz4_flex::frame::FrameInfo;
+
+        for input in [COMPRESSION1K, COMPRESSION66JSON, b""] {
+            let block = lz4_flex::block::compress(input);
+            for frame_info in [
+                FrameInfo::new(),
+                FrameInfo::new()
+                    .block_checksums(true)
+                    .content_checksum(true)
+                    .content_size(Some(input.len() as u64)),
+                FrameInfo::new().block_mode(lz4_flex::frame::BlockMode::Auto),
+            ] {
+                let mut frame = Vec::new();
+                lz4_flex::frame::wrap_block_as_frame(&block, input.len(), &frame_info, &mut frame)
+                    .unwrap();
+                let mut dec = lz4_flex::frame::FrameDecoder::new(&frame[..]);
+                let mut uncompressed = Vec::new();
+                dec.read_to_end(&mut uncompressed).unwrap();
+                assert_eq!(uncompressed, input);
+                assert_eq!(
+                    dec.frame_info().unwrap().block_mode,
+                    lz4_flex::frame::BlockMode::Independent
+                );
+                assert_eq!(dec.compressed_bytes_consumed(), frame.len() as u64);
+                if frame_info.content_checksum {
+                    assert_eq!(dec.verify_consumed(), Some(true));
+                }
+            }
+        }
+
+        let block = lz4_flex::block::compress(COMPRESSION66JSON);
+        let wrap = |uncompressed_size, frame_info: FrameInfo| {
+            lz4_flex::frame::wrap_block_as_frame(&block, uncompressed_size, &frame_info, Vec::new())
+        };
+        assert!(matches!(
+            wrap(
+                COMPRESSION66JSON.len(),
+                FrameInfo::new().block_size(BlockSize::Max64KB)
+            ),
+            Err(lz4_flex::frame::Error::BlockTooBig)
+        ));
+        assert!(matches!(
+            wrap(
+                COMPRESSION66JSON.len(),
+                FrameInfo::new().content_size(Some(1))
+            ),
+            Err(lz4_flex::frame::Error::ContentLengthError { .. })
+        ));
+        assert!(wrap(1000, FrameInfo::new().content_checksum(true)).is_err());
+        // a non-empty block can't form an empty frame
+        assert!(wrap(0, FrameInfo::new()).is_err());
+
+        // incompressible data grows beyond the block size and is stored uncompressed
+        let mut state = 0x1234_5678u32;
+        let random: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let block = lz4_flex::block::compress(&random);
+        assert_gt!(block.len(), random.len());
+        let frame_info = FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_checksums(true);
+        let mut frame = Vec::new();
+        lz4_flex::frame::wrap_block_as_frame(&block, random.len(), &frame_info, &mut frame)
+            .unwrap();
+        assert_eq!(lz4_flex_frame_decompress(&frame).unwrap(), random);
+        assert_eq!(lz4_cpp_frame_decompress(&frame).unwrap(), random);
+    }
+
+    #[test]
+    fn recover_next_frame() {
+        let frame_info = lz4_flex::frame::FrameInfo::new().block_checksums(true);
+        let mut input = lz4_flex_frame_compress_with(frame_info.clone(), COMPRESSION1K).unwrap();
+        let mut corrupt = lz4_flex_frame_compress_with(frame_info.clone(), COMPRESSION34K).unwrap();
+        let corrupt_len = corrupt.len();
+        corrupt[corrupt_len / 2] ^= 0xFF;
+        input.extend_from_slice(&corrupt);
+        // includes a partial magic number
+        input.extend_from_slice(b"\x04\x22\x4D garbage");
+        input.extend_from_slice(
+            &lz4_flex_frame_compress_with(frame_info.clone(), COMPRESSION66JSON).unwrap(),
+        );
+        input.extend_from_slice(b"trailing garbage");
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&input[..]);
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION1K);
+        assert!(dec.read_to_end(&mut Vec::new()).is_err());
+
+        assert!(dec.recover_next_frame().unwrap());
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION66JSON);
+
+        assert!(dec.read_to_end(&mut Vec::new()).is_err());
+        assert!(!dec.recover_next_frame().unwrap());
+        assert_eq!(dec.compressed_bytes_consumed(), input.len() as u64);
+    }
+
+    #[test]
+    fn recover_next_frame_after_fake_magic() {
+        let frame = lz4_flex_frame_compress_with(Default::default(), COMPRESSION1K).unwrap();
+        // a fake magic number directly before the frame, and one with a valid FLG and BD byte,
+        // whose header checksum is the first byte of the frame
+        for fake in [&b"\x04\x22\x4D\x18"[..], b"\x04\x22\x4D\x18\x60\x40"] {
+            let mut input = b"garbage".to_vec();
+            input.extend_from_slice(fake);
+            input.extend_from_slice(&frame);
+
+            let mut dec = lz4_flex::frame::FrameDecoder::new(&input[..]);
+            assert!(dec.read_to_end(&mut Vec::new()).is_err());
+            assert!(dec.recover_next_frame().unwrap());
+            let mut uncompressed = Vec::new();
+            dec.read_to_end(&mut uncompressed).unwrap();
+            assert_eq!(uncompressed, COMPRESSION1K);
+            assert_eq!(dec.compressed_bytes_consumed(), input.len() as u64);
+        }
+    }
+
+    #[test]
+    fn stop_after_frame() {
+        let mut input = lz4_flex_frame_compress_with(
+            lz4_flex::frame::FrameInfo::new().content_checksum(true),
+            COMPRESSION1K,
+        )
+        .unwrap();
+        let frame_len = input.len();
+        // pseudo random trailing garbage
+        let mut state = 0x1234_5678u32;
+        input.extend((0..1000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }));
+
+        // the end of a frame is reported as EOF, but the next read tries to read the next frame
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&input[..]);
+        dec.read_to_end(&mut Vec::new()).unwrap();
+        assert!(dec.read(&mut [0u8; 16]).is_err());
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&input[..]);
+        dec.set_stop_after_frame(true);
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION1K);
+        assert_eq!(dec.read(&mut [0u8; 16]).unwrap(), 0);
+        assert_eq!(dec.verify_consumed(), Some(true));
+        assert_eq!(dec.compressed_bytes_consumed(), frame_len as u64);
+        // the trailing bytes are left in the reader
+        assert_eq!(dec.get_ref().len(), 1000);
+    }
+
+    #[test]
+    fn block_boundary_callback() {
+        use std::sync::{Arc, Mutex};
+        let input = &COMPRESSION10MB[..300_000];
+        let frame_info = lz4_flex::frame::FrameInfo::new().block_size(BlockSize::Max64KB);
+        let compressed = lz4_flex_frame_compress_with(frame_info, input).unwrap();
+
+        let boundaries = Arc::new(Mutex::new(Vec::new()));
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+        let callback_boundaries = boundaries.clone();
+        dec.set_block_boundary_callback(move |offset| {
+            callback_boundaries.lock().unwrap().push(offset)
+        });
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, input);
+        let expected: Vec<u64> = (1..=4)
+            .map(|i| i * 64 * 1024)
+            .chain([input.len() as u64])
+            .collect();
+        assert_eq!(*boundaries.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn dict_id() {
+        let frame_info = lz4_flex::frame::FrameInfo::new()
+            .dict_id(Some(0xDEAD_BEEF))
+            .block_size(BlockSize::Max64KB)
+            .content_checksum(true);
+        let compressed = lz4_flex_frame_compress_with(frame_info.clone(), COMPRESSION34K).unwrap();
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+        let err = dec.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            err.into_inner().unwrap().downcast_ref(),
+            Some(lz4_flex::frame::Error::DictionaryNotSupported)
+        ));
+
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+        assert_eq!(dec.frame_info(), None);
+        dec.set_ignore_dict_id(true);
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION34K);
+        assert_eq!(dec.frame_info(), Some(&frame_info));
+        assert_eq!(dec.frame_info().unwrap().dict_id, Some(0xDEAD_BEEF));
+    }
+
+    #[test]
+    fn read_block_now() {
+        let input = &COMPRESSION10MB[..200 * 1024];
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            let frame_info = lz4_flex::frame::FrameInfo::new()
+                .block_size(BlockSize::Max64KB)
+                .block_mode(block_mode)
+                .content_checksum(true);
+            let compressed = lz4_flex_frame_compress_with(frame_info, input).unwrap();
+            let frames = [&compressed[..], &compressed[..]].concat();
+
+            let mut dec = lz4_flex::frame::FrameDecoder::new(&frames[..]);
+            for _ in 0..2 {
+                for expected in input.chunks(64 * 1024) {
+                    assert_eq!(dec.read_block_now().unwrap(), Some(expected));
+                }
+                // end of the frame
+                assert_eq!(dec.read_block_now().unwrap(), None);
+            }
+            assert_eq!(dec.read_block_now().unwrap(), None);
+            assert_eq!(dec.verify_consumed(), Some(true));
+
+            // The rest of a block partially read with `read`
+            let mut dec = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+            let mut buf = [0u8; 1000];
+            dec.read_exact(&mut buf).unwrap();
+            let rest = dec.read_block_now().unwrap().unwrap();
+            assert_eq!(rest, &input[1000..64 * 1024]);
+            let next = dec.read_block_now().unwrap().unwrap();
+            assert_eq!(next, &input[64 * 1024..128 * 1024]);
+        }
+    }
+
+    #[test]
+    fn checksum_only_full_blocks() {
+        use lz4_flex::frame::{FrameDecoder, FrameEncoder, FrameInfo};
+        // Full blocks of compressible and incompressible data, with small flushes in between
+        let mut incompressible = vec![0u8; 64 * 1024];
+        let mut state = 0x2545_f491_u32;
+        for byte in &mut incompressible {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        let parts: [&[u8]; 5] = [
+            &COMPRESSION10MB[..64 * 1024],
+            b"small flush",
+            &incompressible,
+            &COMPRESSION10MB[..100],
+            &COMPRESSION10MB[..200 * 1024],
+        ];
+        let expected = parts.concat();
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            let frame_info = FrameInfo::new()
+                .block_size(BlockSize::Max64KB)
+                .block_mode(block_mode)
+                .block_checksums(true);
+            let encode = |only_full_blocks: bool| {
+                let mut enc = FrameEncoder::with_frame_info(frame_info.clone(), Vec::new());
+                enc.set_checksum_only_full_blocks(only_full_blocks);
+                for part in parts {
+                    enc.write_all(part).unwrap();
+                    enc.flush().unwrap();
+                }
+                enc.finish_with_stats().unwrap()
+            };
+            let (standard, _) = encode(false);
+            let (compressed, stats) = encode(true);
+            // 2 flushed blocks and the last block of `COMPRESSION10MB[..200 * 1024]` are partial
+            assert_eq!(stats.blocks, 8);
+            assert_eq!(compressed.len(), standard.len() - 3 * 4);
+
+            let mut dec = FrameDecoder::new(&compressed[..]);
+            dec.set_expect_checksum_only_full_blocks(true);
+            let mut uncompressed = Vec::new();
+            dec.read_to_end(&mut uncompressed).unwrap();
+            assert_eq!(uncompressed, expected);
+
+            // Not a standard frame
+            let mut dec = FrameDecoder::new(&compressed[..]);
+            assert!(dec.read_to_end(&mut Vec::new()).is_err());
+            // Standard frames can't be decoded with the setting
+            let mut dec = FrameDecoder::new(&standard[..]);
+            dec.set_expect_checksum_only_full_blocks(true);
+            assert!(dec.read_to_end(&mut Vec::new()).is_err());
+
+            // The checksum of the first (full, compressed) block is still verified
+            let mut corrupted = compressed.clone();
+            let first_block_len = u32::from_le_bytes(corrupted[7..11].try_into().unwrap()) as usize;
+            assert!(first_block_len < 64 * 1024);
+            corrupted[11 + first_block_len] ^= 1;
+            let mut dec = FrameDecoder::new(&corrupted[..]);
+            dec.set_expect_checksum_only_full_blocks(true);
+            let err = dec.read_to_end(&mut Vec::new()).unwrap_err();
+            assert!(matches!(
+                err.into_inner()
+                    .unwrap()
+                    .downcast_ref::<lz4_flex::frame::Error>(),
+                Some(lz4_flex::frame::Error::BlockChecksumError)
+            ));
+        }
+    }
+
+    #[test]
+    fn write_precompressed_block() {
+        use lz4_flex::frame::{FrameDecoder, FrameEncoder, FrameInfo};
+        let blocks: Vec<&[u8]> = COMPRESSION10MB[..200 * 1024].chunks(64 * 1024).collect();
+        let expected = [&blocks[..], &[b"written after".as_slice()]]
+            .concat()
+            .concat();
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            for checksums in [false, true] {
+                let frame_info = FrameInfo::new()
+                    .block_size(BlockSize::Max64KB)
+                    .block_mode(block_mode)
+                    .block_checksums(checksums)
+                    .content_checksum(checksums);
+                let mut enc = FrameEncoder::with_frame_info(frame_info, Vec::new());
+                // Buffered data is written as a block before the precompressed ones
+                enc.write_all(&blocks[0][..100]).unwrap();
+                enc.write_precompressed_block(&compress_block(&blocks[0][100..]), 64 * 1024 - 100)
+                    .unwrap();
+                for block in &blocks[1..] {
+                    enc.write_precompressed_block(&compress_block(block), block.len())
+                        .unwrap();
+                }
+                enc.write_all(b"written after").unwrap();
+                let (compressed, stats) = enc.finish_with_stats().unwrap();
+                assert_eq!(stats.blocks, 6);
+                assert_eq!(stats.input_bytes, expected.len() as u64);
+                assert_eq!(stats.output_bytes, compressed.len() as u64);
+
+                let mut uncompressed = Vec::new();
+                FrameDecoder::new(&compressed[..])
+                    .read_to_end(&mut uncompressed)
+                    .unwrap();
+                assert_eq!(uncompressed, expected);
+                assert_eq!(lz4_cpp_frame_decompress(&compressed).unwrap(), expected);
+            }
+        }
+
+        // With a content checksum, the block is validated before anything is written
+        let frame_info = FrameInfo::new().content_checksum(true);
+        let mut enc = FrameEncoder::with_frame_info(frame_info, Vec::new());
+        let block = compress_block(b"hello hello hello hello");
+        assert!(enc.write_precompressed_block(&block, 10).is_err());
+        assert!(enc.write_precompressed_block(&block[..3], 23).is_err());
+        assert!(enc
+            .write_precompressed_block(&block, 128 * 1024 * 1024)
+            .is_err());
+        enc.write_precompressed_block(&block, 23).unwrap();
+        let compressed = enc.finish().unwrap();
+        assert_eq!(
+            lz4_flex_frame_decompress(&compressed).unwrap(),
+            b"hello hello hello hello"
+        );
+    }
+
+    #[test]
+    fn into_inner_at_frame_end() {
+        let mut stream = Vec::new();
+        for frame_info in [
+            lz4_flex::frame::FrameInfo::new(),
+            lz4_flex::frame::FrameInfo::new()
+                .block_checksums(true)
+                .content_checksum(true),
+        ] {
+            stream.extend(lz4_flex_frame_compress_with(frame_info, COMPRESSION34K).unwrap());
+            stream.extend_from_slice(b"application data");
+        }
+
+        let mut reader = &stream[..];
+        for _ in 0..2 {
+            let mut dec = lz4_flex::frame::FrameDecoder::new(reader);
+            // not at the end of a frame yet
+            dec = dec.into_inner_at_frame_end().unwrap_err();
+            let mut buf = [0u8; 100];
+            dec.read_exact(&mut buf).unwrap();
+            dec = dec.into_inner_at_frame_end().unwrap_err();
+
+            let mut uncompressed = buf.to_vec();
+            dec.read_to_end(&mut uncompressed).unwrap();
+            assert_eq!(uncompressed, COMPRESSION34K);
+            reader = dec.into_inner_at_frame_end().unwrap();
+            let mut app_data = [0u8; 16];
+            reader.read_exact(&mut app_data).unwrap();
+            assert_eq!(&app_data, b"application data");
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn auto_finish() {
+        let mut compressed = Vec::new();
+        {
+            let mut enc = lz4_flex::frame::FrameEncoder::new(&mut compressed).auto_finish();
+            enc.write_all(COMPRESSION66JSON).unwrap();
+            // dropped without finishing
+        }
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, COMPRESSION66JSON);
+        assert!(dec.get_ref().is_empty());
+
+        // without writes, the drop still produces an (empty) frame
+        let mut compressed = Vec::new();
+        drop(lz4_flex::frame::FrameEncoder::new(&mut compressed).auto_finish());
+        assert_eq!(lz4_flex_frame_decompress(&compressed).unwrap(), b"");
+    }
+
+    #[test]
+    fn min_compression_gain() {
+        // pseudo random data with a short compressible section, which saves a few hundred bytes
+        let mut state = 0x1234_5678u32;
+        let mut input: Vec<u8> = (0..30_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        input[10_000..10_500].fill(0);
+
+        let compress = |min_compression_gain| {
+            let mut enc = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            enc.set_min_compression_gain(min_compression_gain);
+            enc.write_all(&input).unwrap();
+            enc.finish_with_stats().unwrap()
+        };
+        let (compressed, stats) = compress(0);
+        assert_eq!((stats.blocks, stats.raw_blocks), (1, 0));
+        assert_eq!(lz4_flex_frame_decompress(&compressed).unwrap(), input);
+
+        let (compressed, stats) = compress(1000);
+        assert_eq!((stats.blocks, stats.raw_blocks), (1, 1));
+        assert_eq!(lz4_flex_frame_decompress(&compressed).unwrap(), input);
+    }
+
+    #[test]
+    fn with_seekable() {
+        use lz4_flex::frame::{FrameEncoder, FrameInfo};
+        use std::io::Cursor;
+        for block_mode in [BlockMode::Independent, BlockMode::Linked, BlockMode::Auto] {
+            let frame_info = FrameInfo::new()
+                .block_size(BlockSize::Max64KB)
+                .block_mode(block_mode)
+                .content_checksum(true);
+            let mut enc = FrameEncoder::with_seekable(frame_info, Cursor::new(Vec::new()));
+            enc.write_all(COMPRESSION66JSON).unwrap();
+            let cursor = enc.finish().unwrap();
+            assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+            let compressed = cursor.into_inner();
+
+            // FLG byte declares the content size, which follows the FLG and BD bytes
+            assert_ne!(compressed[4] & 0b0000_1000, 0);
+            let content_size = u64::from_le_bytes(compressed[6..14].try_into().unwrap());
+            assert_eq!(content_size, COMPRESSION66JSON.len() as u64);
+            // decoding verifies the header checksum
+            assert_eq!(
+                lz4_flex_frame_decompress(&compressed).unwrap(),
+                COMPRESSION66JSON
+            );
+        }
+
+        // every frame of the encoder declares its own size
+        let mut enc = FrameEncoder::with_seekable(FrameInfo::new(), Cursor::new(Vec::new()));
+        enc.write_all(COMPRESSION1K).unwrap();
+        enc.try_finish().unwrap();
+        let first_frame_len = enc.get_ref().position() as usize;
+        enc.write_all(COMPRESSION34K).unwrap();
+        let compressed = enc.finish().unwrap().into_inner();
+        let second_frame = &compressed[first_frame_len..];
+        let content_size = u64::from_le_bytes(second_frame[6..14].try_into().unwrap());
+        assert_eq!(content_size, COMPRESSION34K.len() as u64);
+        assert_eq!(
+            lz4_flex_frame_decompress(&compressed[..first_frame_len]).unwrap(),
+            COMPRESSION1K
+        );
+        assert_eq!(
+            lz4_flex_frame_decompress(second_frame).unwrap(),
+            COMPRESSION34K
+        );
+    }
+
+    #[test]
+    fn finish_with_stats() {
+        use lz4_flex::frame::{EncodeStats, FrameEncoder, FrameInfo};
+        // a compressible block followed by an incompressible one
+        let mut input = COMPRESSION66JSON[..64 * 1024].to_vec();
+        let mut state = 0x1234_5678u32;
+        input.extend((0..1000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }));
+        let frame_info = FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_checksums(true)
+            .content_checksum(true);
+        let mut enc = FrameEncoder::with_frame_info(frame_info, Vec::new());
+        enc.write_all(&input).unwrap();
+        let (compressed, stats) = enc.finish_with_stats().unwrap();
+        assert_eq!(
+            stats,
+            EncodeStats {
+                input_bytes: input.len() as u64,
+                output_bytes: compressed.len() as u64,
+                blocks: 2,
+                raw_blocks: 1,
+            }
+        );
+
+        // the totals cover all frames of the encoder
+        let mut enc = FrameEncoder::new(Vec::new());
+        enc.write_all(COMPRESSION1K).unwrap();
+        enc.try_finish().unwrap();
+        enc.write_all(COMPRESSION1K).unwrap();
+        let (compressed, stats) = enc.finish_with_stats().unwrap();
+        assert_eq!(stats.input_bytes, 2 * COMPRESSION1K.len() as u64);
+        assert_eq!(stats.output_bytes, compressed.len() as u64);
+        assert_eq!(stats.blocks, 2);
+        assert_eq!(stats.raw_blocks, 0);
+    }
+
+    #[test]
+    fn buffered_len() {
+        let input = COMPRESSION66JSON;
+        let compressed = lz4_flex_frame_compress_with(
+            lz4_flex::frame::FrameInfo::new().block_size(BlockSize::Max64KB),
+            input,
+        )
+        .unwrap();
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&compressed[..]);
+        assert_eq!(dec.buffered_len(), 0);
+        assert!(dec.peek().is_empty());
+
+        // reading decompresses the whole first block
+        let mut buf = [0u8; 10];
+        dec.read_exact(&mut buf).unwrap();
+        let block_size = 64 * 1024;
+        assert_eq!(dec.buffered_len(), block_size - 10);
+        assert_eq!(dec.peek(), &input[10..block_size]);
+
+        // the buffered bytes are read without touching the reader
+        let remaining = dec.get_ref().len();
+        let mut buf = vec![0u8; dec.buffered_len()];
+        dec.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, &input[10..block_size]);
+        assert_eq!(dec.get_ref().len(), remaining);
+        assert_eq!(dec.buffered_len(), 0);
+    }
+
+    #[test]
+    fn frame_info_hash_eq() {
+        use lz4_flex::frame::FrameInfo;
+        use std::collections::HashSet;
+
+        let frame_info = || {
+            FrameInfo::new()
+                .block_size(BlockSize::Max256KB)
+                .block_mode(BlockMode::Linked)
+                .content_size(Some(42))
+        };
+        let mut set = HashSet::new();
+        assert!(set.insert(frame_info()));
+        assert!(!set.insert(frame_info()));
+        assert!(set.insert(frame_info().content_size(None)));
+        assert!(set.insert(frame_info().block_mode(BlockMode::Independent)));
+        assert_eq!(set.len(), 3);
+        assert_eq!(frame_info(), frame_info());
+        assert_ne!(frame_info(), FrameInfo::new());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn frame_info_serde() {
+        use lz4_flex::frame::FrameInfo;
+
+        let frame_info = FrameInfo::new()
+            .block_size(BlockSize::Max256KB)
+            .block_mode(BlockMode::Linked)
+            .content_size(Some(42))
+            .dict_id(Some(7))
+            .content_checksum(true);
+        let json = serde_json::to_string(&frame_info).unwrap();
+        let deserialized: FrameInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, frame_info);
+
+        let json = serde_json::to_string(&FrameInfo::new()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<FrameInfo>(&json).unwrap(),
+            FrameInfo::new()
+        );
+
+        // the deserialized settings are used like any other
+        let compressed = lz4_flex_frame_compress_with(
+            serde_json::from_str(&serde_json::to_string(&frame_info.dict_id(None)).unwrap())
+                .unwrap(),
+            &COMPRESSION34K[..42],
+        )
+        .unwrap();
+        assert_eq!(
+            lz4_flex_frame_decompress(&compressed).unwrap(),
+            &COMPRESSION34K[..42]
+        );
+    }
+
+    #[test]
+    fn concat_frames() {
+        use lz4_flex::frame::FrameInfo;
+        let compress = |frame_info: &FrameInfo, input: &[u8]| {
+            lz4_flex_frame_compress_with(frame_info.clone(), input).unwrap()
+        };
+        let frame_info = FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_checksums(true)
+            .content_checksum(true)
+            .content_size(Some(0));
+        let parts: [&[u8]; 3] = [COMPRESSION66JSON, b"", COMPRESSION34K];
+        let frames: Vec<Vec<u8>> = parts
+            .iter()
+            .map(|part| {
+                let frame_info = frame_info.clone().content_size(Some(part.len() as u64));
+                compress(&frame_info, part)
+            })
+            .collect();
+        let frames: Vec<&[u8]> = frames.iter().map(|frame| &frame[..]).collect();
+
+        let mut combined = Vec::new();
+        lz4_flex::frame::concat_frames(&frames, &mut combined).unwrap();
+        let mut expected = COMPRESSION66JSON.to_vec();
+        expected.extend_from_slice(COMPRESSION34K);
+        // a single frame, which is verified against the combined content size and checksum
+        let mut dec = lz4_flex::frame::FrameDecoder::new(&combined[..]);
+        let mut uncompressed = Vec::new();
+        dec.read_to_end(&mut uncompressed).unwrap();
+        assert_eq!(uncompressed, expected);
+        assert_eq!(dec.verify_consumed(), Some(true));
+        assert_eq!(dec.compressed_bytes_consumed(), combined.len() as u64);
+
+        // without content size and checksum
+        let frame_info = FrameInfo::new().block_size(BlockSize::Max64KB);
+        let frames = [
+            compress(&frame_info, COMPRESSION1K),
+            compress(
+                &frame_info.clone().content_size(Some(10)),
+                &COMPRESSION1K[..10],
+            ),
+        ];
+        let mut combined = Vec::new();
+        lz4_flex::frame::concat_frames(&[&frames[0], &frames[1]], &mut combined).unwrap();
+        let mut expected = COMPRESSION1K.to_vec();
+        expected.extend_from_slice(&COMPRESSION1K[..10]);
+        assert_eq!(lz4_flex_frame_decompress(&combined).unwrap(), expected);
+
+        // incompatible frames
+        let linked = compress(
+            &frame_info.clone().block_mode(BlockMode::Linked),
+            COMPRESSION1K,
+        );
+        let other_size = compress(
+            &frame_info.clone().block_size(BlockSize::Max256KB),
+            COMPRESSION1K,
+        );
+        for incompatible in [&linked, &other_size] {
+            let result = lz4_flex::frame::concat_frames(&[&frames[0], incompatible], Vec::new());
+            assert!(matches!(
+                result,
+                Err(lz4_flex::frame::Error::IncompatibleFrames)
+            ));
+        }
+
+        // truncated frame
+        let truncated = &frames[0][..frames[0].len() - 1];
+        assert!(lz4_flex::frame::concat_frames(&[truncated], Vec::new()).is_err());
+
+        assert!(lz4_flex::frame::concat_frames(&[], Vec::new()).is_err());
+
+        // the content sizes overflow when added up
+        let mut huge = compress(&frame_info.clone().content_size(Some(0)), b"");
+        huge[6..14].copy_from_slice(&u64::MAX.to_le_bytes());
+        let mut hasher = twox_hash::XxHash32::with_seed(0);
+        std::hash::Hasher::write(&mut hasher, &huge[4..14]);
+        huge[14] = (std::hash::Hasher::finish(&hasher) >> 8) as u8;
+        assert!(lz4_flex::frame::concat_frames(&[&huge], Vec::new()).is_ok());
+        assert!(lz4_flex::frame::concat_frames(&[&huge, &huge], Vec::new()).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_roundtrip() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_util::compat::{
+            FuturesAsyncReadCompatExt, FuturesAsyncWriteCompatExt, TokioAsyncReadCompatExt,
+            TokioAsyncWriteCompatExt,
+        };
+
+        for block_mode in [BlockMode::Independent, BlockMode::Linked] {
+            let frame_info = lz4_flex::frame::FrameInfo::new()
+                .block_size(BlockSize::Max64KB)
+                .block_mode(block_mode)
+                .block_checksums(true)
+                .content_checksum(true);
+            // A small pipe buffer makes both sides wait for each other.
+            let (writer, reader) = tokio::io::duplex(100);
+
+            let encode = async move {
+                let mut enc = lz4_flex::frame::AsyncFrameEncoder::with_frame_info(
+                    frame_info,
+                    writer.compat_write(),
+                )
+                .compat_write();
+                for chunk in COMPRESSION66JSON.chunks(10_000) {
+                    enc.write_all(chunk).await.unwrap();
+                }
+                enc.shutdown().await.unwrap();
+            };
+            let decode = async move {
+                let mut dec = lz4_flex::frame::AsyncFrameDecoder::new(reader.compat()).compat();
+                let mut uncompressed = Vec::new();
+                dec.read_to_end(&mut uncompressed).await.unwrap();
+                uncompressed
+            };
+            let ((), uncompressed) = tokio::join!(encode, decode);
+            assert_eq!(uncompressed, COMPRESSION66JSON);
+        }
+
+        // matches the synchronous encoder and decoder
+        let mut compressed = Vec::new();
+        let mut enc = lz4_flex::frame::AsyncFrameEncoder::new(&mut compressed).compat_write();
+        enc.write_all(COMPRESSION34K).await.unwrap();
+        enc.shutdown().await.unwrap();
+        assert_eq!(
+            compressed,
+            lz4_flex_frame_compress_with(Default::default(), COMPRESSION34K).unwrap()
+        );
+        let mut uncompressed = Vec::new();
+        lz4_flex::frame::AsyncFrameDecoder::new(&compressed[..])
+            .compat()
+            .read_to_end(&mut uncompressed)
+            .await
+            .unwrap();
+        assert_eq!(uncompressed, COMPRESSION34K);
+
+        // truncated input
+        let compressed =
+            lz4_flex_frame_compress_with(Default::default(), COMPRESSION66JSON).unwrap();
+        let mut dec = lz4_flex::frame::AsyncFrameDecoder::new(&compressed[..1000]).compat();
+        assert!(dec.read_to_end(&mut Vec::new()).await.is_err());
+    }
+
+    #[test]
+    fn non_blocking_reader() {
+        /// Returns `Ok(0)` or `WouldBlock` before each chunk of data.
+        struct NonBlockingReader<'a> {
+            chunks: std::slice::Chunks<'a, u8>,
+            reads: usize,
+        }
+        impl Read for NonBlockingReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.reads += 1;
+                match self.reads % 3 {
+                    1 => Ok(0),
+                    2 => Err(std::io::ErrorKind::WouldBlock.into()),
+                    _ => match self.chunks.next() {
+                        Some(chunk) => {
+                            buf[..chunk.len()].copy_from_slice(chunk);
+                            Ok(chunk.len())
+                        }
+                        None => Ok(0),
+                    },
+                }
+            }
+        }
+
+        let frame_info = lz4_flex::frame::FrameInfo::new()
+            .block_size(BlockSize::Max64KB)
+            .block_checksums(true)
+            .content_checksum(true);
+        let compressed = lz4_flex_frame_compress_with(frame_info, COMPRESSION66JSON).unwrap();
+
+        for chunk_size in [7, 1000, 8 * 1024] {
+            let mut dec = lz4_flex::frame::FrameDecoder::new_non_blocking(NonBlockingReader {
+                chunks: compressed.chunks(chunk_size),
+                reads: 0,
+            });
+            let mut uncompressed = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                match dec.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(read) => uncompressed.extend_from_slice(&buf[..read]),
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(err) => panic!("{err}"),
+                }
+            }
+            assert_eq!(uncompressed, COMPRESSION66JSON);
+            assert!(dec.at_frame_end());
+            assert_eq!(dec.read(&mut buf).unwrap(), 0);
+        }
+
+        // a stream ending inside of a frame isn't taken as EOF
+        let mut dec = lz4_flex::frame::FrameDecoder::new_non_blocking(&compressed[..1000]);
+        let err = dec.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        assert!(!dec.at_frame_end());
+    }
 }
 
 #[cfg(test)]